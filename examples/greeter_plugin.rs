@@ -0,0 +1,24 @@
+//! A plugin built as a dynamic library, which `tests/plugin.rs` loads with
+//! `load-plugin`.
+
+use lisp_rs::{LispError, Plugin, eval::Env, parser::Object};
+
+struct Greeter;
+
+impl Plugin for Greeter {
+    fn name(&self) -> &str {
+        "greeter"
+    }
+
+    fn register(&self, env: &mut Env) {
+        env.register_native("greet", |args| match args {
+            [Object::String(name)] => Ok(Object::String(format!("hello, {}", name))),
+            _ => Err(LispError::Eval("greet expects a name".to_string())),
+        });
+    }
+}
+
+#[unsafe(no_mangle)]
+pub fn lisp_rs_plugin() -> Box<dyn Plugin> {
+    Box::new(Greeter)
+}
@@ -0,0 +1,384 @@
+use std::{cmp::Ordering, fmt};
+
+const BASE: u64 = 1 << 32;
+
+/// Arbitrary precision signed integer stored as little-endian base 2^32 limbs.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    mag: Vec<u32>,
+}
+
+impl BigInt {
+    pub fn zero() -> Self {
+        BigInt {
+            negative: false,
+            mag: Vec::new(),
+        }
+    }
+
+    pub fn from_i64(n: i64) -> Self {
+        let mut mag = Vec::new();
+        let mut m = n.unsigned_abs();
+        while m > 0 {
+            mag.push(m as u32);
+            m >>= 32;
+        }
+        BigInt {
+            negative: n < 0,
+            mag,
+        }
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.mag.len() > 2 {
+            return None;
+        }
+        let m = self
+            .mag
+            .iter()
+            .rev()
+            .fold(0u64, |acc, &limb| (acc << 32) | limb as u64);
+        if self.negative {
+            if m <= i64::MAX as u64 + 1 {
+                Some((m as i64).wrapping_neg())
+            } else {
+                None
+            }
+        } else {
+            i64::try_from(m).ok()
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let m = self
+            .mag
+            .iter()
+            .rev()
+            .fold(0.0, |acc, &limb| acc * BASE as f64 + limb as f64);
+        if self.negative { -m } else { m }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::parse_radix(s, 10)
+    }
+
+    pub fn parse_radix(s: &str, radix: u32) -> Option<Self> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        if digits.is_empty() {
+            return None;
+        }
+
+        let mut mag = Vec::new();
+        for c in digits.chars() {
+            mul_small(&mut mag, radix);
+            add_small(&mut mag, c.to_digit(radix)?);
+        }
+        Some(BigInt { negative, mag }.normalize())
+    }
+
+    /// Formats the number in base `radix` (2 to 36) with lowercase digits.
+    pub fn to_string_radix(&self, radix: u32) -> String {
+        if self.is_zero() {
+            return "0".to_string();
+        }
+
+        let mut digits = Vec::new();
+        let mut mag = self.mag.clone();
+        while !mag.is_empty() {
+            let digit = div_small(&mut mag, radix);
+            digits.push(char::from_digit(digit, radix).expect("digit is below radix"));
+        }
+        if self.negative {
+            digits.push('-');
+        }
+        digits.iter().rev().collect()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mag.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn abs(&self) -> Self {
+        BigInt {
+            negative: false,
+            mag: self.mag.clone(),
+        }
+    }
+
+    pub fn neg(&self) -> Self {
+        BigInt {
+            negative: !self.negative,
+            mag: self.mag.clone(),
+        }
+        .normalize()
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        if self.negative == other.negative {
+            return BigInt {
+                negative: self.negative,
+                mag: add_mag(&self.mag, &other.mag),
+            }
+            .normalize();
+        }
+        match cmp_mag(&self.mag, &other.mag) {
+            Ordering::Equal => BigInt::zero(),
+            Ordering::Greater => BigInt {
+                negative: self.negative,
+                mag: sub_mag(&self.mag, &other.mag),
+            }
+            .normalize(),
+            Ordering::Less => BigInt {
+                negative: other.negative,
+                mag: sub_mag(&other.mag, &self.mag),
+            }
+            .normalize(),
+        }
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        let mut mag = vec![0u32; self.mag.len() + other.mag.len()];
+        for (i, &a) in self.mag.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.mag.iter().enumerate() {
+                let cur = mag[i + j] as u64 + a as u64 * b as u64 + carry;
+                mag[i + j] = cur as u32;
+                carry = cur >> 32;
+            }
+            mag[i + other.mag.len()] = carry as u32;
+        }
+        BigInt {
+            negative: self.negative != other.negative,
+            mag,
+        }
+        .normalize()
+    }
+
+    /// Truncating division, returning `(quotient, remainder)` with the
+    /// remainder taking the sign of the dividend. Returns `None` on division
+    /// by zero.
+    pub fn div_rem(&self, other: &Self) -> Option<(Self, Self)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (q, r) = div_rem_mag(&self.mag, &other.mag);
+        let quotient = BigInt {
+            negative: self.negative != other.negative,
+            mag: q,
+        }
+        .normalize();
+        let remainder = BigInt {
+            negative: self.negative,
+            mag: r,
+        }
+        .normalize();
+        Some((quotient, remainder))
+    }
+
+    /// Greatest common divisor, always non-negative.
+    pub fn gcd(&self, other: &Self) -> Self {
+        let mut a = self.abs();
+        let mut b = other.abs();
+        while !b.is_zero() {
+            let (_, r) = a.div_rem(&b).expect("divisor is non-zero");
+            a = b;
+            b = r;
+        }
+        a
+    }
+
+    fn normalize(mut self) -> Self {
+        while self.mag.last() == Some(&0) {
+            self.mag.pop();
+        }
+        if self.mag.is_empty() {
+            self.negative = false;
+        }
+        self
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.mag, &other.mag),
+            (true, true) => cmp_mag(&other.mag, &self.mag),
+        }
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        let mut chunks = Vec::new();
+        let mut mag = self.mag.clone();
+        while !mag.is_empty() {
+            chunks.push(div_small(&mut mag, 1_000_000_000));
+        }
+
+        if self.negative {
+            write!(f, "-")?;
+        }
+        let mut iter = chunks.iter().rev();
+        if let Some(first) = iter.next() {
+            write!(f, "{}", first)?;
+        }
+        for chunk in iter {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..a.len().max(b.len()) {
+        let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    }
+    if carry > 0 {
+        result.push(carry as u32);
+    }
+    result
+}
+
+/// Subtracts `b` from `a`, assuming `|a| >= |b|`.
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &limb) in a.iter().enumerate() {
+        let mut diff = limb as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        if diff < 0 {
+            diff += BASE as i64;
+            borrow = 1;
+        } else {
+            borrow = 0;
+        }
+        result.push(diff as u32);
+    }
+    result
+}
+
+fn mul_small(mag: &mut Vec<u32>, m: u32) {
+    let mut carry = 0u64;
+    for limb in mag.iter_mut() {
+        let cur = *limb as u64 * m as u64 + carry;
+        *limb = cur as u32;
+        carry = cur >> 32;
+    }
+    if carry > 0 {
+        mag.push(carry as u32);
+    }
+}
+
+fn add_small(mag: &mut Vec<u32>, n: u32) {
+    let mut carry = n as u64;
+    for limb in mag.iter_mut() {
+        if carry == 0 {
+            return;
+        }
+        let cur = *limb as u64 + carry;
+        *limb = cur as u32;
+        carry = cur >> 32;
+    }
+    if carry > 0 {
+        mag.push(carry as u32);
+    }
+}
+
+/// Divides `mag` in place by `d`, returning the remainder.
+fn div_small(mag: &mut Vec<u32>, d: u32) -> u32 {
+    let mut rem = 0u64;
+    for limb in mag.iter_mut().rev() {
+        let cur = (rem << 32) | *limb as u64;
+        *limb = (cur / d as u64) as u32;
+        rem = cur % d as u64;
+    }
+    while mag.last() == Some(&0) {
+        mag.pop();
+    }
+    rem as u32
+}
+
+fn div_rem_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_mag(a, b) == Ordering::Less {
+        return (Vec::new(), a.to_vec());
+    }
+    if b.len() == 1 {
+        let mut q = a.to_vec();
+        let r = div_small(&mut q, b[0]);
+        return (q, if r == 0 { Vec::new() } else { vec![r] });
+    }
+
+    // Binary long division; slow for huge operands but simple and exact.
+    let mut quotient = vec![0u32; a.len()];
+    let mut rem: Vec<u32> = Vec::new();
+    for i in (0..a.len() * 32).rev() {
+        mul_small(&mut rem, 2);
+        if (a[i / 32] >> (i % 32)) & 1 == 1 {
+            add_small(&mut rem, 1);
+        }
+        if cmp_mag(&rem, b) != Ordering::Less {
+            rem = sub_mag(&rem, b);
+            while rem.last() == Some(&0) {
+                rem.pop();
+            }
+            quotient[i / 32] |= 1 << (i % 32);
+        }
+    }
+    while quotient.last() == Some(&0) {
+        quotient.pop();
+    }
+    (quotient, rem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bigint_arithmetic() {
+        let a = BigInt::from_i64(99999999999);
+        let product = a.mul(&a);
+        assert_eq!("9999999999800000000001", product.to_string());
+
+        let (q, r) = product.div_rem(&BigInt::from_i64(-7)).unwrap();
+        assert_eq!("-1428571428542857142857", q.to_string());
+        assert_eq!(BigInt::from_i64(2), r);
+        assert_eq!(product, BigInt::parse("9999999999800000000001").unwrap());
+        assert_eq!(Some(i64::MIN), BigInt::from_i64(i64::MIN).to_i64());
+        assert_eq!("-ff", BigInt::from_i64(-255).to_string_radix(16));
+        assert_eq!("1010", BigInt::from_i64(10).to_string_radix(2));
+    }
+}
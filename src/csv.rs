@@ -0,0 +1,96 @@
+/// Splits CSV text into rows of fields. Fields may be quoted, with `""`
+/// standing for a literal quote, and quoted fields may span lines. Blank
+/// lines are skipped.
+pub fn parse(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut chars = text.chars().peekable();
+
+    // Whether the current line has content, so blank lines can be dropped.
+    let mut started = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() => {
+                started = true;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => field.push(c),
+                        None => return Err("read-csv: unterminated quoted field".to_string()),
+                    }
+                }
+                if !matches!(chars.peek(), None | Some(',' | '\n' | '\r')) {
+                    return Err("read-csv: unexpected text after quoted field".to_string());
+                }
+            }
+            ',' => {
+                started = true;
+                row.push(std::mem::take(&mut field));
+            }
+            '\r' | '\n' => {
+                if c == '\r' {
+                    chars.next_if_eq(&'\n');
+                }
+                if started {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+                started = false;
+            }
+            c => {
+                started = true;
+                field.push(c);
+            }
+        }
+    }
+    if started {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Formats one row as a CSV line, quoting fields that need it.
+pub fn format_row(fields: &[String]) -> String {
+    let fields: Vec<_> = fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect();
+    format!("{}\n", fields.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_round_trip() {
+        let text = "name,note\r\nalice,\"says \"\"hi\"\"\"\n\nbob,\"a,b\nc\"\n,\n";
+        let rows = parse(text).unwrap();
+        assert_eq!(
+            vec![
+                vec!["name".to_string(), "note".to_string()],
+                vec!["alice".to_string(), "says \"hi\"".to_string()],
+                vec!["bob".to_string(), "a,b\nc".to_string()],
+                vec![String::new(), String::new()],
+            ],
+            rows
+        );
+
+        let written: String = rows.iter().map(|row| format_row(row)).collect();
+        assert_eq!(rows, parse(&written).unwrap());
+        assert!(parse("\"open").is_err());
+        assert!(parse("\"a\"b").is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use std::{error::Error, fmt, fs};
+
+use crate::{
+    LispError,
+    lexer::Span,
+    parser::{Object, Spanned},
+};
+
+/// Frames of a backtrace shown before the rest are elided.
+const MAX_FRAMES: usize = 16;
+
+/// An error together with the span of source it was raised from, if known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub error: LispError,
+    pub span: Option<Span>,
+}
+
+impl Diagnostic {
+    /// Formats the error for a terminal: the error kind and message, the
+    /// offending source line with the span underlined by carets, then the
+    /// backtrace. `origin` names the source, e.g. a file path. An error in a
+    /// `load`ed file is shown in that file instead.
+    pub fn render(&self, source: &str, origin: &str) -> String {
+        let mut out = match self.error.kind() {
+            "error" => format!("error: {}\n", self.error),
+            kind => format!("error[{}]: {}\n", kind, self.error),
+        };
+        if let Some((path, span)) = self.error.loaded_from() {
+            let source = fs::read_to_string(path).unwrap_or_default();
+            out += &render_span(*span, &source, path);
+        } else if let Some(span) = self.span {
+            out += &render_span(span, source, origin);
+        }
+        let backtrace = self.error.backtrace();
+        if !backtrace.is_empty() {
+            out += "backtrace:\n";
+            for (i, name) in backtrace.iter().take(MAX_FRAMES).enumerate() {
+                out += &format!("  {}: {}\n", i, name);
+            }
+            if backtrace.len() > MAX_FRAMES {
+                out += &format!("  ... {} more\n", backtrace.len() - MAX_FRAMES);
+            }
+        }
+        out
+    }
+}
+
+/// The line of `source` holding the start of `span`, with the span
+/// underlined.
+fn render_span(span: Span, source: &str, origin: &str) -> String {
+    let Some(line) = span.line.checked_sub(1).and_then(|i| source.lines().nth(i)) else {
+        return String::new();
+    };
+
+    // A span running past its first line is underlined to the line's end.
+    let end = if span.end_line == span.line {
+        span.end_column
+    } else {
+        line.chars().count() + 1
+    };
+    let gutter = " ".repeat(span.line.to_string().len());
+    let mut out = format!("{}--> {}:{}\n", gutter, origin, span);
+    out += &format!("{} |\n", gutter);
+    out += &format!("{} | {}\n", span.line, line);
+    out += &format!(
+        "{} | {}{}\n",
+        gutter,
+        " ".repeat(span.column.saturating_sub(1)),
+        "^".repeat(end.saturating_sub(span.column).max(1))
+    );
+    out
+}
+
+/// The span of the innermost of `forms`, those an error unwound through
+/// while evaluating `form`, one of `program`. The forms are followed down
+/// from `form`; one not within the form around it, such as the body of a
+/// procedure called there, is looked for in the rest of `program`. Forms
+/// found nowhere, such as macro expansions, are skipped.
+pub(crate) fn error_span(forms: &[Object], form: &Spanned, program: &[Spanned]) -> Span {
+    let (mut obj, mut spans) = (&form.object, &form.spans);
+    for target in forms.iter().rev() {
+        let found = spans.find(obj, target).or_else(|| {
+            program
+                .iter()
+                .find_map(|form| form.spans.find(&form.object, target))
+        });
+        if let Some(found) = found {
+            (obj, spans) = found;
+        }
+    }
+    spans.span
+}
+
+impl From<LispError> for Diagnostic {
+    fn from(error: LispError) -> Self {
+        Diagnostic { error, span: None }
+    }
+}
+
+impl Error for Diagnostic {}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.error.loaded_from(), self.span) {
+            (Some((path, span)), _) => write!(f, "{}:{}: {}", path, span, self.error),
+            (None, Some(span)) => write!(f, "{}: {}", span, self.error),
+            (None, None) => write!(f, "{}", self.error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Interpreter;
+
+    #[test]
+    fn test_diagnostic_render() {
+        let mut interp = Interpreter::new();
+        let source = "(define x 1)\n(define (f)\n  (+ x missing))\n(f)";
+        let err = interp.eval_source(source).unwrap_err();
+        assert_eq!(
+            concat!(
+                "error[unbound-variable]: Unbound symbol: missing\n",
+                " --> test.lisp:3:8\n",
+                "  |\n",
+                "3 |   (+ x missing))\n",
+                "  |        ^^^^^^^\n",
+                "backtrace:\n",
+                "  0: f\n",
+            ),
+            err.render(source, "test.lisp")
+        );
+
+        let source = "(car 1 #\\bogus)";
+        let err = interp.eval_source(source).unwrap_err();
+        assert_eq!(
+            concat!(
+                "error[syntax-error]: unexpected character: b\n",
+                " --> <repl>:1:8\n",
+                "  |\n",
+                "1 | (car 1 #\\bogus)\n",
+                "  |        ^^^^^^^\n",
+            ),
+            err.render(source, "<repl>")
+        );
+
+        let source = "(error \"oops\")";
+        let err = interp.eval_source(source).unwrap_err();
+        assert_eq!(
+            concat!(
+                "error: oops\n",
+                " --> <repl>:1:1\n",
+                "  |\n",
+                "1 | (error \"oops\")\n",
+                "  | ^^^^^^^^^^^^^^\n",
+            ),
+            err.render(source, "<repl>")
+        );
+    }
+
+    #[test]
+    fn test_diagnostic_sub_form() {
+        let mut interp = Interpreter::new();
+        let source = "(list '(nope) (+ 1 (car 5)) nope)";
+        let err = interp.eval_source(source).unwrap_err();
+        assert_eq!(
+            concat!(
+                "error[type-error]: car: not a pair: 5\n",
+                " --> <repl>:1:20\n",
+                "  |\n",
+                "1 | (list '(nope) (+ 1 (car 5)) nope)\n",
+                "  |                    ^^^^^^^\n",
+            ),
+            err.render(source, "<repl>")
+        );
+
+        let source = "(list '(nope) 'nope nope)";
+        let err = interp.eval_source(source).unwrap_err();
+        assert_eq!(Some(21), err.span.map(|span| span.column));
+    }
+
+    #[test]
+    fn test_diagnostic_loaded_file() {
+        let path = std::env::temp_dir().join(format!("lisp-rs-diag-{}.lisp", std::process::id()));
+        std::fs::write(&path, "(define y 1)\n(car y)\n").unwrap();
+        let path = path.to_str().unwrap();
+
+        let mut interp = Interpreter::new();
+        let source = format!("(load {:?})", path);
+        let err = interp.eval_source(&source).unwrap_err();
+        assert_eq!(format!("{}:2:1: car: not a pair: 1", path), err.to_string());
+        assert!(err.render(&source, "<repl>").contains("2 | (car y)\n"));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,223 @@
+use std::{error::Error, fmt};
+
+use crate::{lexer::Span, parser::Object};
+
+/// An error from reading or evaluating Lisp source.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LispError {
+    /// The source could not be tokenized or parsed.
+    ParseError(String),
+    /// A special form used with the wrong shape, e.g. `(let (x) x)`.
+    Syntax(String),
+    /// A symbol with no binding in scope.
+    UnboundSymbol(String),
+    /// A procedure or special form given the wrong number of arguments.
+    /// `expected` is `None` for forms without a single expected count.
+    ArityMismatch {
+        name: String,
+        expected: Option<String>,
+        got: usize,
+    },
+    /// An argument of the wrong type, e.g. `(car 1)`. `got` is the printed
+    /// argument.
+    TypeMismatch {
+        name: String,
+        expected: String,
+        got: String,
+    },
+    /// Exact division by zero.
+    DivideByZero,
+    /// A file that could not be opened.
+    FileError { path: String, reason: String },
+    /// An object passed to `raise`, unwinding to the nearest `guard`.
+    Raise(Object),
+    /// An escape continuation invoked with `value`, unwinding to the
+    /// `call/cc` that made it. Seen anywhere else, the continuation was used
+    /// after that `call/cc` returned.
+    Escape { continuation: usize, value: Object },
+    /// Any other failure, e.g. an index out of range.
+    Eval(String),
+    /// Another error together with where it arose.
+    Traced(Box<Traced>),
+}
+
+/// An error together with where it arose.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Traced {
+    pub error: LispError,
+    /// The innermost form whose evaluation raised the error, then the forms
+    /// being evaluated around it, outermost last.
+    pub forms: Vec<Object>,
+    /// The names of the procedures that were being applied, innermost
+    /// first.
+    pub backtrace: Vec<String>,
+    /// The file and span the error arose at, if it arose in a `load`ed
+    /// file.
+    pub loaded_from: Option<(String, Span)>,
+}
+
+impl LispError {
+    /// The error itself, without where it arose.
+    pub fn cause(&self) -> &LispError {
+        match self {
+            LispError::Traced(traced) => &traced.error,
+            error => error,
+        }
+    }
+
+    /// The innermost form whose evaluation raised the error, if known.
+    pub fn form(&self) -> Option<&Object> {
+        match self {
+            LispError::Traced(traced) => traced.forms.first(),
+            _ => None,
+        }
+    }
+
+    /// The file and span the error arose at, if it arose in a `load`ed
+    /// file.
+    pub fn loaded_from(&self) -> Option<&(String, Span)> {
+        match self {
+            LispError::Traced(traced) => traced.loaded_from.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// The forms being evaluated when the error was raised, innermost
+    /// first.
+    pub fn forms(&self) -> &[Object] {
+        match self {
+            LispError::Traced(traced) => &traced.forms,
+            _ => &[],
+        }
+    }
+
+    /// The procedures being applied when the error was raised, innermost
+    /// first.
+    pub fn backtrace(&self) -> &[String] {
+        match self {
+            LispError::Traced(traced) => &traced.backtrace,
+            _ => &[],
+        }
+    }
+
+    /// The condition kind `guard` sees this error as, e.g. `arity-error`.
+    pub fn kind(&self) -> &str {
+        match self {
+            LispError::ParseError(_) | LispError::Syntax(_) => "syntax-error",
+            LispError::UnboundSymbol(_) => "unbound-variable",
+            LispError::ArityMismatch { .. } => "arity-error",
+            LispError::TypeMismatch { .. } => "type-error",
+            LispError::DivideByZero => "divide-by-zero",
+            LispError::FileError { .. } => "file-error",
+            LispError::Raise(Object::Condition(condition)) => &condition.kind,
+            LispError::Raise(_) | LispError::Escape { .. } | LispError::Eval(_) => "error",
+            LispError::Traced(traced) => traced.error.kind(),
+        }
+    }
+}
+
+impl Error for LispError {}
+
+impl fmt::Display for LispError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LispError::ParseError(msg) | LispError::Syntax(msg) | LispError::Eval(msg) => {
+                write!(f, "{}", msg)
+            }
+            LispError::UnboundSymbol(name) => write!(f, "Unbound symbol: {}", name),
+            LispError::ArityMismatch {
+                name,
+                expected: Some(expected),
+                got,
+            } => write!(f, "{} expects {} argument(s), got {}", name, expected, got),
+            LispError::ArityMismatch { name, .. } => {
+                write!(f, "Invalid number of arguments for {}", name)
+            }
+            LispError::TypeMismatch {
+                name,
+                expected,
+                got,
+            } => {
+                let article = match expected.chars().next() {
+                    Some('a' | 'e' | 'i' | 'o' | 'u') => "an",
+                    _ => "a",
+                };
+                write!(f, "{}: not {} {}: {}", name, article, expected, got)
+            }
+            LispError::DivideByZero => write!(f, "Division by zero"),
+            LispError::FileError { path, reason } => write!(f, "Cannot open {}: {}", path, reason),
+            LispError::Raise(Object::Condition(condition)) => write!(f, "{}", condition),
+            LispError::Raise(obj) => write!(f, "Uncaught raise: {}", obj),
+            LispError::Escape { .. } => {
+                write!(f, "Continuation invoked outside of its dynamic extent")
+            }
+            LispError::Traced(traced) => write!(f, "{}", traced.error),
+        }
+    }
+}
+
+impl From<String> for LispError {
+    fn from(msg: String) -> Self {
+        LispError::Eval(msg)
+    }
+}
+
+impl From<&str> for LispError {
+    fn from(msg: &str) -> Self {
+        LispError::Eval(msg.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lisp_error() {
+        let err = LispError::TypeMismatch {
+            name: "car".to_string(),
+            expected: "pair".to_string(),
+            got: "1".to_string(),
+        };
+        assert_eq!("car: not a pair: 1", err.to_string());
+        assert_eq!("type-error", err.kind());
+
+        let err = LispError::ArityMismatch {
+            name: "f".to_string(),
+            expected: Some("at least 2".to_string()),
+            got: 1,
+        };
+        assert_eq!("f expects at least 2 argument(s), got 1", err.to_string());
+        assert_eq!(
+            "Invalid number of arguments for if statement",
+            LispError::ArityMismatch {
+                name: "if statement".to_string(),
+                expected: None,
+                got: 1,
+            }
+            .to_string()
+        );
+        assert_eq!(
+            "syntax-error",
+            LispError::Syntax("Invalid let bindings".to_string()).kind()
+        );
+        assert_eq!("error", LispError::from("Invalid oops").kind());
+        assert_eq!(
+            "Uncaught raise: 42",
+            LispError::Raise(Object::Integer(42)).to_string()
+        );
+
+        let form = Object::Symbol("x".to_string());
+        let err = LispError::Traced(Box::new(Traced {
+            error: LispError::UnboundSymbol("x".to_string()),
+            forms: vec![form.clone()],
+            backtrace: vec!["f".to_string()],
+            loaded_from: None,
+        }));
+        assert_eq!("Unbound symbol: x", err.to_string());
+        assert_eq!("unbound-variable", err.kind());
+        assert_eq!(Some(&form), err.form());
+        assert_eq!(["f"], err.backtrace());
+        assert_eq!(&LispError::UnboundSymbol("x".to_string()), err.cause());
+    }
+}
@@ -1,6 +1,77 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt, fs,
+    io::{self, BufRead, Write},
+    net::{TcpListener, TcpStream},
+    process::{Command, Stdio},
+    rc::Rc,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
 
-use crate::parser::Object;
+use crate::{
+    LispError, Traced,
+    bigint::BigInt,
+    csv,
+    diagnostic::error_span,
+    lexer::{Token, datum_end, tokenize, tokenize_spanned},
+    parser::{
+        Atom, Channel, Class, Condition, EnvRef, Future, HashKey, Instance, Lambda, NativeFn,
+        Object, Port, PortState, Promise, ThreadHandle, parse, parse_program_spanned,
+    },
+    rational::Rational,
+    regex::Regex,
+    sendable::{Sendable, ThreadResult},
+    syntax_rules::{SyntaxRules, unalias},
+};
+
+static SYMBOL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+static CONTINUATION_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A builtin procedure, called with the name it was invoked under and its
+/// evaluated arguments.
+type Builtin = fn(&str, Vec<Object>) -> Result<Object, LispError>;
+
+/// Variables set by `setenv`, shared by every interpreter thread. The process
+/// environment itself is never written, as `std::env::set_var` is unsound
+/// while any other thread may read it; instead `getenv` and subprocesses see
+/// these variables layered over the inherited environment.
+static ENV_OVERLAY: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+fn env_overlay() -> MutexGuard<'static, BTreeMap<String, String>> {
+    ENV_OVERLAY
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+thread_local! {
+    /// Modules registered by `define-module`, keyed by their printed name.
+    static MODULES: RefCell<HashMap<String, Module>> = RefCell::new(HashMap::new());
+    /// Names of the modules whose bodies are being evaluated, innermost last.
+    static CURRENT_MODULE: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    /// Number of `load`s in progress, which a bodiless `define-module` needs.
+    static LOAD_DEPTH: Cell<usize> = const { Cell::new(0) };
+    /// Timers scheduled by `after` and `every` on this thread.
+    static TIMERS: RefCell<Timers> = RefCell::new(Timers::default());
+    /// The builtin procedures looked up as values so far, so that every
+    /// lookup of a name yields the same procedure.
+    static BUILTINS: RefCell<HashMap<String, Rc<NativeFn>>> = RefCell::new(HashMap::new());
+    /// Names of the procedures being applied, outermost first. Tail calls
+    /// after the first in a chain replace the frame of their caller.
+    static CALL_STACK: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Returns a fresh name for an identifier renamed by syntax-rules.
+pub fn fresh_symbol(base: &str) -> String {
+    let n = SYMBOL_COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{}%{}", unalias(base), n)
+}
 
 #[derive(Debug, PartialEq, Default)]
 pub struct Env {
@@ -23,10 +94,53 @@ impl Env {
         }
     }
 
+    /// The bindings made directly in this scope.
+    pub fn bindings(&self) -> impl Iterator<Item = (&String, &Object)> {
+        self.vars.iter()
+    }
+
+    pub fn parent(&self) -> Option<&Rc<RefCell<Env>>> {
+        self.parent.as_ref()
+    }
+
     pub fn set(&mut self, name: &str, val: Object) {
         self.vars.insert(name.to_string(), val);
     }
 
+    /// Rebinds `name` in the nearest scope that defines it. Returns `false`
+    /// if no enclosing scope has a binding for `name`.
+    pub fn assign(&mut self, name: &str, val: Object) -> bool {
+        if let Some(slot) = self.vars.get_mut(name) {
+            *slot = val;
+            return true;
+        }
+        match &self.parent {
+            Some(parent) => parent.borrow_mut().assign(name, val),
+            None => false,
+        }
+    }
+
+    /// Returns the outermost (global) scope of `env`.
+    pub fn root(env: &Rc<RefCell<Self>>) -> Rc<RefCell<Self>> {
+        match &env.borrow().parent {
+            Some(parent) => Env::root(parent),
+            None => env.clone(),
+        }
+    }
+
+    /// Binds `name` to a procedure implemented in Rust.
+    pub fn register_native(
+        &mut self,
+        name: &str,
+        func: impl Fn(&[Object]) -> Result<Object, LispError> + 'static,
+    ) {
+        let native = NativeFn {
+            name: name.to_string(),
+            func: Box::new(func),
+        };
+        self.set(name, Object::NativeFn(Rc::new(native)));
+    }
+
     pub fn extend(parent: Rc<RefCell<Self>>) -> Env {
         Env {
             vars: HashMap::new(),
@@ -35,171 +149,5944 @@ impl Env {
     }
 }
 
-pub fn eval(obj: &Object, env: &mut Rc<RefCell<Env>>) -> Result<Object, String> {
+/// A namespace created by `define-module`: its own top-level scope and the
+/// names it makes visible to `import`.
+struct Module {
+    env: Rc<RefCell<Env>>,
+    exports: Vec<String>,
+}
+
+/// Outcome of evaluating a single form. Forms whose value is the value of a
+/// sub-expression in tail position return `Tail` instead of recursing, and the
+/// loop in `eval` picks the expression up without growing the Rust stack.
+enum Step {
+    Done(Object),
+    Tail(Object, Rc<RefCell<Env>>),
+}
+
+pub fn eval(obj: &Object, env: &mut Rc<RefCell<Env>>) -> Result<Object, LispError> {
+    let depth = call_depth();
+    let result = (|| {
+        let mut step = eval_step(obj, env).map_err(|e| trace(e, obj))?;
+        loop {
+            match step {
+                Step::Done(val) => return Ok(val),
+                Step::Tail(next, mut next_env) => {
+                    // Keep the procedure this loop entered and the one whose
+                    // body is `next`, so tail calls don't grow the stack.
+                    CALL_STACK.with(|stack| {
+                        let mut stack = stack.borrow_mut();
+                        if stack.len() > depth + 2 {
+                            let top = stack.len() - 1;
+                            stack.drain(depth + 1..top);
+                        }
+                    });
+                    step = eval_step(&next, &mut next_env).map_err(|e| trace(e, &next))?
+                }
+            }
+        }
+    })();
+    CALL_STACK.with(|stack| stack.borrow_mut().truncate(depth));
+    result
+}
+
+/// Records that `err` unwound through the evaluation of `form`. The first
+/// form recorded, the one that raised it, comes with the call stack.
+/// Escapes are left alone, being no error to report.
+fn trace(err: LispError, form: &Object) -> LispError {
+    match err {
+        LispError::Escape { .. } => err,
+        LispError::Traced(mut traced) => {
+            traced.forms.push(form.clone());
+            LispError::Traced(traced)
+        }
+        error => LispError::Traced(Box::new(Traced {
+            error,
+            forms: vec![form.clone()],
+            backtrace: CALL_STACK.with(|stack| stack.borrow().iter().rev().cloned().collect()),
+            loaded_from: None,
+        })),
+    }
+}
+
+fn call_depth() -> usize {
+    CALL_STACK.with(|stack| stack.borrow().len())
+}
+
+fn eval_step(obj: &Object, env: &mut Rc<RefCell<Env>>) -> Result<Step, LispError> {
     match obj {
-        Object::Void => Ok(Object::Void),
-        Object::Lambda(_, _) => Ok(Object::Void),
-        Object::Bool(_) => Ok(obj.clone()),
-        Object::Integer(n) => Ok(Object::Integer(*n)),
-        Object::Symbol(s) => eval_symbol(s, env),
+        Object::Void => Ok(Step::Done(Object::Void)),
+        Object::Eof => Ok(Step::Done(Object::Eof)),
+        Object::Lambda(_)
+        | Object::CaseLambda(_)
+        | Object::Macro(_)
+        | Object::Syntax(_)
+        | Object::Environment(_)
+        | Object::Values(_)
+        | Object::Continuation(_)
+        | Object::NativeFn(_)
+        | Object::Foreign(_)
+        | Object::Promise(_)
+        | Object::Condition(_)
+        | Object::Class(_)
+        | Object::Instance(_)
+        | Object::Port(_)
+        | Object::Thread(_)
+        | Object::Future(_)
+        | Object::Timer(_)
+        | Object::Channel(_)
+        | Object::Atom(_)
+        | Object::Vector(_)
+        | Object::HashTable(_)
+        | Object::Set(_)
+        | Object::Bytevector(_) => Ok(Step::Done(obj.clone())),
+        Object::Bool(_) => Ok(Step::Done(obj.clone())),
+        Object::Integer(n) => Ok(Step::Done(Object::Integer(*n))),
+        Object::BigInt(n) => Ok(Step::Done(Object::BigInt(n.clone()))),
+        Object::Rational(n) => Ok(Step::Done(Object::Rational(n.clone()))),
+        Object::Float(n) => Ok(Step::Done(Object::Float(*n))),
+        Object::String(s) => Ok(Step::Done(Object::String(s.clone()))),
+        Object::Char(c) => Ok(Step::Done(Object::Char(*c))),
+        Object::Keyword(_) => Ok(Step::Done(obj.clone())),
+        Object::Symbol(s) => eval_symbol(s, env).map(Step::Done),
+        // `()` evaluates to itself, like `'()`.
+        Object::List(list) if list.is_empty() => Ok(Step::Done(Object::List(Vec::new()))),
         Object::List(list) => eval_list(list, env),
+        Object::DottedList(..) => Err(LispError::Syntax(format!(
+            "Cannot evaluate improper list: {}",
+            obj
+        ))),
     }
 }
 
-fn eval_symbol(s: &str, env: &mut Rc<RefCell<Env>>) -> Result<Object, String> {
-    let val = env.borrow_mut().get(s);
+fn eval_symbol(s: &str, env: &mut Rc<RefCell<Env>>) -> Result<Object, LispError> {
+    // Identifiers renamed by syntax-rules fall back to the original name when
+    // the template did not bind them itself.
+    let mut val = env.borrow().get(s);
+    if val.is_none() && unalias(s) != s {
+        val = env.borrow().get(unalias(s));
+    }
+    // Builtin procedures are values too, unless a binding shadows them.
+    if val.is_none() {
+        val = builtin(unalias(s)).map(Object::NativeFn);
+    }
     match val {
         Some(v) => Ok(v.clone()),
-        None => Err(format!("Unbound symbol: {}", s)),
+        None if s == "nil" => Ok(Object::List(Vec::new())),
+        None => Err(LispError::UnboundSymbol(s.to_string())),
     }
 }
 
-fn eval_list(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Object, String> {
+fn eval_list(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Step, LispError> {
     let head = &list[0];
     if let Object::Symbol(s) = head {
-        match s.as_str() {
-            "+" | "-" | "*" | "/" | "<" | ">" | "=" | "!=" => eval_binary_op(list, env),
-            "define" => eval_define(list, env),
+        let mut s = s.as_str();
+        let mut binding = env.borrow().get(s);
+        if binding.is_none() && unalias(s) != s {
+            s = unalias(s);
+            binding = env.borrow().get(s);
+        }
+        // Variables and macros shadow builtins and special forms; a builtin
+        // bound under its own name is dispatched directly.
+        if let Some(f) = binding
+            && !matches!(&f, Object::NativeFn(native) if native.name == s && is_builtin(native))
+        {
+            return eval_function_call(s, f, list, env);
+        }
+        match s {
+            "begin" => eval_body(&list[1..], env),
             "if" => eval_if(list, env),
-            "lambda" => eval_function_definition(list),
-            _ => eval_function_call(s, list, env),
+            "cond" => eval_cond(list, env),
+            "case" => eval_case(list, env),
+            "when" | "unless" => eval_when(s, list, env),
+            "and" => eval_and(list, env),
+            "or" => eval_or(list, env),
+            "let" | "let*" | "letrec" => eval_let(s, list, env),
+            "eval" => eval_eval(list, env),
+            "match" => eval_match(list, env),
+            "do" => eval_do(list, env),
+            "call-with-values" => eval_call_with_values(list, env),
+            "guard" => eval_guard(list, env),
+            _ => eval_builtin(s, list, env),
         }
     } else {
-        let new_list: Result<Vec<_>, _> = list
-            .iter()
-            .map(|obj| eval(obj, env))
+        let f = eval(head, env)?;
+        if f.is_procedure() {
+            let args = eval_args(list, env)?;
+            return apply_procedure_step("lambda", &f, args);
+        }
+        let new_list: Result<Vec<_>, _> = std::iter::once(Ok(f))
+            .chain(list[1..].iter().map(|obj| eval(obj, env)))
             .filter(|result| !matches!(result, Ok(Object::Void)))
             .collect();
-        Ok(Object::List(new_list?))
+        Ok(Step::Done(Object::List(new_list?)))
     }
 }
 
-fn eval_define(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Object, String> {
-    if list.len() != 3 {
-        return Err("Invalid number of arguments for define".to_string());
+/// The builtin procedure `name` as a value, if there is one.
+pub(crate) fn builtin(name: &str) -> Option<Rc<NativeFn>> {
+    let func = builtin_fn(name)?;
+    let native = BUILTINS.with(|builtins| {
+        builtins
+            .borrow_mut()
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                let op = name.to_string();
+                Rc::new(NativeFn {
+                    name: name.to_string(),
+                    func: Box::new(move |args| func(&op, args.to_vec())),
+                })
+            })
+            .clone()
+    });
+    Some(native)
+}
+
+/// Whether `f` is one of the builtin procedures rather than a function the
+/// host registered.
+pub(crate) fn is_builtin(f: &Rc<NativeFn>) -> bool {
+    builtin(&f.name).is_some_and(|builtin| Rc::ptr_eq(f, &builtin))
+}
+
+/// Dispatches builtins that are never in tail position.
+fn eval_builtin(s: &str, list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Step, LispError> {
+    let val = match s {
+        "define" => eval_define(list, env),
+        "define-macro" => eval_define_macro(list, env),
+        "define-syntax" => eval_define_syntax(list, env),
+        "syntax-rules" => Ok(Object::Syntax(Rc::new(SyntaxRules::new(list)?))),
+        "set!" => eval_set(list, env),
+        "quote" => eval_quote(list),
+        "lambda" => eval_function_definition(list, env),
+        "case-lambda" => eval_case_lambda(list, env),
+        "while" => eval_while(list, env),
+        "delay" => eval_delay(list, env),
+        "future" => eval_future(list, env),
+        "load" => eval_load(list, env),
+        "load-plugin" => eval_load_plugin(list, env),
+        "define-module" => eval_define_module(list, env),
+        "export" => eval_export(list),
+        "import" => eval_import(list, env),
+        "stream-cons" => eval_stream_cons(list, env),
+        "the-environment" => Ok(Object::Environment(EnvRef(env.clone()))),
+        "interaction-environment" => Ok(Object::Environment(EnvRef(Env::root(env)))),
+        _ => match builtin_fn(s) {
+            Some(func) => func(s, eval_args(list, env)?),
+            None => return Err(LispError::UnboundSymbol(s.to_string())),
+        },
     };
+    val.map(Step::Done)
+}
 
-    let sym = if let Object::Symbol(s) = &list[1] {
-        s.clone()
-    } else {
-        return Err("Invalid define".to_string());
+/// The builtin procedure called `name`, which takes its arguments evaluated.
+fn builtin_fn(name: &str) -> Option<Builtin> {
+    let func: Builtin = match name {
+        "+" | "-" | "*" | "/" | "<" | ">" | "<=" | ">=" | "=" | "!=" => eval_arithmetic_op,
+        "abs" | "min" | "max" | "quotient" | "remainder" | "modulo" | "expt" | "gcd" | "lcm"
+        | "floor" | "ceiling" | "round" | "truncate" => eval_math_op,
+        "bit-and" | "bit-or" | "bit-xor" | "bit-not" | "arithmetic-shift" | "bit-count" => {
+            eval_bitwise_op
+        }
+        "sqrt" | "exp" | "log" | "sin" | "cos" | "tan" | "atan" => eval_transcendental_op,
+        "car" | "cdr" | "cons" | "list" | "null?" | "pair?" => eval_pair_op,
+        "map" | "for-each" | "filter" | "fold-left" | "fold-right" | "reduce" => {
+            eval_higher_order_op
+        }
+        "assoc" | "assq" | "member" | "memq" | "find" | "position" => eval_search_op,
+        "make-vector" | "vector" | "vector-ref" | "vector-set!" | "vector-length"
+        | "vector->list" | "list->vector" | "vector?" => eval_vector_op,
+        "make-hash-table" | "hash-table?" | "hash-set!" | "hash-ref" | "hash-remove!"
+        | "hash-keys" | "hash-count" | "hash-for-each" => eval_hash_op,
+        "set" | "set?" | "set-add" | "set-member?" | "set-union" | "set-intersection"
+        | "set-difference" | "set->list" => eval_set_op,
+        "make-bytevector" | "bytevector" | "bytevector?" | "bytevector-length"
+        | "bytevector-u8-ref" | "bytevector-u8-set!" | "utf8->string" | "string->utf8" => {
+            eval_bytevector_op
+        }
+        "string-length" | "substring" | "string-append" | "string-split" | "string-upcase"
+        | "string-downcase" | "string-trim" | "string-contains?" | "string-join"
+        | "string-index" | "string->list" | "list->string" => eval_string_op,
+        "number->string" | "string->number" | "symbol->string" | "string->symbol"
+        | "read-from-string" | "write-to-string" => eval_conversion_op,
+        "json-parse" | "json-stringify" => eval_json_op,
+        "length" | "append" | "reverse" | "list-ref" | "last" | "take" | "drop" | "flatten" => {
+            eval_list_library
+        }
+        "char->integer" | "integer->char" => eval_char_conversion,
+        "char-alphabetic?" | "char-numeric?" | "char-whitespace?" | "char-upcase"
+        | "char-downcase" => eval_char_op,
+        "not" | "boolean?" => eval_boolean_op,
+        "number?" | "integer?" | "rational?" | "real?" | "float?" | "symbol?" | "keyword?"
+        | "string?" | "char?" | "list?" | "procedure?" | "type-of" => eval_type_op,
+        "display" | "write" | "newline" => eval_print_op,
+        "format" => |_, args| eval_format(args),
+        "read-csv" | "write-csv" => eval_csv_op,
+        "tcp-connect" | "tcp-listen" | "tcp-accept" => eval_tcp_op,
+        "spawn" | "join" | "pmap" | "await" => eval_thread_op,
+        "make-channel" | "channel-send" | "channel-recv" => eval_thread_op,
+        "atom" | "deref" | "reset!" | "swap!" => eval_atom_op,
+        "regex-match?" | "regex-find" | "regex-replace" | "regex-split" => eval_regex_op,
+        "after" | "every" | "cancel-timer" => eval_timer_op,
+        "sleep" | "getenv" | "setenv" | "command-line" | "system" | "process-output" | "exit" => {
+            eval_system_op
+        }
+        "read" => |op, args| {
+            if args.len() > 1 {
+                return Err(LispError::ArityMismatch {
+                    name: "read".to_string(),
+                    expected: Some("0 or 1".to_string()),
+                    got: args.len(),
+                });
+            }
+            read_input(op, args.first(), read_datum)
+        },
+        "open-input-file" | "open-output-file" | "open-input-string" | "open-output-string"
+        | "get-output-string" | "close-port" | "port?" | "read-line" | "read-char"
+        | "write-char" => eval_port_op,
+        "eof-object" => |_, _| Ok(Object::Eof),
+        "eof-object?" => |op, args| {
+            check_arity(op, &args, 1)?;
+            Ok(Object::Bool(args[0] == Object::Eof))
+        },
+        "eq?" | "eqv?" | "equal?" => |op, args| {
+            check_arity(op, &args, 2)?;
+            Ok(Object::Bool(if op == "equal?" {
+                args[0] == args[1]
+            } else {
+                is_eqv(&args[0], &args[1])
+            }))
+        },
+        "gensym" => |_, args| eval_gensym(args),
+        "values" => |_, args| eval_values(args),
+        "call-with-values" => |_, procs| apply_call_with_values(procs),
+        "call-with-current-continuation" | "call/cc" => |_, args| eval_call_cc(args),
+        "dynamic-wind" => |_, args| eval_dynamic_wind(args),
+        "error" => |_, args| eval_error(args),
+        "raise" => |_, args| {
+            check_arity("raise", &args, 1)?;
+            Err(LispError::Raise(args[0].clone()))
+        },
+        "force" | "promise?" => eval_promise_op,
+        "make-condition" => |_, args| eval_make_condition(args),
+        "condition?"
+        | "condition-kind"
+        | "condition-message"
+        | "condition-irritants"
+        | "condition-location"
+        | "error-object?"
+        | "error-object-message"
+        | "error-object-irritants"
+        | "file-error?"
+        | "arity-error?"
+        | "type-error?"
+        | "unbound-variable-error?"
+        | "syntax-error?"
+        | "divide-by-zero-error?" => eval_condition_op,
+        "make-class" => |_, args| eval_make_class(args),
+        "make-instance" | "send" | "slot-ref" | "slot-set!" | "class-of" | "class?"
+        | "instance?" | "is-a?" => eval_object_op,
+        "stream-car" | "stream-cdr" | "stream-null?" | "stream-map" | "stream-filter"
+        | "stream-take" => eval_stream_op,
+        _ => return None,
     };
+    Some(func)
+}
 
-    let val = eval(&list[2], env)?;
-    env.borrow_mut().set(&sym, val);
+fn eval_pair_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    if op == "list" {
+        return Ok(Object::List(args));
+    }
+    check_arity(op, &args, if op == "cons" { 2 } else { 1 })?;
 
-    Ok(Object::Void)
+    match (op, &args[0]) {
+        ("cons", _) => {
+            let mut args = args.into_iter();
+            Ok(Object::cons(args.next().unwrap(), args.next().unwrap()))
+        }
+        ("null?", obj) => Ok(Object::Bool(*obj == Object::List(Vec::new()))),
+        ("pair?", obj) => Ok(Object::Bool(match obj {
+            Object::List(items) => !items.is_empty(),
+            Object::DottedList(..) => true,
+            _ => false,
+        })),
+        ("car", Object::List(items) | Object::DottedList(items, _)) if !items.is_empty() => {
+            Ok(items[0].clone())
+        }
+        ("cdr", Object::List(items)) if !items.is_empty() => Ok(Object::List(items[1..].to_vec())),
+        ("cdr", Object::DottedList(items, tail)) => {
+            Ok(Object::dotted(items[1..].to_vec(), (**tail).clone()))
+        }
+        (_, obj) => Err(type_error(op, "pair", obj)),
+    }
 }
 
-fn eval_binary_op(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Object, String> {
-    if list.len() != 3 {
-        return Err("Invalid number of arguments for infix operator".to_string());
+fn eval_list_library(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    if op == "append" {
+        // Every argument but the last must be a proper list; the last becomes
+        // the tail, so `(append '(1) 2)` is `(1 . 2)`.
+        let Some((last, init)) = args.split_last() else {
+            return Ok(Object::List(Vec::new()));
+        };
+        let mut items = Vec::new();
+        for arg in init {
+            items.extend(proper_list(op, arg)?.iter().cloned());
+        }
+        return Ok(Object::dotted(items, last.clone()));
     }
 
-    let operator = list[0].clone();
-    let left = eval(&list[1].clone(), env)?;
-    let right = eval(&list[2].clone(), env)?;
+    match op {
+        "list-ref" | "take" | "drop" => check_arity(op, &args, 2)?,
+        _ => check_arity(op, &args, 1)?,
+    }
+    let items = proper_list(op, &args[0])?;
 
-    let left_val = if let Object::Integer(n) = left {
-        n
-    } else {
-        return Err(format!("Left operand must be an integer {:?}", left));
+    match op {
+        "length" => Ok(Object::Integer(items.len() as i64)),
+        "reverse" => Ok(Object::List(items.iter().rev().cloned().collect())),
+        "last" => items
+            .last()
+            .cloned()
+            .ok_or_else(|| "last: empty list".into()),
+        "list-ref" => {
+            let index = list_index(op, &args[1])?;
+            items
+                .get(index)
+                .cloned()
+                .ok_or_else(|| format!("list-ref: index {} out of range", index).into())
+        }
+        "take" | "drop" => {
+            let n = list_index(op, &args[1])?;
+            if n > items.len() {
+                return Err(format!("{}: list has fewer than {} elements", op, n).into());
+            }
+            let (taken, dropped) = items.split_at(n);
+            Ok(Object::List(
+                if op == "take" { taken } else { dropped }.to_vec(),
+            ))
+        }
+        "flatten" => {
+            let mut flat = Vec::new();
+            flatten_into(items, &mut flat);
+            Ok(Object::List(flat))
+        }
+        _ => Err(format!("Unknown list operation: {}", op).into()),
+    }
+}
+
+/// `(map f list...)`, `(for-each f list...)`, `(filter pred list)`, `(fold-left f init list...)`,
+/// `(fold-right f init list...)` and `(reduce f default list)`. Several lists
+/// are walked in lockstep, stopping at the shortest.
+fn eval_higher_order_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    let min_args = match op {
+        "map" | "for-each" | "filter" => 2,
+        _ => 3,
     };
-    let right_val = if let Object::Integer(n) = right {
-        n
-    } else {
-        return Err(format!("Right operand must be an integer {:?}", right));
+    if args.len() < min_args || (matches!(op, "filter" | "reduce") && args.len() != min_args) {
+        return Err(arity_error(op, args.len()));
+    }
+    let f = &args[0];
+
+    match op {
+        "map" => {
+            let rows = zip_lists(op, &args[1..])?;
+            rows.into_iter()
+                .map(|row| apply_procedure(op, f, row))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Object::List)
+        }
+        "for-each" => {
+            for row in zip_lists(op, &args[1..])? {
+                apply_procedure(op, f, row)?;
+            }
+            Ok(Object::Void)
+        }
+        "filter" => {
+            let mut kept = Vec::new();
+            for item in proper_list(op, &args[1])? {
+                if is_truthy(&apply_procedure(op, f, vec![item.clone()])?) {
+                    kept.push(item.clone());
+                }
+            }
+            Ok(Object::List(kept))
+        }
+        "fold-left" => {
+            zip_lists(op, &args[2..])?
+                .into_iter()
+                .try_fold(args[1].clone(), |acc, mut row| {
+                    row.insert(0, acc);
+                    apply_procedure(op, f, row)
+                })
+        }
+        "fold-right" => zip_lists(op, &args[2..])?.into_iter().rev().try_fold(
+            args[1].clone(),
+            |acc, mut row| {
+                row.push(acc);
+                apply_procedure(op, f, row)
+            },
+        ),
+        "reduce" => {
+            let items = proper_list(op, &args[2])?;
+            let Some((first, rest)) = items.split_first() else {
+                return Ok(args[1].clone());
+            };
+            rest.iter().try_fold(first.clone(), |acc, item| {
+                apply_procedure(op, f, vec![item.clone(), acc])
+            })
+        }
+        _ => Err(format!("Unknown higher-order operation: {}", op).into()),
+    }
+}
+
+/// Association-list lookup and list search. `assoc`, `member` and `position`
+/// compare with structural equality (or an optional comparison procedure),
+/// `assq` and `memq` with `is_eqv`, and `find` takes a predicate.
+fn eval_search_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    let custom = matches!(op, "assoc" | "member") && args.len() == 3;
+    if !custom {
+        check_arity(op, &args, 2)?;
+    }
+    let items = proper_list(op, &args[1])?;
+    let matches = |a: &Object, b: &Object| -> Result<bool, LispError> {
+        match op {
+            _ if custom => Ok(is_truthy(&apply_procedure(
+                op,
+                &args[2],
+                vec![a.clone(), b.clone()],
+            )?)),
+            "assq" | "memq" => Ok(is_eqv(a, b)),
+            _ => Ok(a == b),
+        }
     };
 
-    if let Object::Symbol(s) = operator {
-        match s.as_str() {
-            "+" => Ok(Object::Integer(left_val + right_val)),
-            "-" => Ok(Object::Integer(left_val - right_val)),
-            "*" => Ok(Object::Integer(left_val * right_val)),
-            "/" => Ok(Object::Integer(left_val / right_val)),
-            "<" => Ok(Object::Bool(left_val < right_val)),
-            ">" => Ok(Object::Bool(left_val > right_val)),
-            "=" => Ok(Object::Bool(left_val == right_val)),
-            "!=" => Ok(Object::Bool(left_val != right_val)),
-            _ => Err(format!("Invalid infix operator: {}", s)),
+    match op {
+        "assoc" | "assq" => {
+            for entry in items {
+                let key = match entry {
+                    Object::List(pair) | Object::DottedList(pair, _) if !pair.is_empty() => {
+                        &pair[0]
+                    }
+                    _ => return Err(type_error(op, "association list entry", entry)),
+                };
+                if matches(&args[0], key)? {
+                    return Ok(entry.clone());
+                }
+            }
+            Ok(Object::Bool(false))
         }
-    } else {
-        Err("Operator must be a symbol".to_string())
+        "member" | "memq" => {
+            for (i, item) in items.iter().enumerate() {
+                if matches(&args[0], item)? {
+                    return Ok(Object::List(items[i..].to_vec()));
+                }
+            }
+            Ok(Object::Bool(false))
+        }
+        "position" => {
+            for (i, item) in items.iter().enumerate() {
+                if matches(&args[0], item)? {
+                    return Ok(Object::Integer(i as i64));
+                }
+            }
+            Ok(Object::Bool(false))
+        }
+        "find" => {
+            for item in items {
+                if is_truthy(&apply_procedure(op, &args[0], vec![item.clone()])?) {
+                    return Ok(item.clone());
+                }
+            }
+            Ok(Object::Bool(false))
+        }
+        _ => Err(format!("Unknown search operation: {}", op).into()),
     }
 }
 
-fn eval_if(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Object, String> {
-    if list.len() != 4 {
-        return Err("Invalid number of arguments for if statement".to_string());
+/// `(display obj)` prints in human form, `(write obj)` in a form `read` can
+/// parse back, and `(newline)` ends the line.
+fn eval_print_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    let (text, port) = match (op, args.as_slice()) {
+        ("display", [obj, port @ ..]) if port.len() <= 1 => (obj.display_string(), port.first()),
+        ("write", [obj, port @ ..]) if port.len() <= 1 => (obj.to_string(), port.first()),
+        ("newline", port) if port.len() <= 1 => ("\n".to_string(), port.first()),
+        _ => return Err(arity_error(op, args.len())),
+    };
+
+    write_output(op, port, &text)?;
+    Ok(Object::Void)
+}
+
+fn eval_port_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    match (op, args.as_slice()) {
+        ("open-input-file", [Object::String(path)]) => {
+            let file = fs::File::open(path).map_err(|e| file_error(path, e))?;
+            let input = PortState::Input(Box::new(io::BufReader::new(file)));
+            Ok(Object::Port(Rc::new(Port::new(path, input))))
+        }
+        ("open-output-file", [Object::String(path)]) => {
+            let file = fs::File::create(path).map_err(|e| file_error(path, e))?;
+            let output = PortState::Output(Box::new(file));
+            Ok(Object::Port(Rc::new(Port::new(path, output))))
+        }
+        ("open-input-string", [Object::String(s)]) => {
+            let input = PortState::Input(Box::new(io::Cursor::new(s.clone().into_bytes())));
+            Ok(Object::Port(Rc::new(Port::new("string", input))))
+        }
+        ("open-output-string", []) => Ok(Object::Port(Rc::new(Port::new(
+            "string",
+            PortState::OutputString(String::new()),
+        )))),
+        ("get-output-string", [Object::Port(port)]) => match &*port.state.borrow() {
+            PortState::OutputString(buf) => Ok(Object::String(buf.clone())),
+            _ => Err(type_error(
+                "get-output-string",
+                "output string port",
+                &port.name,
+            )),
+        },
+        ("close-port", [Object::Port(port)]) => {
+            let state = port.state.replace(PortState::Closed);
+            if let PortState::Output(mut out) = state {
+                out.flush().map_err(|e| format!("close-port: {}", e))?;
+            }
+            Ok(Object::Void)
+        }
+        ("port?", [obj]) => Ok(Object::Bool(matches!(obj, Object::Port(_)))),
+        ("read-line", port) if port.len() <= 1 => read_input(op, port.first(), |input| {
+            let mut line = String::new();
+            if input
+                .read_line(&mut line)
+                .map_err(|e| format!("read-line: {}", e))?
+                == 0
+            {
+                return Ok(Object::Eof);
+            }
+            let len = line.trim_end_matches(['\n', '\r']).len();
+            line.truncate(len);
+            Ok(Object::String(line))
+        }),
+        ("read-char", port) if port.len() <= 1 => read_input(op, port.first(), read_char),
+        ("write-char", [Object::Char(c), port @ ..]) if port.len() <= 1 => {
+            write_output(op, port.first(), &c.to_string())?;
+            Ok(Object::Void)
+        }
+        _ => Err(invalid_args(op, &args)),
     }
+}
 
-    let cond_obj = eval(&list[1], env)?;
-    let cond = if let Object::Bool(b) = cond_obj {
-        b
-    } else {
-        return Err("Condition must be boolean".to_string());
+/// Stack size for interpreter threads, matching the main thread since
+/// evaluation recurses deeply.
+const WORKER_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// `(spawn thunk)` runs `thunk` on a new thread with its own interpreter
+/// and `(join thread)` waits for its result. The thunk, its environment and
+/// the result are copied between threads, so the threads share no state
+/// except channels, whose values are copied too. `(make-channel)` makes a
+/// channel, `(channel-send ch val)` sends to it and `(channel-recv ch)`
+/// waits for a value; both take the `channel-` prefix so that `send` stays
+/// the object system's. `(pmap f list)` maps `f` over `list` on several such
+/// threads, and `(await future)` waits for a `future`.
+fn eval_thread_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    match (op, args.as_slice()) {
+        ("spawn", [thunk]) => Ok(Object::Thread(Rc::new(ThreadHandle {
+            handle: RefCell::new(Some(spawn_thunk(op, thunk)?)),
+        }))),
+        // Splits the list into one contiguous chunk per core and maps each
+        // chunk on its own thread, keeping the results in order.
+        ("pmap", [f, items]) => {
+            let items = proper_list(op, items)?;
+            let workers = thread::available_parallelism().map_or(1, |n| n.get());
+            let chunk_size = items.len().div_ceil(workers).max(1);
+            let jobs = items
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    Ok((
+                        Sendable::new(f)?,
+                        Sendable::new(&Object::List(chunk.to_vec()))?,
+                    ))
+                })
+                .collect::<Result<Vec<_>, LispError>>()?;
+
+            let chunks = thread::scope(|scope| {
+                let handles = jobs
+                    .into_iter()
+                    .map(|(f, chunk)| {
+                        thread::Builder::new()
+                            .stack_size(WORKER_STACK_SIZE)
+                            .spawn_scoped(scope, move || {
+                                let (f, Object::List(chunk)) = (f.to_object(), chunk.to_object())
+                                else {
+                                    unreachable!("chunks are copied as lists");
+                                };
+                                let results = chunk
+                                    .into_iter()
+                                    .map(|item| apply_procedure(op, &f, vec![item]))
+                                    .collect::<Result<Vec<_>, _>>();
+                                send_result(results.map(Object::List))
+                            })
+                            .map_err(|e| LispError::from(format!("pmap: {}", e)))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                handles
+                    .into_iter()
+                    .map(|handle| match handle.join() {
+                        Ok(result) => receive_result(result),
+                        Err(_) => Err("pmap: thread panicked".into()),
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })?;
+
+            let mut results = Vec::with_capacity(items.len());
+            for chunk in chunks {
+                if let Object::List(chunk) = chunk {
+                    results.extend(chunk);
+                }
+            }
+            Ok(Object::List(results))
+        }
+        ("make-channel", []) => {
+            let (sender, receiver) = mpsc::channel();
+            Ok(Object::Channel(Arc::new(Channel {
+                sender,
+                receiver: Mutex::new(receiver),
+            })))
+        }
+        ("channel-send", [Object::Channel(channel), val]) => {
+            channel
+                .sender
+                .send(Sendable::new(val)?)
+                .map_err(|e| format!("channel-send: {}", e))?;
+            Ok(Object::Void)
+        }
+        // Blocks until a value arrives. The channel holds its own sender, so
+        // it never disconnects.
+        ("channel-recv", [Object::Channel(channel)]) => {
+            let receiver = channel
+                .receiver
+                .lock()
+                .map_err(|_| "channel-recv: channel is poisoned".to_string())?;
+            receiver
+                .recv()
+                .map(|val| val.to_object())
+                .map_err(|e| format!("channel-recv: {}", e).into())
+        }
+        // Waits for the future the first time and returns the same result,
+        // or raises the same error, every time after.
+        ("await", [Object::Future(future)]) => {
+            let mut state = future.borrow_mut();
+            let result = match std::mem::replace(&mut *state, Future::Done(Ok(Object::Void))) {
+                Future::Running(handle) => match handle.join() {
+                    Ok(result) => receive_result(result),
+                    Err(_) => Err("await: future panicked".into()),
+                },
+                Future::Done(result) => result,
+            };
+            *state = Future::Done(result.clone());
+            result
+        }
+        ("join", [Object::Thread(thread)]) => {
+            let handle = thread
+                .handle
+                .borrow_mut()
+                .take()
+                .ok_or_else(|| "join: thread was already joined".to_string())?;
+            match handle.join() {
+                Ok(result) => receive_result(result),
+                Err(_) => Err("join: thread panicked".into()),
+            }
+        }
+        _ => Err(invalid_args(op, &args)),
+    }
+}
+
+/// Runs `thunk` on a new thread with its own interpreter, copying the thunk
+/// there and its result back.
+fn spawn_thunk(op: &str, thunk: &Object) -> Result<JoinHandle<ThreadResult>, LispError> {
+    let thunk = Sendable::new(thunk)?;
+    let name = op.to_string();
+    thread::Builder::new()
+        .stack_size(WORKER_STACK_SIZE)
+        .spawn(move || {
+            let thunk = thunk.to_object();
+            send_result(apply_procedure(&name, &thunk, Vec::new()))
+        })
+        .map_err(|e| format!("{}: {}", op, e).into())
+}
+
+/// Copies a worker thread's result for the thread that joins it. An error
+/// whose raised object cannot be copied is sent as a condition instead.
+fn send_result(result: Result<Object, LispError>) -> ThreadResult {
+    let err = match result.and_then(|val| Sendable::new(&val)) {
+        Ok(val) => return Ok(val),
+        Err(err) => err,
     };
+    Err(Box::new(
+        Sendable::new(&raised_object(&err))
+            .or_else(|_| Sendable::new(&error_condition(&err)))
+            .expect("conditions can be sent"),
+    ))
+}
 
-    eval(if cond { &list[2] } else { &list[3] }, env)
+/// The result of a joined worker thread, re-raising its error here.
+fn receive_result(result: ThreadResult) -> Result<Object, LispError> {
+    result
+        .map(|val| val.to_object())
+        .map_err(|obj| LispError::Raise(obj.to_object()))
 }
 
-fn eval_function_definition(list: &[Object]) -> Result<Object, String> {
-    let params = match &list[1] {
-        Object::List(list) => list
-            .iter()
-            .map(|param| match param {
-                Object::Symbol(s) => Ok(s.clone()),
-                _ => Err("Invalid lambda parameter".to_string()),
+/// `(future expr)` starts evaluating `expr` on a worker thread, as if by
+/// `(spawn (lambda () expr))`, and returns a handle for `await`.
+fn eval_future(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Object, LispError> {
+    if list.len() != 2 {
+        return Err(arity_error("future", list.len() - 1));
+    }
+
+    let thunk = Object::Lambda(Rc::new(Lambda {
+        params: Vec::new(),
+        rest: None,
+        keys: Vec::new(),
+        body: vec![list[1].clone()],
+        env: env.clone(),
+    }));
+    Ok(Object::Future(Rc::new(RefCell::new(Future::Running(
+        spawn_thunk("future", &thunk)?,
+    )))))
+}
+
+/// Atoms hold a value that any thread may read with `deref` or replace with
+/// `reset!`. `(swap! atom f args...)` stores `(f current args...)`; `f` runs
+/// without the lock held and is retried if another update landed meanwhile.
+fn eval_atom_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    match (op, args.as_slice()) {
+        ("atom", [val]) => Ok(Object::Atom(Arc::new(Atom {
+            state: Mutex::new((0, Sendable::new(val)?)),
+        }))),
+        ("deref", [Object::Atom(atom)]) => Ok(lock_atom(op, atom)?.1.to_object()),
+        ("reset!", [Object::Atom(atom), val]) => {
+            let val_copy = Sendable::new(val)?;
+            let mut state = lock_atom(op, atom)?;
+            *state = (state.0 + 1, val_copy);
+            Ok(val.clone())
+        }
+        ("swap!", [Object::Atom(atom), f, rest @ ..]) => loop {
+            let (version, current) = {
+                let state = lock_atom(op, atom)?;
+                (state.0, state.1.to_object())
+            };
+            let mut f_args = vec![current];
+            f_args.extend(rest.iter().cloned());
+            let val = apply_procedure(op, f, f_args)?;
+            let val_copy = Sendable::new(&val)?;
+
+            let mut state = lock_atom(op, atom)?;
+            if state.0 == version {
+                *state = (version + 1, val_copy);
+                return Ok(val);
+            }
+        },
+        _ => Err(invalid_args(op, &args)),
+    }
+}
+
+fn lock_atom<'a>(op: &str, atom: &'a Atom) -> Result<MutexGuard<'a, (u64, Sendable)>, LispError> {
+    atom.state
+        .lock()
+        .map_err(|_| format!("{}: atom is poisoned", op).into())
+}
+
+/// Pending timers: callbacks with their repeat interval by id, and the ids
+/// ordered by deadline. A cancelled timer's id stays queued and is skipped
+/// when it comes due.
+#[derive(Default)]
+struct Timers {
+    next_id: usize,
+    callbacks: HashMap<usize, (Object, Option<Duration>)>,
+    queue: BTreeSet<(Instant, usize)>,
+}
+
+/// `(after ms thunk)` calls `thunk` once, `ms` milliseconds from now, and
+/// `(every ms thunk)` calls it every `ms` milliseconds until `(cancel-timer
+/// timer)`. Timers run on the thread that scheduled them whenever it is idle:
+/// during `sleep`, while the REPL waits for input, and after a script ends
+/// until none are left.
+fn eval_timer_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    match (op, args.as_slice()) {
+        ("after" | "every", [ms, thunk]) => {
+            let delay = millis(op, ms)?;
+            let interval = match op {
+                "every" if delay.is_zero() => return Err("every: interval must be positive".into()),
+                "every" => Some(delay),
+                _ => None,
+            };
+            let deadline = Instant::now() + delay;
+            TIMERS.with_borrow_mut(|timers| {
+                let id = timers.next_id;
+                timers.next_id += 1;
+                timers.callbacks.insert(id, (thunk.clone(), interval));
+                timers.queue.insert((deadline, id));
+                Ok(Object::Timer(id))
             })
-            .collect::<Result<Vec<_>, _>>()?,
-        _ => return Err("Invalid lambda".to_string()),
+        }
+        // Returns whether the timer was still pending.
+        ("cancel-timer", [Object::Timer(id)]) => {
+            Ok(Object::Bool(TIMERS.with_borrow_mut(|timers| {
+                timers.callbacks.remove(id).is_some()
+            })))
+        }
+        _ => Err(invalid_args(op, &args)),
+    }
+}
+
+/// Converts a non-negative number of milliseconds to a `Duration`.
+fn millis(op: &str, ms: &Object) -> Result<Duration, LispError> {
+    match ms {
+        Object::Integer(ms) if *ms >= 0 => Ok(Duration::from_millis(*ms as u64)),
+        Object::Float(ms) if *ms >= 0.0 => Duration::try_from_secs_f64(ms / 1000.0)
+            .map_err(|_| format!("{}: invalid duration: {}", op, ms).into()),
+        _ => Err(format!("{}: invalid duration: {}", op, ms).into()),
+    }
+}
+
+/// Sleeps for `duration`, running timers that come due meanwhile.
+fn sleep(duration: Duration) -> Result<(), LispError> {
+    let end = Instant::now() + duration;
+    loop {
+        run_due_timers()?;
+        let now = Instant::now();
+        if now >= end {
+            return Ok(());
+        }
+        let wake = next_timer_deadline().map_or(end, |deadline| deadline.min(end));
+        thread::sleep(wake.saturating_duration_since(now));
+    }
+}
+
+/// Calls the timers that are due, earliest first. Repeating timers are
+/// rescheduled before their callback runs, so a callback may cancel its own
+/// timer.
+pub fn run_due_timers() -> Result<(), LispError> {
+    let now = Instant::now();
+    loop {
+        let due = TIMERS.with_borrow_mut(|timers| {
+            while let Some(&(deadline, id)) = timers.queue.first()
+                && deadline <= now
+            {
+                timers.queue.pop_first();
+                let Some((thunk, interval)) = timers.callbacks.get(&id).cloned() else {
+                    continue;
+                };
+                match interval {
+                    Some(interval) => {
+                        timers.queue.insert((deadline + interval, id));
+                    }
+                    None => {
+                        timers.callbacks.remove(&id);
+                    }
+                }
+                return Some(thunk);
+            }
+            None
+        });
+        let Some(thunk) = due else {
+            return Ok(());
+        };
+        apply_procedure("timer", &thunk, Vec::new())?;
+    }
+}
+
+/// When the earliest pending timer is due, if any are pending.
+pub fn next_timer_deadline() -> Option<Instant> {
+    TIMERS.with_borrow(|timers| {
+        timers
+            .queue
+            .iter()
+            .find(|(_, id)| timers.callbacks.contains_key(id))
+            .map(|&(deadline, _)| deadline)
+    })
+}
+
+/// The scheduler loop: waits for and runs timers until none are left.
+pub fn run_timers() -> Result<(), LispError> {
+    while let Some(deadline) = next_timer_deadline() {
+        thread::sleep(deadline.saturating_duration_since(Instant::now()));
+        run_due_timers()?;
+    }
+    Ok(())
+}
+
+/// `(tcp-connect host port)` and `(tcp-accept listener)` return socket ports
+/// usable with the usual reading and writing procedures. `(tcp-listen port
+/// [host])` listens on `host`, by default 127.0.0.1; port 0 picks a free
+/// port, which the listener's name shows.
+fn eval_tcp_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    let socket_port = |stream: TcpStream| {
+        let name = stream
+            .peer_addr()
+            .map_or_else(|_| "socket".to_string(), |addr| addr.to_string());
+        Object::Port(Rc::new(Port::new(
+            &name,
+            PortState::Socket(io::BufReader::new(stream)),
+        )))
+    };
+
+    match (op, args.as_slice()) {
+        ("tcp-connect", [Object::String(host), Object::Integer(port @ 0..=65535)]) => {
+            let stream = TcpStream::connect((host.as_str(), *port as u16))
+                .map_err(|e| format!("tcp-connect: {}:{}: {}", host, port, e))?;
+            Ok(socket_port(stream))
+        }
+        ("tcp-listen", [Object::Integer(port @ 0..=65535), host @ ..]) if host.len() <= 1 => {
+            let host = match host.first() {
+                None => "127.0.0.1",
+                Some(Object::String(host)) => host,
+                Some(other) => return Err(type_error("tcp-listen", "host", other)),
+            };
+            let listener = TcpListener::bind((host, *port as u16))
+                .map_err(|e| format!("tcp-listen: {}:{}: {}", host, port, e))?;
+            let name = listener
+                .local_addr()
+                .map_err(|e| format!("tcp-listen: {}", e))?
+                .to_string();
+            Ok(Object::Port(Rc::new(Port::new(
+                &name,
+                PortState::Listener(listener),
+            ))))
+        }
+        ("tcp-accept", [Object::Port(port)]) => {
+            let stream = match &*port.state.borrow() {
+                PortState::Listener(listener) => listener.accept().map(|(stream, _)| stream),
+                _ => return Err(type_error("tcp-accept", "open listener", &port.name)),
+            };
+            Ok(socket_port(
+                stream.map_err(|e| format!("tcp-accept: {}", e))?,
+            ))
+        }
+        _ => Err(invalid_args(op, &args)),
+    }
+}
+
+/// `(read-csv source [header?])` reads rows of strings from a port or a file
+/// path. With `header?`, the first row names the columns and every other row
+/// becomes an association list of `(column . value)` pairs.
+/// `(write-csv rows [port])` writes a list of rows, displaying each field.
+fn eval_csv_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    match (op, args.as_slice()) {
+        ("read-csv", [source, header @ ..]) if header.len() <= 1 => {
+            let text = match source {
+                Object::String(path) => {
+                    fs::read_to_string(path).map_err(|e| file_error(path, e))?
+                }
+                port => read_input(op, Some(port), |input| {
+                    let mut text = String::new();
+                    input
+                        .read_to_string(&mut text)
+                        .map_err(|e| format!("read-csv: {}", e))?;
+                    Ok(text)
+                })?,
+            };
+            let mut rows = csv::parse(&text)?
+                .into_iter()
+                .map(|row| row.into_iter().map(Object::String).collect::<Vec<_>>());
+
+            if header.first().is_none_or(|h| *h == Object::Bool(false)) {
+                return Ok(Object::List(rows.map(Object::List).collect()));
+            }
+            let Some(columns) = rows.next() else {
+                return Ok(Object::List(Vec::new()));
+            };
+            Ok(Object::List(
+                rows.map(|row| {
+                    Object::List(
+                        columns
+                            .iter()
+                            .zip(row)
+                            .map(|(column, value)| Object::dotted(vec![column.clone()], value))
+                            .collect(),
+                    )
+                })
+                .collect(),
+            ))
+        }
+        ("write-csv", [rows, port @ ..]) if port.len() <= 1 => {
+            let mut text = String::new();
+            for row in proper_list(op, rows)? {
+                let fields: Vec<_> = proper_list(op, row)?
+                    .iter()
+                    .map(Object::display_string)
+                    .collect();
+                text.push_str(&csv::format_row(&fields));
+            }
+            write_output(op, port.first(), &text)?;
+            Ok(Object::Void)
+        }
+        _ => Err(invalid_args(op, &args)),
+    }
+}
+
+/// Regular expression builtins, each taking the pattern first. A match is
+/// returned as a list of the whole match followed by each group, with `#f`
+/// for groups that did not participate. `regex-replace` replaces every match,
+/// expanding `$n` to group `n` and `$$` to `$`.
+fn eval_regex_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    let (regex, text, rest) = match args.as_slice() {
+        [Object::String(pattern), Object::String(text), rest @ ..] => {
+            (Regex::new(pattern)?, text.chars().collect::<Vec<_>>(), rest)
+        }
+        _ => return Err(invalid_args(op, &args)),
+    };
+    let span = |(start, end): (usize, usize)| text[start..end].iter().collect::<String>();
+
+    match (op, rest) {
+        ("regex-match?", []) => Ok(Object::Bool(regex.find_at(&text, 0).is_some())),
+        ("regex-find", []) => Ok(match regex.find_at(&text, 0) {
+            Some(caps) => Object::List(
+                caps.into_iter()
+                    .map(|cap| cap.map_or(Object::Bool(false), |cap| Object::String(span(cap))))
+                    .collect(),
+            ),
+            None => Object::Bool(false),
+        }),
+        ("regex-replace", [Object::String(replacement)]) => {
+            let mut out = String::new();
+            let mut last = 0;
+            for caps in regex.find_all(&text) {
+                let (start, end) = caps[0].expect("the whole match is always set");
+                out.push_str(&span((last, start)));
+                let mut chars = replacement.chars().peekable();
+                while let Some(c) = chars.next() {
+                    match (c, chars.peek().and_then(|d| d.to_digit(10))) {
+                        ('$', Some(n)) => {
+                            chars.next();
+                            if let Some(Some(cap)) = caps.get(n as usize) {
+                                out.push_str(&span(*cap));
+                            }
+                        }
+                        ('$', None) if chars.next_if_eq(&'$').is_some() => out.push('$'),
+                        _ => out.push(c),
+                    }
+                }
+                last = end;
+            }
+            out.push_str(&span((last, text.len())));
+            Ok(Object::String(out))
+        }
+        ("regex-split", []) => {
+            let mut pieces = Vec::new();
+            let mut last = 0;
+            for caps in regex.find_all(&text) {
+                let (start, end) = caps[0].expect("the whole match is always set");
+                // An empty match at the very start would split off nothing.
+                if end == 0 {
+                    continue;
+                }
+                pieces.push(Object::String(span((last, start))));
+                last = end;
+            }
+            pieces.push(Object::String(span((last, text.len()))));
+            Ok(Object::List(pieces))
+        }
+        _ => Err(invalid_args(op, &args)),
+    }
+}
+
+/// Writes `text` to `port`, or to standard output when no port is given.
+fn write_output(op: &str, port: Option<&Object>, text: &str) -> Result<(), LispError> {
+    let result = match port {
+        None => {
+            let mut stdout = io::stdout();
+            stdout
+                .write_all(text.as_bytes())
+                .and_then(|_| stdout.flush())
+        }
+        Some(Object::Port(port)) => match &mut *port.state.borrow_mut() {
+            PortState::Output(out) => out.write_all(text.as_bytes()).and_then(|_| out.flush()),
+            PortState::OutputString(buf) => {
+                buf.push_str(text);
+                Ok(())
+            }
+            PortState::Socket(socket) => {
+                let stream = socket.get_mut();
+                stream
+                    .write_all(text.as_bytes())
+                    .and_then(|_| stream.flush())
+            }
+            _ => return Err(type_error(op, "open output port", &port.name)),
+        },
+        Some(other) => return Err(type_error(op, "port", other)),
     };
+    result.map_err(|e| format!("{}: {}", op, e).into())
+}
+
+/// Runs `read` on `port`, or on standard input when no port is given.
+fn read_input<T>(
+    op: &str,
+    port: Option<&Object>,
+    read: impl FnOnce(&mut dyn BufRead) -> Result<T, LispError>,
+) -> Result<T, LispError> {
+    match port {
+        None => read(&mut io::stdin().lock()),
+        Some(Object::Port(port)) => match &mut *port.state.borrow_mut() {
+            PortState::Input(input) => read(input.as_mut()),
+            PortState::Socket(socket) => read(socket),
+            _ => Err(type_error(op, "open input port", &port.name)),
+        },
+        Some(other) => Err(type_error(op, "port", other)),
+    }
+}
 
-    let body = match &list[2] {
-        Object::List(list) => list.clone(),
-        _ => return Err("Invalid lambad".to_string()),
+/// Reads one UTF-8 encoded character, or returns `Eof` at the end of input.
+fn read_char(input: &mut dyn BufRead) -> Result<Object, LispError> {
+    let first = match input.fill_buf().map_err(|e| format!("read-char: {}", e))? {
+        [] => return Ok(Object::Eof),
+        [first, ..] => *first,
+    };
+    let width = match first {
+        0x00..=0x7f => 1,
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        _ => 4,
     };
+    let mut bytes = vec![0; width];
+    input
+        .read_exact(&mut bytes)
+        .map_err(|e| format!("read-char: {}", e))?;
+    std::str::from_utf8(&bytes)
+        .ok()
+        .and_then(|s| s.chars().next())
+        .map(Object::Char)
+        .ok_or_else(|| "read-char: invalid UTF-8 input".into())
+}
+
+fn eval_system_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    match (op, args.as_slice()) {
+        ("sleep", [ms]) => {
+            sleep(millis(op, ms)?)?;
+            Ok(Object::Void)
+        }
+        ("getenv", [Object::String(key)]) => Ok(env_overlay()
+            .get(key)
+            .cloned()
+            .or_else(|| std::env::var(key).ok())
+            .map_or(Object::Bool(false), Object::String)),
+        ("setenv", [Object::String(key), Object::String(val)]) => {
+            if key.is_empty() || key.contains(['=', '\0']) || val.contains('\0') {
+                return Err(format!("setenv: invalid variable: {}", key).into());
+            }
+            env_overlay().insert(key.clone(), val.clone());
+            Ok(Object::Void)
+        }
+        // The script path followed by its arguments; empty in the REPL.
+        ("command-line", []) => Ok(Object::List(
+            std::env::args().skip(1).map(Object::String).collect(),
+        )),
+        ("exit", []) => exit(0),
+        ("exit", [Object::Integer(code)]) => match i32::try_from(*code) {
+            Ok(code) => exit(code),
+            Err(_) => Err(format!("exit: code out of range: {}", code).into()),
+        },
+        ("exit", [Object::Bool(success)]) => exit(if *success { 0 } else { 1 }),
+        // The exit code, or #f if the command was killed by a signal.
+        ("system", [Object::String(cmd)]) => {
+            let status = shell(cmd).status().map_err(|e| format!("system: {}", e))?;
+            Ok(status
+                .code()
+                .map_or(Object::Bool(false), |code| Object::Integer(code as i64)))
+        }
+        ("process-output", [Object::String(cmd)]) => {
+            let output = shell(cmd)
+                .stderr(Stdio::inherit())
+                .output()
+                .map_err(|e| format!("process-output: {}", e))?;
+            Ok(Object::String(
+                String::from_utf8_lossy(&output.stdout).into_owned(),
+            ))
+        }
+        _ => Err(invalid_args(op, &args)),
+    }
+}
 
-    Ok(Object::Lambda(params, body))
+/// Terminates the process once buffered standard output is written.
+fn exit(code: i32) -> ! {
+    let _ = io::stdout().flush();
+    std::process::exit(code)
 }
 
-fn eval_function_call(
-    s: &str,
-    list: &[Object],
-    env: &mut Rc<RefCell<Env>>,
-) -> Result<Object, String> {
-    let lambda = env
-        .borrow_mut()
-        .get(s)
-        .ok_or_else(|| format!("Unbound symbol: {}", s))?;
+fn shell(cmd: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(cmd).envs(env_overlay().iter());
+    command
+}
+
+/// `(format dest control args...)`. `dest` is `#t` to print to standard
+/// output or `#f` to return a string; it may also be omitted, in which case
+/// the string is returned.
+fn eval_format(args: Vec<Object>) -> Result<Object, LispError> {
+    let (print, control, rest) = match args.as_slice() {
+        [Object::Bool(print), Object::String(control), rest @ ..] => (*print, control, rest),
+        [Object::String(control), rest @ ..] => (false, control, rest),
+        _ => return Err(invalid_args("format", &args)),
+    };
+
+    let control: Vec<char> = control.chars().collect();
+    let mut used = 0;
+    let text = format_directives(&control, rest, &mut used)?;
+    if used < rest.len() {
+        return Err(format!("format: {} unused argument(s)", rest.len() - used).into());
+    }
+    if !print {
+        return Ok(Object::String(text));
+    }
+
+    write_output("format", None, &text)?;
+    Ok(Object::Void)
+}
 
-    match lambda {
-        Object::Lambda(params, body) => {
-            let mut new_env = Rc::new(RefCell::new(Env::extend(env.clone())));
-            for (i, param) in params.iter().enumerate() {
-                let val = eval(&list[i + 1], env)?;
-                new_env.borrow_mut().set(param, val);
+/// Expands `control`, consuming `args` from index `used` onwards. Supports
+/// `~a` (display), `~s` (write), `~d` (number), `~%` (newline), `~~` and
+/// `~{...~}`, which repeats its body over the elements of a list argument.
+fn format_directives(
+    control: &[char],
+    args: &[Object],
+    used: &mut usize,
+) -> Result<String, LispError> {
+    let mut next_arg = |directive: char| {
+        let arg = args
+            .get(*used)
+            .ok_or_else(|| format!("format: missing argument for ~{}", directive))?;
+        *used += 1;
+        Ok::<_, String>(arg)
+    };
+
+    let mut out = String::new();
+    let mut i = 0;
+    while i < control.len() {
+        if control[i] != '~' {
+            out.push(control[i]);
+            i += 1;
+            continue;
+        }
+        let directive = *control
+            .get(i + 1)
+            .ok_or_else(|| "format: control string ends with ~".to_string())?;
+        i += 2;
+        match directive.to_ascii_lowercase() {
+            'a' => out.push_str(&next_arg('a')?.display_string()),
+            's' => out.push_str(&next_arg('s')?.to_string()),
+            'd' => match next_arg('d')? {
+                n @ (Object::Integer(_)
+                | Object::BigInt(_)
+                | Object::Rational(_)
+                | Object::Float(_)) => out.push_str(&n.to_string()),
+                other => return Err(type_error("format", "number", other)),
+            },
+            '%' => out.push('\n'),
+            '~' => out.push('~'),
+            '{' => {
+                let end = matching_iteration_end(control, i)?;
+                let items = proper_list("format", next_arg('{')?)?;
+                let mut pos = 0;
+                while pos < items.len() {
+                    let start = pos;
+                    out.push_str(&format_directives(&control[i..end], items, &mut pos)?);
+                    if pos == start {
+                        break;
+                    }
+                }
+                i = end + 2;
             }
-            eval(&Object::List(body), &mut new_env)
+            c => return Err(format!("format: unknown directive ~{}", c).into()),
         }
-        _ => Err(format!("Not a lambda: {}", s)),
     }
+    Ok(out)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Finds the `~}` closing a `~{` whose body starts at `start`.
+fn matching_iteration_end(control: &[char], start: usize) -> Result<usize, LispError> {
+    let mut depth = 0;
+    let mut i = start;
+    while i + 1 < control.len() {
+        if control[i] == '~' {
+            match control[i + 1] {
+                '{' => depth += 1,
+                '}' if depth == 0 => return Ok(i),
+                '}' => depth -= 1,
+                _ => {}
+            }
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+    Err("format: ~{ without matching ~}".into())
+}
 
-    #[test]
-    fn test_eval() {
-        let mut env = Rc::new(RefCell::new(Env::new()));
-        let input = Object::List(vec![
-            Object::Symbol("+".to_string()),
-            Object::Integer(1),
-            Object::Integer(2),
-        ]);
-        let expeted = Ok(Object::Integer(3));
+/// Reads one datum from `reader` and parses it, consuming input only up to
+/// the end of the datum. Returns `Eof` when the input ends before any datum
+/// starts.
+fn read_datum(reader: &mut dyn BufRead) -> Result<Object, LispError> {
+    let mut source = Vec::new();
+    loop {
+        let buf = reader.fill_buf().map_err(|e| format!("read: {}", e))?;
+        let at_eof = buf.is_empty();
+        // Take a line at a time so interactive input is parsed as it arrives.
+        let take = buf
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(buf.len(), |i| i + 1);
+        source.extend_from_slice(&buf[..take]);
 
-        assert_eq!(expeted, eval(&input, &mut env));
+        let text = match std::str::from_utf8(&source) {
+            Ok(text) => text,
+            // The chunk may end partway through a character.
+            Err(e) if e.error_len().is_none() && !at_eof => {
+                reader.consume(take);
+                continue;
+            }
+            Err(_) => return Err("read: invalid UTF-8 input".into()),
+        };
+        match datum_end(text) {
+            // A datum running to the end of the text may continue in the
+            // next chunk, unless there is none.
+            Ok(Some(end)) if end < text.len() || at_eof => {
+                reader.consume(take - (source.len() - end));
+                let mut tokens =
+                    tokenize(&text[..end]).map_err(|e| LispError::Syntax(e.to_string()))?;
+                return parse(&mut tokens).map_err(|e| LispError::Syntax(e.to_string()));
+            }
+            _ if !at_eof => reader.consume(take),
+            Ok(_) if tokenize(text).is_ok_and(|tokens| tokens.is_empty()) => {
+                return Ok(Object::Eof);
+            }
+            Ok(_) => {
+                return Err(LispError::Syntax(
+                    "read: unexpected end of input".to_string(),
+                ));
+            }
+            Err(e) => return Err(LispError::Syntax(e.to_string())),
+        }
+    }
+}
+
+fn eval_type_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    check_arity(op, &args, 1)?;
+    let obj = &args[0];
+
+    let result = match op {
+        "type-of" => return Ok(Object::Symbol(type_of(obj).to_string())),
+        "number?" | "real?" => matches!(
+            obj,
+            Object::Integer(_) | Object::BigInt(_) | Object::Rational(_) | Object::Float(_)
+        ),
+        "integer?" => match obj {
+            Object::Integer(_) | Object::BigInt(_) => true,
+            Object::Float(f) => f.is_finite() && f.fract() == 0.0,
+            _ => false,
+        },
+        "rational?" => matches!(
+            obj,
+            Object::Integer(_) | Object::BigInt(_) | Object::Rational(_)
+        ),
+        "float?" => matches!(obj, Object::Float(_)),
+        "symbol?" => matches!(obj, Object::Symbol(_)),
+        "keyword?" => matches!(obj, Object::Keyword(_)),
+        "string?" => matches!(obj, Object::String(_)),
+        "char?" => matches!(obj, Object::Char(_)),
+        "list?" => matches!(obj, Object::List(_)),
+        "procedure?" => obj.is_procedure(),
+        _ => return Err(format!("Unknown type predicate: {}", op).into()),
+    };
+    Ok(Object::Bool(result))
+}
+
+/// Names the type of a value, as returned by `type-of`.
+fn type_of(obj: &Object) -> &'static str {
+    match obj {
+        Object::Void => "void",
+        Object::Eof => "eof",
+        Object::Integer(_) | Object::BigInt(_) => "integer",
+        Object::Rational(_) => "rational",
+        Object::Float(_) => "float",
+        Object::Bool(_) => "boolean",
+        Object::Symbol(_) => "symbol",
+        Object::Keyword(_) => "keyword",
+        Object::String(_) => "string",
+        Object::Char(_) => "char",
+        Object::Lambda(_)
+        | Object::CaseLambda(_)
+        | Object::Continuation(_)
+        | Object::NativeFn(_) => "procedure",
+        Object::Macro(_) | Object::Syntax(_) => "macro",
+        Object::Environment(_) => "environment",
+        Object::Values(_) => "values",
+        Object::Promise(_) => "promise",
+        Object::Condition(_) => "condition",
+        Object::Class(_) => "class",
+        Object::Instance(_) => "instance",
+        Object::Port(_) => "port",
+        Object::Thread(_) => "thread",
+        Object::Future(_) => "future",
+        Object::Foreign(foreign) => foreign.type_name,
+        Object::Timer(_) => "timer",
+        Object::Channel(_) => "channel",
+        Object::Atom(_) => "atom",
+        Object::Vector(_) => "vector",
+        Object::Bytevector(_) => "bytevector",
+        Object::HashTable(_) => "hash-table",
+        Object::Set(_) => "set",
+        Object::List(items) if items.is_empty() => "null",
+        Object::List(_) | Object::DottedList(..) => "pair",
+    }
+}
+
+/// Identity-style comparison used by `eq?` and `eqv?`: atoms compare by
+/// value, mutable containers by identity, while strings and non-empty lists
+/// are fresh objects and never match.
+fn is_eqv(a: &Object, b: &Object) -> bool {
+    match (a, b) {
+        (Object::List(x), Object::List(y)) => x.is_empty() && y.is_empty(),
+        (Object::String(_) | Object::DottedList(..), _) => false,
+        (Object::Vector(x), Object::Vector(y)) => Rc::ptr_eq(x, y),
+        (Object::Bytevector(x), Object::Bytevector(y)) => Rc::ptr_eq(x, y),
+        (Object::HashTable(x), Object::HashTable(y)) => Rc::ptr_eq(x, y),
+        (Object::Set(x), Object::Set(y)) => Rc::ptr_eq(x, y),
+        _ => a == b,
+    }
+}
+
+/// Transposes lists into rows of corresponding elements, truncated to the
+/// shortest list.
+fn zip_lists(op: &str, lists: &[Object]) -> Result<Vec<Vec<Object>>, LispError> {
+    let lists = lists
+        .iter()
+        .map(|obj| proper_list(op, obj))
+        .collect::<Result<Vec<_>, _>>()?;
+    let len = lists.iter().map(|items| items.len()).min().unwrap_or(0);
+    Ok((0..len)
+        .map(|i| lists.iter().map(|items| items[i].clone()).collect())
+        .collect())
+}
+
+fn eval_vector_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    let new_vector = |items| Object::Vector(Rc::new(RefCell::new(items)));
+
+    match (op, args.as_slice()) {
+        ("vector", _) => Ok(new_vector(args.clone())),
+        ("make-vector", [len]) => Ok(new_vector(vec![Object::Integer(0); list_index(op, len)?])),
+        ("make-vector", [len, fill]) => Ok(new_vector(vec![fill.clone(); list_index(op, len)?])),
+        ("list->vector", [obj]) => Ok(new_vector(proper_list(op, obj)?.clone())),
+        ("vector?", [obj]) => Ok(Object::Bool(matches!(obj, Object::Vector(_)))),
+        ("vector-length", [Object::Vector(items)]) => {
+            Ok(Object::Integer(items.borrow().len() as i64))
+        }
+        ("vector->list", [Object::Vector(items)]) => Ok(Object::List(items.borrow().clone())),
+        ("vector-ref", [Object::Vector(items), index]) => {
+            let index = list_index(op, index)?;
+            items
+                .borrow()
+                .get(index)
+                .cloned()
+                .ok_or_else(|| format!("vector-ref: index {} out of range", index).into())
+        }
+        ("vector-set!", [Object::Vector(items), index, val]) => {
+            let index = list_index(op, index)?;
+            match items.borrow_mut().get_mut(index) {
+                Some(slot) => *slot = val.clone(),
+                None => return Err(format!("vector-set!: index {} out of range", index).into()),
+            }
+            Ok(Object::Void)
+        }
+        _ => Err(invalid_args(op, &args)),
+    }
+}
+
+fn eval_hash_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    let key = |obj: &Object| {
+        obj.hash_key()
+            .ok_or_else(|| LispError::from(format!("{}: unhashable key: {}", op, obj)))
+    };
+
+    match (op, args.as_slice()) {
+        ("make-hash-table", []) => Ok(Object::HashTable(Rc::new(RefCell::new(HashMap::new())))),
+        ("hash-table?", [obj]) => Ok(Object::Bool(matches!(obj, Object::HashTable(_)))),
+        ("hash-set!", [Object::HashTable(table), k, v]) => {
+            table.borrow_mut().insert(key(k)?, (k.clone(), v.clone()));
+            Ok(Object::Void)
+        }
+        ("hash-ref", [Object::HashTable(table), k, rest @ ..]) if rest.len() <= 1 => {
+            match (table.borrow().get(&key(k)?), rest.first()) {
+                (Some((_, v)), _) => Ok(v.clone()),
+                (None, Some(default)) => Ok(default.clone()),
+                (None, None) => Err(format!("hash-ref: key not found: {}", k).into()),
+            }
+        }
+        ("hash-remove!", [Object::HashTable(table), k]) => {
+            table.borrow_mut().remove(&key(k)?);
+            Ok(Object::Void)
+        }
+        ("hash-keys", [Object::HashTable(table)]) => Ok(Object::List(
+            table.borrow().values().map(|(k, _)| k.clone()).collect(),
+        )),
+        ("hash-count", [Object::HashTable(table)]) => {
+            Ok(Object::Integer(table.borrow().len() as i64))
+        }
+        ("hash-for-each", [Object::HashTable(table), f]) => {
+            // Snapshot the entries so the procedure may modify the table.
+            let entries: Vec<_> = table.borrow().values().cloned().collect();
+            for (k, v) in entries {
+                apply_procedure(op, f, vec![k, v])?;
+            }
+            Ok(Object::Void)
+        }
+        _ => Err(invalid_args(op, &args)),
+    }
+}
+
+/// Sets are immutable: `set-add` and the set algebra return new sets.
+fn eval_set_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    let entry = |obj: &Object| match obj.hash_key() {
+        Some(key) => Ok((key, obj.clone())),
+        None => Err(format!("{}: unhashable element: {}", op, obj)),
+    };
+    let new_set = |items: BTreeMap<HashKey, Object>| Object::Set(Rc::new(items));
+
+    match (op, args.as_slice()) {
+        ("set", _) => Ok(new_set(args.iter().map(entry).collect::<Result<_, _>>()?)),
+        ("set?", [obj]) => Ok(Object::Bool(matches!(obj, Object::Set(_)))),
+        ("set-add", [Object::Set(items), obj]) => {
+            let (key, val) = entry(obj)?;
+            let mut items = (**items).clone();
+            items.insert(key, val);
+            Ok(new_set(items))
+        }
+        ("set-member?", [Object::Set(items), obj]) => Ok(Object::Bool(
+            obj.hash_key().is_some_and(|key| items.contains_key(&key)),
+        )),
+        ("set-union", [Object::Set(a), Object::Set(b)]) => {
+            let mut items = (**a).clone();
+            items.extend(b.iter().map(|(k, v)| (k.clone(), v.clone())));
+            Ok(new_set(items))
+        }
+        ("set-intersection", [Object::Set(a), Object::Set(b)]) => Ok(new_set(
+            a.iter()
+                .filter(|(k, _)| b.contains_key(k))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        )),
+        ("set-difference", [Object::Set(a), Object::Set(b)]) => Ok(new_set(
+            a.iter()
+                .filter(|(k, _)| !b.contains_key(k))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        )),
+        ("set->list", [Object::Set(items)]) => Ok(Object::List(items.values().cloned().collect())),
+        _ => Err(invalid_args(op, &args)),
+    }
+}
+
+fn eval_bytevector_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    let new_bytevector = |bytes| Object::Bytevector(Rc::new(RefCell::new(bytes)));
+    let byte = |obj: &Object| match obj {
+        Object::Integer(n) => u8::try_from(*n).map_err(|_| type_error(op, "byte", n)),
+        _ => Err(type_error(op, "byte", obj)),
+    };
+
+    match (op, args.as_slice()) {
+        ("bytevector", _) => Ok(new_bytevector(
+            args.iter().map(byte).collect::<Result<_, _>>()?,
+        )),
+        ("make-bytevector", [len]) => Ok(new_bytevector(vec![0; list_index(op, len)?])),
+        ("make-bytevector", [len, fill]) => {
+            Ok(new_bytevector(vec![byte(fill)?; list_index(op, len)?]))
+        }
+        ("bytevector?", [obj]) => Ok(Object::Bool(matches!(obj, Object::Bytevector(_)))),
+        ("bytevector-length", [Object::Bytevector(bytes)]) => {
+            Ok(Object::Integer(bytes.borrow().len() as i64))
+        }
+        ("bytevector-u8-ref", [Object::Bytevector(bytes), index]) => {
+            let index = list_index(op, index)?;
+            match bytes.borrow().get(index) {
+                Some(b) => Ok(Object::Integer(*b as i64)),
+                None => Err(format!("bytevector-u8-ref: index {} out of range", index).into()),
+            }
+        }
+        ("bytevector-u8-set!", [Object::Bytevector(bytes), index, val]) => {
+            let index = list_index(op, index)?;
+            let val = byte(val)?;
+            match bytes.borrow_mut().get_mut(index) {
+                Some(slot) => *slot = val,
+                None => {
+                    return Err(format!("bytevector-u8-set!: index {} out of range", index).into());
+                }
+            }
+            Ok(Object::Void)
+        }
+        ("utf8->string", [Object::Bytevector(bytes)]) => String::from_utf8(bytes.borrow().clone())
+            .map(Object::String)
+            .map_err(|e| format!("utf8->string: {}", e).into()),
+        ("string->utf8", [Object::String(s)]) => Ok(new_bytevector(s.as_bytes().to_vec())),
+        _ => Err(invalid_args(op, &args)),
+    }
+}
+
+/// String builtins. Lengths and indices count characters, not bytes.
+fn eval_string_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    match (op, args.as_slice()) {
+        ("string-append", _) => args
+            .iter()
+            .map(|arg| match arg {
+                Object::String(s) => Ok(s.as_str()),
+                _ => Err(type_error("string-append", "string", arg)),
+            })
+            .collect::<Result<String, _>>()
+            .map(Object::String),
+        ("string-length", [Object::String(s)]) => Ok(Object::Integer(s.chars().count() as i64)),
+        ("substring", [Object::String(s), start, rest @ ..]) if rest.len() <= 1 => {
+            let chars: Vec<char> = s.chars().collect();
+            let start = list_index(op, start)?;
+            let end = match rest.first() {
+                Some(end) => list_index(op, end)?,
+                None => chars.len(),
+            };
+            if start > end || end > chars.len() {
+                return Err(format!("substring: range {}..{} out of bounds", start, end).into());
+            }
+            Ok(Object::String(chars[start..end].iter().collect()))
+        }
+        ("string-split", [Object::String(s)]) => Ok(Object::List(
+            s.split_whitespace()
+                .map(|part| Object::String(part.to_string()))
+                .collect(),
+        )),
+        ("string-split", [Object::String(s), Object::String(sep)]) if !sep.is_empty() => {
+            Ok(Object::List(
+                s.split(sep.as_str())
+                    .map(|part| Object::String(part.to_string()))
+                    .collect(),
+            ))
+        }
+        ("string-split", [Object::String(s), Object::Char(sep)]) => Ok(Object::List(
+            s.split(*sep)
+                .map(|part| Object::String(part.to_string()))
+                .collect(),
+        )),
+        ("string->list", [Object::String(s)]) => {
+            Ok(Object::List(s.chars().map(Object::Char).collect()))
+        }
+        ("list->string", [chars]) => proper_list(op, chars)?
+            .iter()
+            .map(|c| match c {
+                Object::Char(c) => Ok(*c),
+                _ => Err(type_error("list->string", "character", c)),
+            })
+            .collect::<Result<String, _>>()
+            .map(Object::String),
+        ("string-upcase", [Object::String(s)]) => Ok(Object::String(s.to_uppercase())),
+        ("string-downcase", [Object::String(s)]) => Ok(Object::String(s.to_lowercase())),
+        ("string-trim", [Object::String(s)]) => Ok(Object::String(s.trim().to_string())),
+        ("string-contains?", [Object::String(s), Object::String(sub)]) => {
+            Ok(Object::Bool(s.contains(sub.as_str())))
+        }
+        ("string-join", [strings, rest @ ..]) if rest.len() <= 1 => {
+            let sep = match rest.first() {
+                Some(Object::String(sep)) => sep.as_str(),
+                None => " ",
+                Some(other) => return Err(type_error("string-join", "string", other)),
+            };
+            let parts = proper_list(op, strings)?
+                .iter()
+                .map(|part| match part {
+                    Object::String(s) => Ok(s.as_str()),
+                    _ => Err(type_error("string-join", "string", part)),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Object::String(parts.join(sep)))
+        }
+        ("string-index", [Object::String(s), needle]) => {
+            let byte_index = match needle {
+                Object::Char(c) => s.find(*c),
+                Object::String(sub) => s.find(sub.as_str()),
+                _ => return Err(type_error("string-index", "char or string", needle)),
+            };
+            Ok(match byte_index {
+                Some(i) => Object::Integer(s[..i].chars().count() as i64),
+                None => Object::Bool(false),
+            })
+        }
+        _ => Err(invalid_args(op, &args)),
+    }
+}
+
+fn eval_conversion_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    let radix = match args.get(1) {
+        _ if !op.contains("number") => 10,
+        None => 10,
+        Some(Object::Integer(r @ 2..=36)) => *r as u32,
+        Some(other) => return Err(format!("{}: invalid radix: {}", op, other).into()),
+    };
+
+    match (op, args.as_slice()) {
+        ("number->string", [n, ..]) if args.len() <= 2 => {
+            let s = match n {
+                _ if radix == 10 => match n {
+                    Object::Integer(_)
+                    | Object::BigInt(_)
+                    | Object::Rational(_)
+                    | Object::Float(_) => n.to_string(),
+                    _ => return Err(type_error("number->string", "number", n)),
+                },
+                Object::Integer(_) | Object::BigInt(_) => {
+                    to_bigint("number->string", n)?.to_string_radix(radix)
+                }
+                Object::Rational(r) => format!(
+                    "{}/{}",
+                    r.numer().to_string_radix(radix),
+                    r.denom().to_string_radix(radix)
+                ),
+                _ => {
+                    return Err(
+                        format!("number->string: cannot format {} in radix {}", n, radix).into(),
+                    );
+                }
+            };
+            Ok(Object::String(s))
+        }
+        ("string->number", [Object::String(s), ..]) if args.len() <= 2 => {
+            Ok(parse_number(s, radix).unwrap_or(Object::Bool(false)))
+        }
+        ("symbol->string", [Object::Symbol(s)]) => Ok(Object::String(s.clone())),
+        ("string->symbol", [Object::String(s)]) => Ok(Object::Symbol(s.clone())),
+        ("read-from-string", [Object::String(s)]) => read_datum(&mut io::Cursor::new(s)),
+        ("write-to-string", [obj]) => Ok(Object::String(obj.to_string())),
+        _ => Err(invalid_args(op, &args)),
+    }
+}
+
+/// `(json-parse string)` reads a JSON document and `(json-stringify obj)`
+/// writes one; see the `json` module for how values map.
+#[cfg(feature = "json")]
+fn eval_json_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    use crate::json;
+
+    match (op, args.as_slice()) {
+        ("json-parse", [Object::String(s)]) => Ok(json::parse(s)?),
+        ("json-stringify", [obj]) => json::stringify(obj).map(Object::String),
+        _ => Err(invalid_args(op, &args)),
+    }
+}
+
+#[cfg(not(feature = "json"))]
+fn eval_json_op(op: &str, _args: Vec<Object>) -> Result<Object, LispError> {
+    Err(format!("{}: built without the json feature", op).into())
+}
+
+/// Reads a number the way the reader would, or as digits in `radix` when it
+/// is not 10.
+fn parse_number(s: &str, radix: u32) -> Option<Object> {
+    if radix != 10 {
+        return BigInt::parse_radix(s, radix).map(normalize_bigint);
+    }
+    match tokenize(s).ok()?.as_slice() {
+        [Token::Integer(n)] => Some(Object::Integer(*n)),
+        [Token::BigInt(n)] => Some(Object::BigInt(n.clone())),
+        [Token::Rational(n)] => Some(normalize_rational(n.clone())),
+        [Token::Float(n)] => Some(Object::Float(*n)),
+        _ => None,
+    }
+}
+
+fn proper_list<'a>(op: &str, obj: &'a Object) -> Result<&'a Vec<Object>, LispError> {
+    match obj {
+        Object::List(items) => Ok(items),
+        _ => Err(type_error(op, "proper list", obj)),
+    }
+}
+
+fn list_index(op: &str, obj: &Object) -> Result<usize, LispError> {
+    match obj {
+        Object::Integer(n) if *n >= 0 => Ok(*n as usize),
+        _ => Err(format!("{}: index must be a non-negative integer: {}", op, obj).into()),
+    }
+}
+
+fn flatten_into(items: &[Object], flat: &mut Vec<Object>) {
+    for item in items {
+        match item {
+            Object::List(nested) => flatten_into(nested, flat),
+            _ => flat.push(item.clone()),
+        }
+    }
+}
+
+fn eval_define(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Object, LispError> {
+    // (define (name args...) body...) => (define name (lambda (args...) body...))
+    if let Some(signature @ (Object::List(_) | Object::DottedList(..))) = list.get(1) {
+        let (name, params) = split_signature(signature).ok_or_else(|| syntax_error("define"))?;
+        let mut lambda = vec![Object::Symbol("lambda".to_string()), params];
+        lambda.extend_from_slice(&list[2..]);
+        return eval_define(&[list[0].clone(), name.clone(), Object::List(lambda)], env);
+    }
+
+    if list.len() != 3 {
+        return Err(arity_error("define", list.len() - 1));
+    };
+
+    let sym = if let Object::Symbol(s) = &list[1] {
+        s.clone()
+    } else {
+        return Err(syntax_error("define"));
+    };
+
+    let val = eval(&list[2], env)?;
+    env.borrow_mut().set(&sym, val);
+
+    Ok(Object::Void)
+}
+
+/// Defines a macro with `(define-macro (name args...) body...)` or
+/// `(define-macro name (lambda (args...) body...))`. The macro receives its
+/// arguments unevaluated and its result is evaluated in place of the call.
+fn eval_define_macro(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Object, LispError> {
+    let (name, lambda) = match list.get(1).and_then(split_signature) {
+        Some((name, params)) if list.len() >= 3 => {
+            let mut lambda = vec![Object::Symbol("lambda".to_string()), params];
+            lambda.extend_from_slice(&list[2..]);
+            (name, eval_function_definition(&lambda, env)?)
+        }
+        None if list.len() == 3 => (&list[1], eval(&list[2], env)?),
+        _ => return Err(syntax_error("define-macro")),
+    };
+
+    match (name, lambda) {
+        (Object::Symbol(name), Object::Lambda(lambda)) => {
+            env.borrow_mut().set(name, Object::Macro(lambda));
+            Ok(Object::Void)
+        }
+        _ => Err(syntax_error("define-macro")),
+    }
+}
+
+/// Splits a `(name args...)` or `(name args... . rest)` signature into the
+/// name and the lambda parameter list.
+fn split_signature(signature: &Object) -> Option<(&Object, Object)> {
+    match signature {
+        Object::List(items) => items
+            .split_first()
+            .map(|(name, params)| (name, Object::List(params.to_vec()))),
+        Object::DottedList(items, rest) => items
+            .split_first()
+            .map(|(name, params)| (name, Object::dotted(params.to_vec(), (**rest).clone()))),
+        _ => None,
+    }
+}
+
+fn eval_define_syntax(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Object, LispError> {
+    if list.len() != 3 {
+        return Err(arity_error("define-syntax", list.len() - 1));
+    }
+
+    match (&list[1], eval(&list[2], env)?) {
+        (Object::Symbol(name), syntax @ Object::Syntax(_)) => {
+            env.borrow_mut().set(name, syntax);
+            Ok(Object::Void)
+        }
+        _ => Err(syntax_error("define-syntax")),
+    }
+}
+
+fn eval_set(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Object, LispError> {
+    if list.len() != 3 {
+        return Err(arity_error("set!", list.len() - 1));
+    };
+
+    let sym = if let Object::Symbol(s) = &list[1] {
+        s.clone()
+    } else {
+        return Err(syntax_error("set!"));
+    };
+
+    let val = eval(&list[2], env)?;
+    if !env.borrow_mut().assign(&sym, val) {
+        return Err(LispError::UnboundSymbol(sym.to_string()));
+    }
+
+    Ok(Object::Void)
+}
+
+fn eval_quote(list: &[Object]) -> Result<Object, LispError> {
+    if list.len() != 2 {
+        return Err(arity_error("quote", list.len() - 1));
+    }
+
+    Ok(list[1].clone())
+}
+
+/// Arithmetic folds over any number of operands: `(+)` is 0, `(*)` is 1,
+/// `(- x)` negates and `(/ x)` takes the reciprocal. `<`, `>`, `<=`, `>=` and
+/// `=` hold when they hold for every adjacent pair, so `(< 0 x 10)` is a
+/// range check; `!=` takes exactly two operands.
+fn eval_arithmetic_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    match (op, args.as_slice()) {
+        ("+" | "*", _) => {
+            let identity = Object::Integer(if op == "+" { 0 } else { 1 });
+            args.iter()
+                .try_fold(identity, |acc, x| numeric_op(op, &acc, x))
+        }
+        ("-" | "/", []) => Err(arity_error(op, args.len())),
+        ("-", [x]) => numeric_op(op, &Object::Integer(0), x),
+        ("/", [x]) => numeric_op(op, &Object::Integer(1), x),
+        ("-" | "/", [first, rest @ ..]) => rest
+            .iter()
+            .try_fold(first.clone(), |acc, x| numeric_op(op, &acc, x)),
+        ("<" | ">" | "<=" | ">=" | "=", [_, ..]) => {
+            // Every operand is type checked, even after the result is known.
+            let mut holds = true;
+            for pair in args.windows(2) {
+                holds &= numeric_op(op, &pair[0], &pair[1])? == Object::Bool(true);
+            }
+            if let [x] = args.as_slice() {
+                to_float(op, x)?;
+            }
+            Ok(Object::Bool(holds))
+        }
+        (_, [left, right]) => numeric_op(op, left, right),
+        _ => Err(arity_error("infix operator", args.len())),
+    }
+}
+
+/// Applies a binary arithmetic or comparison operator, promoting both
+/// operands to the wider of their numeric types.
+fn numeric_op(op: &str, left: &Object, right: &Object) -> Result<Object, LispError> {
+    match (left, right) {
+        (Object::Integer(l), Object::Integer(r)) => eval_integer_op(op, *l, *r),
+        (Object::Float(_), _) | (_, Object::Float(_)) => {
+            eval_float_op(op, to_float(op, left)?, to_float(op, right)?)
+        }
+        (Object::Rational(_), _) | (_, Object::Rational(_)) => {
+            eval_rational_op(op, &to_rational(op, left)?, &to_rational(op, right)?)
+        }
+        _ => eval_bigint_op(op, &to_bigint(op, left)?, &to_bigint(op, right)?),
+    }
+}
+
+fn to_float(op: &str, obj: &Object) -> Result<f64, LispError> {
+    match obj {
+        Object::Integer(n) => Ok(*n as f64),
+        Object::BigInt(n) => Ok(n.to_f64()),
+        Object::Rational(n) => Ok(n.to_f64()),
+        Object::Float(n) => Ok(*n),
+        _ => Err(type_error(op, "number", obj)),
+    }
+}
+
+fn to_bigint(op: &str, obj: &Object) -> Result<BigInt, LispError> {
+    match obj {
+        Object::Integer(n) => Ok(BigInt::from_i64(*n)),
+        Object::BigInt(n) => Ok(n.clone()),
+        _ if has_type(obj, "number") => Err(type_error(op, "integer", obj)),
+        _ => Err(type_error(op, "number", obj)),
+    }
+}
+
+fn to_rational(op: &str, obj: &Object) -> Result<Rational, LispError> {
+    match obj {
+        Object::Rational(n) => Ok(n.clone()),
+        _ => Ok(Rational::from_bigint(to_bigint(op, obj)?)),
+    }
+}
+
+/// Demotes a bignum result back to a fixnum when it fits.
+fn normalize_bigint(n: BigInt) -> Object {
+    match n.to_i64() {
+        Some(i) => Object::Integer(i),
+        None => Object::BigInt(n),
+    }
+}
+
+/// Demotes a rational result to an integer when the denominator is 1.
+fn normalize_rational(n: Rational) -> Object {
+    if n.is_integer() {
+        normalize_bigint(n.numer().clone())
+    } else {
+        Object::Rational(n)
+    }
+}
+
+fn eval_integer_op(op: &str, left_val: i64, right_val: i64) -> Result<Object, LispError> {
+    let checked = match op {
+        "+" => left_val.checked_add(right_val),
+        "-" => left_val.checked_sub(right_val),
+        "*" => left_val.checked_mul(right_val),
+        "/" => {
+            return eval_rational_op(
+                op,
+                &Rational::from_bigint(BigInt::from_i64(left_val)),
+                &Rational::from_bigint(BigInt::from_i64(right_val)),
+            );
+        }
+        "<" => return Ok(Object::Bool(left_val < right_val)),
+        ">" => return Ok(Object::Bool(left_val > right_val)),
+        "<=" => return Ok(Object::Bool(left_val <= right_val)),
+        ">=" => return Ok(Object::Bool(left_val >= right_val)),
+        "=" => return Ok(Object::Bool(left_val == right_val)),
+        "!=" => return Ok(Object::Bool(left_val != right_val)),
+        _ => return Err(format!("Invalid infix operator: {}", op).into()),
+    };
+
+    match checked {
+        Some(n) => Ok(Object::Integer(n)),
+        None => eval_bigint_op(
+            op,
+            &BigInt::from_i64(left_val),
+            &BigInt::from_i64(right_val),
+        ),
+    }
+}
+
+fn eval_bigint_op(op: &str, left_val: &BigInt, right_val: &BigInt) -> Result<Object, LispError> {
+    match op {
+        "+" => Ok(normalize_bigint(left_val.add(right_val))),
+        "-" => Ok(normalize_bigint(left_val.sub(right_val))),
+        "*" => Ok(normalize_bigint(left_val.mul(right_val))),
+        "/" => eval_rational_op(
+            op,
+            &Rational::from_bigint(left_val.clone()),
+            &Rational::from_bigint(right_val.clone()),
+        ),
+        "<" => Ok(Object::Bool(left_val < right_val)),
+        ">" => Ok(Object::Bool(left_val > right_val)),
+        "<=" => Ok(Object::Bool(left_val <= right_val)),
+        ">=" => Ok(Object::Bool(left_val >= right_val)),
+        "=" => Ok(Object::Bool(left_val == right_val)),
+        "!=" => Ok(Object::Bool(left_val != right_val)),
+        _ => Err(format!("Invalid infix operator: {}", op).into()),
+    }
+}
+
+fn eval_rational_op(
+    op: &str,
+    left_val: &Rational,
+    right_val: &Rational,
+) -> Result<Object, LispError> {
+    match op {
+        "+" => Ok(normalize_rational(left_val.add(right_val))),
+        "-" => Ok(normalize_rational(left_val.sub(right_val))),
+        "*" => Ok(normalize_rational(left_val.mul(right_val))),
+        "/" => match left_val.div(right_val) {
+            Some(n) => Ok(normalize_rational(n)),
+            None => Err(LispError::DivideByZero),
+        },
+        "<" => Ok(Object::Bool(left_val < right_val)),
+        ">" => Ok(Object::Bool(left_val > right_val)),
+        "<=" => Ok(Object::Bool(left_val <= right_val)),
+        ">=" => Ok(Object::Bool(left_val >= right_val)),
+        "=" => Ok(Object::Bool(left_val == right_val)),
+        "!=" => Ok(Object::Bool(left_val != right_val)),
+        _ => Err(format!("Invalid infix operator: {}", op).into()),
+    }
+}
+
+fn eval_float_op(op: &str, left_val: f64, right_val: f64) -> Result<Object, LispError> {
+    match op {
+        "+" => Ok(Object::Float(left_val + right_val)),
+        "-" => Ok(Object::Float(left_val - right_val)),
+        "*" => Ok(Object::Float(left_val * right_val)),
+        "/" => Ok(Object::Float(left_val / right_val)),
+        "<" => Ok(Object::Bool(left_val < right_val)),
+        ">" => Ok(Object::Bool(left_val > right_val)),
+        "<=" => Ok(Object::Bool(left_val <= right_val)),
+        ">=" => Ok(Object::Bool(left_val >= right_val)),
+        "=" => Ok(Object::Bool(left_val == right_val)),
+        "!=" => Ok(Object::Bool(left_val != right_val)),
+        _ => Err(format!("Invalid infix operator: {}", op).into()),
+    }
+}
+
+fn eval_math_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    let zero = Object::Integer(0);
+
+    match (op, args.as_slice()) {
+        ("abs", [x]) => match numeric_op("<", x, &zero)? {
+            Object::Bool(true) => numeric_op("-", &zero, x),
+            _ => numeric_op("+", x, &zero),
+        },
+        ("min" | "max", [first, rest @ ..]) => {
+            let replace_if = if op == "min" { "<" } else { ">" };
+            let mut best = numeric_op("+", first, &zero)?;
+            for x in rest {
+                if numeric_op(replace_if, x, &best)? == Object::Bool(true) {
+                    best = x.clone();
+                }
+            }
+            // Like other arithmetic, any inexact argument makes the result inexact.
+            if args.iter().any(|x| matches!(x, Object::Float(_))) {
+                best = Object::Float(to_float(op, &best)?);
+            }
+            Ok(best)
+        }
+        ("quotient" | "remainder" | "modulo", [n, d]) => {
+            let n = to_bigint(op, n)?;
+            let d = to_bigint(op, d)?;
+            let (q, r) = n.div_rem(&d).ok_or(LispError::DivideByZero)?;
+            let result = match op {
+                "quotient" => q,
+                "modulo" if !r.is_zero() && r.is_negative() != d.is_negative() => r.add(&d),
+                _ => r,
+            };
+            Ok(normalize_bigint(result))
+        }
+        ("expt", [base, exponent]) => expt(base, exponent),
+        ("gcd" | "lcm", _) => {
+            let init = BigInt::from_i64(if op == "gcd" { 0 } else { 1 });
+            args.iter()
+                .try_fold(init, |acc, x| {
+                    let x = to_bigint(op, x)?;
+                    Ok(match op {
+                        "gcd" => acc.gcd(&x),
+                        _ if x.is_zero() || acc.is_zero() => BigInt::zero(),
+                        _ => {
+                            let (q, _) = acc
+                                .mul(&x)
+                                .abs()
+                                .div_rem(&acc.gcd(&x))
+                                .expect("gcd of non-zero values is non-zero");
+                            q
+                        }
+                    })
+                })
+                .map(normalize_bigint)
+        }
+        ("floor" | "ceiling" | "round" | "truncate", [x]) => match x {
+            Object::Integer(_) | Object::BigInt(_) => Ok(x.clone()),
+            Object::Float(f) => Ok(Object::Float(match op {
+                "floor" => f.floor(),
+                "ceiling" => f.ceil(),
+                "round" => f.round_ties_even(),
+                _ => f.trunc(),
+            })),
+            Object::Rational(r) => Ok(normalize_bigint(round_rational(op, r))),
+            _ => Err(type_error(op, "number", x)),
+        },
+        _ => Err(invalid_args(op, &args)),
+    }
+}
+
+/// Bitwise operations on fixnums, using two's complement for negatives.
+fn eval_bitwise_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    let args = args
+        .iter()
+        .map(|arg| match arg {
+            Object::Integer(n) => Ok(*n),
+            _ => Err(type_error(op, "fixnum", arg)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match (op, args.as_slice()) {
+        ("bit-and", _) => Ok(Object::Integer(args.iter().fold(-1, |acc, n| acc & n))),
+        ("bit-or", _) => Ok(Object::Integer(args.iter().fold(0, |acc, n| acc | n))),
+        ("bit-xor", _) => Ok(Object::Integer(args.iter().fold(0, |acc, n| acc ^ n))),
+        ("bit-not", [n]) => Ok(Object::Integer(!n)),
+        ("bit-count", [n]) => Ok(Object::Integer(if *n < 0 {
+            (!n).count_ones()
+        } else {
+            n.count_ones()
+        } as i64)),
+        ("arithmetic-shift", [n, shift]) if *shift >= 0 => {
+            let shift = u32::try_from(*shift).map_err(|_| "arithmetic-shift: shift too large")?;
+            match n
+                .checked_shl(shift)
+                .filter(|shifted| shifted >> shift == *n)
+            {
+                Some(shifted) => Ok(Object::Integer(shifted)),
+                // Overflowing left shifts promote to a bignum.
+                None => expt(&Object::Integer(2), &Object::Integer(shift as i64))
+                    .and_then(|pow| numeric_op("*", &Object::Integer(*n), &pow)),
+            }
+        }
+        ("arithmetic-shift", [n, shift]) => Ok(Object::Integer(n >> shift.unsigned_abs().min(63))),
+        _ => Err(arity_error(op, args.len())),
+    }
+}
+
+/// Float functions; exact arguments are converted to floats first.
+fn eval_transcendental_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    let args = args
+        .iter()
+        .map(|x| to_float(op, x))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let val = match (op, args.as_slice()) {
+        ("sqrt", [x]) => x.sqrt(),
+        ("exp", [x]) => x.exp(),
+        ("log", [x]) => x.ln(),
+        ("log", [x, base]) => x.log(*base),
+        ("sin", [x]) => x.sin(),
+        ("cos", [x]) => x.cos(),
+        ("tan", [x]) => x.tan(),
+        ("atan", [x]) => x.atan(),
+        ("atan", [y, x]) => y.atan2(*x),
+        _ => return Err(arity_error(op, args.len())),
+    };
+    Ok(Object::Float(val))
+}
+
+/// Raises `base` to `exponent`, staying exact for exact bases and integer
+/// exponents.
+fn expt(base: &Object, exponent: &Object) -> Result<Object, LispError> {
+    let one = Object::Integer(1);
+    match exponent {
+        Object::Integer(e) if !matches!(base, Object::Float(_)) => {
+            let mut result = one.clone();
+            let mut square = base.clone();
+            let mut e_abs = e.unsigned_abs();
+            while e_abs > 0 {
+                if e_abs & 1 == 1 {
+                    result = numeric_op("*", &result, &square)?;
+                }
+                square = numeric_op("*", &square, &square)?;
+                e_abs >>= 1;
+            }
+            if *e < 0 {
+                numeric_op("/", &one, &result)
+            } else {
+                Ok(result)
+            }
+        }
+        _ => Ok(Object::Float(
+            to_float("expt", base)?.powf(to_float("expt", exponent)?),
+        )),
+    }
+}
+
+/// Rounds an exact fraction to an integer; `round` rounds halves to even.
+fn round_rational(op: &str, r: &Rational) -> BigInt {
+    let one = BigInt::from_i64(1);
+    let (q, rem) = r
+        .numer()
+        .div_rem(r.denom())
+        .expect("denominator is non-zero");
+    match op {
+        "floor" if rem.is_negative() => q.sub(&one),
+        "ceiling" if !rem.is_zero() && !rem.is_negative() => q.add(&one),
+        "round" => {
+            let twice = rem.abs().mul(&BigInt::from_i64(2));
+            let away = match twice.cmp(r.denom()) {
+                std::cmp::Ordering::Greater => true,
+                // Exactly halfway: round towards the even neighbour.
+                std::cmp::Ordering::Equal => {
+                    let (_, parity) = q
+                        .div_rem(&BigInt::from_i64(2))
+                        .expect("divisor is non-zero");
+                    !parity.is_zero()
+                }
+                std::cmp::Ordering::Less => false,
+            };
+            match (away, rem.is_negative()) {
+                (false, _) => q,
+                (true, true) => q.sub(&one),
+                (true, false) => q.add(&one),
+            }
+        }
+        _ => q,
+    }
+}
+
+fn eval_char_conversion(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    check_arity(op, &args, 1)?;
+    match (op, &args[0]) {
+        ("char->integer", Object::Char(c)) => Ok(Object::Integer(*c as i64)),
+        ("integer->char", Object::Integer(n)) => u32::try_from(*n)
+            .ok()
+            .and_then(char::from_u32)
+            .map(Object::Char)
+            .ok_or_else(|| format!("Invalid character code: {}", n).into()),
+        _ => Err(invalid_args(op, &args)),
+    }
+}
+
+fn eval_char_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    check_arity(op, &args, 1)?;
+    let Object::Char(c) = args[0] else {
+        return Err(type_error(op, "char", &args[0]));
+    };
+
+    // Case mappings that expand to several characters leave `c` unchanged.
+    let single = |mapped: String| {
+        let mut chars = mapped.chars();
+        match (chars.next(), chars.next()) {
+            (Some(m), None) => m,
+            _ => c,
+        }
+    };
+    match op {
+        "char-alphabetic?" => Ok(Object::Bool(c.is_alphabetic())),
+        "char-numeric?" => Ok(Object::Bool(c.is_numeric())),
+        "char-whitespace?" => Ok(Object::Bool(c.is_whitespace())),
+        "char-upcase" => Ok(Object::Char(single(c.to_uppercase().collect()))),
+        "char-downcase" => Ok(Object::Char(single(c.to_lowercase().collect()))),
+        _ => Err(format!("Unknown character operation: {}", op).into()),
+    }
+}
+
+/// Evaluates the arguments of a builtin call, i.e. everything after the head.
+fn eval_args(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Vec<Object>, LispError> {
+    list[1..].iter().map(|obj| eval(obj, env)).collect()
+}
+
+fn arity_error(name: impl fmt::Display, got: usize) -> LispError {
+    LispError::ArityMismatch {
+        name: name.to_string(),
+        expected: None,
+        got,
+    }
+}
+
+fn type_error(name: &str, expected: &str, got: impl fmt::Display) -> LispError {
+    LispError::TypeMismatch {
+        name: name.to_string(),
+        expected: expected.to_string(),
+        got: got.to_string(),
+    }
+}
+
+/// The error for arguments that fit none of the forms of the builtin `op`,
+/// judged by its signature: an arity error if it takes no form with that
+/// many arguments, otherwise a type error for the first argument of the
+/// wrong type.
+fn invalid_args(op: &str, args: &[Object]) -> LispError {
+    let params: Vec<&str> = signature(op).split_whitespace().collect();
+    let variadic = params.last().is_some_and(|param| param.ends_with("..."));
+    let required = params
+        .iter()
+        .filter(|param| !param.ends_with(['?', '.']))
+        .count();
+    if args.len() < required || (!variadic && args.len() > params.len()) {
+        let expected = match variadic {
+            true => format!("at least {}", required),
+            false if required == params.len() => required.to_string(),
+            false => format!("{} to {}", required, params.len()),
+        };
+        return LispError::ArityMismatch {
+            name: op.to_string(),
+            expected: Some(expected),
+            got: args.len(),
+        };
+    }
+    for (i, arg) in args.iter().enumerate() {
+        let param = params[i.min(params.len() - 1)].trim_end_matches(['?', '.']);
+        if !param.split('|').any(|ty| has_type(arg, ty)) {
+            return type_error(op, &param.replace('|', " or "), arg);
+        }
+    }
+    LispError::Eval(format!(
+        "{}: invalid arguments: {}",
+        op,
+        Object::List(args.to_vec())
+    ))
+}
+
+/// The parameter types of a builtin whose arguments are checked by
+/// `invalid_args`, as names accepted by `has_type`. A name may list
+/// alternatives separated by `|`, end in `?` if the argument is optional,
+/// or end in `...` if it takes any number of arguments.
+fn signature(op: &str) -> &'static str {
+    match op {
+        "open-output-string" | "make-channel" | "command-line" | "make-hash-table" => "",
+        "open-input-file" | "open-output-file" | "open-input-string" | "getenv" | "system"
+        | "process-output" | "string->utf8" | "string-length" | "string->list"
+        | "string-upcase" | "string-downcase" | "string-trim" | "string->symbol"
+        | "read-from-string" | "json-parse" => "string",
+        "get-output-string" | "close-port" | "tcp-accept" => "port",
+        "read-line" | "read-char" => "port?",
+        "write-char" => "char port?",
+        "spawn" => "procedure",
+        "pmap" => "procedure list",
+        "channel-send" => "channel obj",
+        "channel-recv" => "channel",
+        "await" => "future",
+        "join" => "thread",
+        "atom" | "port?" | "vector?" | "hash-table?" | "set?" | "bytevector?"
+        | "write-to-string" | "json-stringify" | "class?" | "instance?" => "obj",
+        "deref" => "atom",
+        "reset!" => "atom obj",
+        "swap!" => "atom procedure obj...",
+        "after" | "every" => "number procedure",
+        "cancel-timer" => "timer",
+        "tcp-connect" => "string integer",
+        "tcp-listen" => "integer string?",
+        "read-csv" => "string|port obj?",
+        "write-csv" => "list port?",
+        "regex-match?" | "regex-find" | "regex-split" => "string string",
+        "regex-replace" => "string string string",
+        "sleep" => "number",
+        "setenv" => "string string",
+        "exit" => "integer|boolean?",
+        "format" => "boolean|string obj...",
+        "make-vector" => "integer obj?",
+        "list->vector" | "list->string" => "list",
+        "vector-length" | "vector->list" => "vector",
+        "vector-ref" => "vector integer",
+        "vector-set!" => "vector integer obj",
+        "hash-set!" => "hash-table obj obj",
+        "hash-ref" => "hash-table obj obj?",
+        "hash-remove!" => "hash-table obj",
+        "hash-keys" | "hash-count" => "hash-table",
+        "hash-for-each" => "hash-table procedure",
+        "set-add" | "set-member?" => "set obj",
+        "set-union" | "set-intersection" | "set-difference" => "set set",
+        "set->list" => "set",
+        "make-bytevector" => "integer integer?",
+        "bytevector-length" | "utf8->string" => "bytevector",
+        "bytevector-u8-ref" => "bytevector integer",
+        "bytevector-u8-set!" => "bytevector integer integer",
+        "substring" => "string integer integer?",
+        "string-split" => "string string|char?",
+        "string-contains?" => "string string",
+        "string-join" => "list string?",
+        "string-index" => "string char|string",
+        "number->string" => "number integer?",
+        "string->number" => "string integer?",
+        "symbol->string" => "symbol",
+        "abs" | "floor" | "ceiling" | "round" | "truncate" => "number",
+        "min" | "max" => "number number...",
+        "quotient" | "remainder" | "modulo" | "expt" => "number number",
+        "char->integer" => "char",
+        "integer->char" => "integer",
+        "make-instance" => "class obj...",
+        "make-class" => "symbol class|boolean list obj...",
+        "gensym" => "string?",
+        "eval" => "obj environment?",
+        "load" => "string environment?",
+        "load-plugin" => "string",
+        "error" => "obj obj...",
+        "make-condition" => "symbol string obj...",
+        "slot-ref" => "instance symbol",
+        "slot-set!" => "instance symbol obj",
+        "class-of" => "instance",
+        "is-a?" => "obj class",
+        _ => "obj...",
+    }
+}
+
+/// Whether `obj` has the type named in a builtin signature: a name returned
+/// by `type-of`, or `obj` for anything, `list` for proper and empty lists
+/// and `number` for any number.
+fn has_type(obj: &Object, ty: &str) -> bool {
+    match ty {
+        "obj" => true,
+        "list" => matches!(obj, Object::List(_)),
+        "number" => matches!(
+            obj,
+            Object::Integer(_) | Object::BigInt(_) | Object::Rational(_) | Object::Float(_)
+        ),
+        _ => type_of(obj) == ty,
+    }
+}
+
+/// The error for a special form of the wrong shape, reading `Invalid what`.
+fn syntax_error(what: impl fmt::Display) -> LispError {
+    LispError::Syntax(format!("Invalid {}", what))
+}
+
+fn file_error(path: &str, reason: impl fmt::Display) -> LispError {
+    LispError::FileError {
+        path: path.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+fn check_arity(name: &str, args: &[Object], expected: usize) -> Result<(), LispError> {
+    if args.len() != expected {
+        return Err(LispError::ArityMismatch {
+            name: name.to_string(),
+            expected: Some(expected.to_string()),
+            got: args.len(),
+        });
+    }
+    Ok(())
+}
+
+fn eval_boolean_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    check_arity(op, &args, 1)?;
+
+    match op {
+        "not" => Ok(Object::Bool(!is_truthy(&args[0]))),
+        "boolean?" => Ok(Object::Bool(matches!(args[0], Object::Bool(_)))),
+        _ => Err(format!("Unknown boolean operation: {}", op).into()),
+    }
+}
+
+/// `(gensym)` or `(gensym "prefix")` returns a symbol distinct from every
+/// symbol previously produced by gensym.
+fn eval_gensym(args: Vec<Object>) -> Result<Object, LispError> {
+    let prefix = match args.as_slice() {
+        [] => "g",
+        [Object::String(s)] => s.as_str(),
+        [Object::Symbol(s)] => s.as_str(),
+        _ => return Err(invalid_args("gensym", &args)),
+    };
+
+    let n = SYMBOL_COUNTER.fetch_add(1, Ordering::Relaxed);
+    Ok(Object::Symbol(format!("{}#{}", prefix, n)))
+}
+
+/// `(eval expr [env])` evaluates the value of `expr` as code, in `env` if
+/// given and in the current environment otherwise.
+fn eval_eval(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Step, LispError> {
+    let args = eval_args(list, env)?;
+    match args.as_slice() {
+        [expr] => Ok(Step::Tail(expr.clone(), env.clone())),
+        [expr, Object::Environment(EnvRef(target))] => Ok(Step::Tail(expr.clone(), target.clone())),
+        _ => Err(invalid_args("eval", &args)),
+    }
+}
+
+/// `(values x)` is just `x`; any other number of values is packed into a
+/// `Values` object that `call-with-values` spreads back out.
+fn eval_values(mut args: Vec<Object>) -> Result<Object, LispError> {
+    if args.len() == 1 {
+        Ok(args.remove(0))
+    } else {
+        Ok(Object::Values(args))
+    }
+}
+
+fn eval_call_with_values(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Step, LispError> {
+    let procs = eval_args(list, env)?;
+    let vals = produce_values(&procs)?;
+    apply_procedure_step("call-with-values", &procs[1], vals)
+}
+
+/// `call-with-values` as a procedure value, where the consumer is not in
+/// tail position.
+fn apply_call_with_values(procs: Vec<Object>) -> Result<Object, LispError> {
+    let vals = produce_values(&procs)?;
+    apply_procedure("call-with-values", &procs[1], vals)
+}
+
+/// Calls the producer of `(call-with-values producer consumer)` and returns
+/// the values it produced.
+fn produce_values(procs: &[Object]) -> Result<Vec<Object>, LispError> {
+    check_arity("call-with-values", procs, 2)?;
+    let vals = match apply_procedure("call-with-values", &procs[0], Vec::new())? {
+        Object::Values(vals) => vals,
+        val => vec![val],
+    };
+    Ok(vals)
+}
+
+/// Escape-only `call/cc`: the continuation passed to the procedure can be
+/// invoked to return from the `call/cc` form early, but not re-entered once
+/// that form has returned.
+fn eval_call_cc(args: Vec<Object>) -> Result<Object, LispError> {
+    check_arity("call/cc", &args, 1)?;
+
+    let id = CONTINUATION_COUNTER.fetch_add(1, Ordering::Relaxed);
+    match apply_procedure("call/cc", &args[0], vec![Object::Continuation(id)]) {
+        Err(LispError::Escape {
+            continuation,
+            value,
+        }) if continuation == id => Ok(value),
+        result => result,
+    }
+}
+
+/// `(load "path" [env])` evaluates every top-level form of a file, in the
+/// global environment unless another one is given, and returns the value of
+/// the last form.
+fn eval_load(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Object, LispError> {
+    let args = eval_args(list, env)?;
+    let (path, mut load_env) = match args.as_slice() {
+        [Object::String(path)] => (path, Env::root(env)),
+        [Object::String(path), Object::Environment(EnvRef(load_env))] => (path, load_env.clone()),
+        _ => return Err(invalid_args("load", &args)),
+    };
+
+    let source = fs::read_to_string(path).map_err(|e| file_error(path, e))?;
+    let tokens = tokenize_spanned(&source).map_err(|e| format!("{}: {}", path, e))?;
+    let program = parse_program_spanned(&tokens).map_err(|e| format!("{}: {}", path, e))?;
+
+    // A bodiless `define-module` leaves its module current; the rest of the
+    // file is then evaluated in it, and it stops being current afterwards.
+    let modules = CURRENT_MODULE.with(|current| current.borrow().len());
+    LOAD_DEPTH.with(|depth| depth.set(depth.get() + 1));
+    let result = program.iter().try_fold(Object::Void, |_, form| {
+        let val = eval(&form.object, &mut load_env).map_err(|err| match err {
+            // Errors of nested loads are already placed in their own file.
+            LispError::Traced(mut traced) if traced.loaded_from.is_none() => {
+                let span = error_span(&traced.forms, form, &program);
+                traced.loaded_from = Some((path.clone(), span));
+                LispError::Traced(traced)
+            }
+            err => err,
+        })?;
+        if let Some(module_env) = file_module_env(modules) {
+            load_env = module_env;
+        }
+        Ok(val)
+    });
+    LOAD_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    CURRENT_MODULE.with(|current| current.borrow_mut().truncate(modules));
+    result
+}
+
+/// The scope of the module a bodiless `define-module` made current, if one
+/// was pushed above the `modules` that were current when the load began.
+fn file_module_env(modules: usize) -> Option<Rc<RefCell<Env>>> {
+    let name = CURRENT_MODULE.with(|current| current.borrow()[modules..].last().cloned())?;
+    MODULES.with(|registry| {
+        registry
+            .borrow()
+            .get(&name)
+            .map(|module| module.env.clone())
+    })
+}
+
+/// `(load-plugin path)` loads a dynamic library exporting a `Plugin` entry
+/// point and registers its procedures in the global environment, returning
+/// the plugin's name. Libraries stay loaded, since the procedures they
+/// registered point into them.
+#[cfg(feature = "plugins")]
+fn eval_load_plugin(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Object, LispError> {
+    use crate::{PLUGIN_ENTRY, PluginEntry};
+
+    let args = eval_args(list, env)?;
+    let [Object::String(path)] = args.as_slice() else {
+        return Err(invalid_args("load-plugin", &args));
+    };
+
+    // SAFETY: loading runs the library's initializers and trusts its entry
+    // point to have the `PluginEntry` signature. Both hold for plugins built
+    // against this version of lisp-rs, which is all `Plugin` supports.
+    let library = unsafe { libloading::Library::new(path) }.map_err(|e| file_error(path, e))?;
+    let entry = unsafe { library.get::<PluginEntry>(PLUGIN_ENTRY.as_bytes()) }
+        .map_err(|e| format!("load-plugin: {}: {}", path, e))?;
+    let plugin = entry();
+    plugin.register(&mut Env::root(env).borrow_mut());
+    let name = plugin.name().to_string();
+    drop(plugin);
+    std::mem::forget(library);
+    Ok(Object::String(name))
+}
+
+#[cfg(not(feature = "plugins"))]
+fn eval_load_plugin(_list: &[Object], _env: &mut Rc<RefCell<Env>>) -> Result<Object, LispError> {
+    Err("load-plugin: built without the plugins feature".into())
+}
+
+/// `(define-module (name...) body...)` evaluates `body` in a fresh scope
+/// extending the global environment and registers it under `(name...)`.
+/// Without a body, as the first form of a loaded file, the rest of the file
+/// makes up the module instead.
+fn eval_define_module(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Object, LispError> {
+    let name = match list.get(1) {
+        Some(name @ Object::List(_)) => name.to_string(),
+        _ => return Err(syntax_error("define-module")),
+    };
+    let bodiless = list.len() == 2;
+    if bodiless && LOAD_DEPTH.with(|depth| depth.get()) == 0 {
+        return Err("define-module without a body must be in a loaded file".into());
+    }
+
+    let mut module_env = Rc::new(RefCell::new(Env::extend(Env::root(env))));
+    MODULES.with(|modules| {
+        modules.borrow_mut().insert(
+            name.clone(),
+            Module {
+                env: module_env.clone(),
+                exports: Vec::new(),
+            },
+        )
+    });
+
+    CURRENT_MODULE.with(|current| current.borrow_mut().push(name));
+    if bodiless {
+        // `eval_load` switches to the module and pops it at the end of file.
+        return Ok(Object::Void);
+    }
+    let result = list[2..]
+        .iter()
+        .try_for_each(|form| eval(form, &mut module_env).map(drop));
+    CURRENT_MODULE.with(|current| current.borrow_mut().pop());
+    result.map(|_| Object::Void)
+}
+
+/// `(export name...)` adds names to the exports of the module being defined.
+fn eval_export(list: &[Object]) -> Result<Object, LispError> {
+    let names = list[1..]
+        .iter()
+        .map(|name| match name {
+            Object::Symbol(s) => Ok(s.clone()),
+            _ => Err(syntax_error(format_args!("export: {}", name))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let current = CURRENT_MODULE.with(|current| current.borrow().last().cloned());
+    let current = current.ok_or_else(|| "export used outside of define-module".to_string())?;
+    MODULES.with(|modules| {
+        if let Some(module) = modules.borrow_mut().get_mut(&current) {
+            module.exports.extend(names);
+        }
+    });
+    Ok(Object::Void)
+}
+
+/// `(import (name...) ...)` binds every export of the named modules in the
+/// current scope.
+fn eval_import(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Object, LispError> {
+    for spec in &list[1..] {
+        let Object::List(_) = spec else {
+            return Err(syntax_error(format_args!("import: {}", spec)));
+        };
+        let name = spec.to_string();
+
+        let bindings = MODULES.with(|modules| {
+            let modules = modules.borrow();
+            let module = modules
+                .get(&name)
+                .ok_or_else(|| format!("Unknown module: {}", name))?;
+            module
+                .exports
+                .iter()
+                .map(|export| match module.env.borrow().get(export) {
+                    Some(val) => Ok((export.clone(), val)),
+                    None => Err(format!("Module {} does not define {}", name, export).into()),
+                })
+                .collect::<Result<Vec<_>, LispError>>()
+        })?;
+
+        for (export, val) in bindings {
+            env.borrow_mut().set(&export, val);
+        }
+    }
+    Ok(Object::Void)
+}
+
+/// `(dynamic-wind before thunk after)` runs `after` however `thunk` is left,
+/// whether it returns normally, raises an error, or escapes through a
+/// continuation.
+fn eval_dynamic_wind(args: Vec<Object>) -> Result<Object, LispError> {
+    check_arity("dynamic-wind", &args, 3)?;
+
+    apply_procedure("dynamic-wind", &args[0], Vec::new())?;
+    let result = apply_procedure("dynamic-wind", &args[1], Vec::new());
+    apply_procedure("dynamic-wind", &args[2], Vec::new())?;
+    result
+}
+
+fn eval_delay(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Object, LispError> {
+    if list.len() != 2 {
+        return Err(arity_error("delay", list.len() - 1));
+    }
+
+    Ok(Object::Promise(Rc::new(RefCell::new(Promise::Delayed(
+        list[1].clone(),
+        env.clone(),
+    )))))
+}
+
+fn eval_promise_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    check_arity(op, &args, 1)?;
+
+    match op {
+        "force" => force(&args[0]),
+        "promise?" => Ok(Object::Bool(matches!(args[0], Object::Promise(_)))),
+        _ => Err(format!("Unknown promise operation: {}", op).into()),
+    }
+}
+
+/// Evaluates a promise the first time it is forced and returns the memoized
+/// value afterwards. Forcing a non-promise returns it unchanged.
+fn force(obj: &Object) -> Result<Object, LispError> {
+    let Object::Promise(promise) = obj else {
+        return Ok(obj.clone());
+    };
+
+    let (expr, mut promise_env) = match &*promise.borrow() {
+        Promise::Forced(val) => return Ok(val.clone()),
+        Promise::Delayed(expr, promise_env) => (expr.clone(), promise_env.clone()),
+    };
+
+    let val = eval(&expr, &mut promise_env)?;
+    let mut state = promise.borrow_mut();
+    // The expression may have forced this same promise re-entrantly; the
+    // first value to be computed wins.
+    if let Promise::Forced(val) = &*state {
+        return Ok(val.clone());
+    }
+    *state = Promise::Forced(val.clone());
+    Ok(val)
+}
+
+/// `(make-class 'name parent '(fields...) 'method proc ...)` creates a class.
+/// `parent` is another class or `#f`; each method is a procedure taking the
+/// receiving instance as its first argument.
+fn eval_make_class(args: Vec<Object>) -> Result<Object, LispError> {
+    let (name, parent, fields, methods) = match args.as_slice() {
+        [
+            Object::Symbol(name),
+            parent,
+            Object::List(fields),
+            methods @ ..,
+        ] => (name, parent, fields, methods),
+        _ => return Err(invalid_args("make-class", &args)),
+    };
+
+    let parent = match parent {
+        Object::Class(parent) => Some(parent.clone()),
+        Object::Bool(false) => None,
+        _ => return Err(type_error("make-class", "class", parent)),
+    };
+    let fields = fields
+        .iter()
+        .map(|field| match field {
+            Object::Symbol(s) => Ok(s.clone()),
+            _ => Err(format!("make-class: invalid field name: {}", field)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if !methods.len().is_multiple_of(2) {
+        return Err("make-class: methods must be name/procedure pairs".into());
+    }
+    let methods = methods
+        .chunks(2)
+        .map(|pair| match pair {
+            [Object::Symbol(name), method] if method.is_procedure() => {
+                Ok((name.clone(), method.clone()))
+            }
+            _ => Err(format!("make-class: invalid method: {}", pair[0])),
+        })
+        .collect::<Result<HashMap<_, _>, _>>()?;
+
+    Ok(Object::Class(Rc::new(Class {
+        name: name.clone(),
+        parent,
+        fields,
+        methods,
+    })))
+}
+
+fn eval_object_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    match (op, args.as_slice()) {
+        ("make-instance", [Object::Class(class), inits @ ..]) => {
+            let fields = class.all_fields();
+            let mut slots: HashMap<String, Object> = fields
+                .iter()
+                .map(|field| (field.clone(), Object::Bool(false)))
+                .collect();
+            if !inits.len().is_multiple_of(2) {
+                return Err("make-instance: initializers must be name/value pairs".into());
+            }
+            for pair in inits.chunks(2) {
+                match &pair[0] {
+                    Object::Symbol(field) if slots.contains_key(field) => {
+                        slots.insert(field.clone(), pair[1].clone());
+                    }
+                    other => return Err(format!("make-instance: unknown field: {}", other).into()),
+                }
+            }
+            Ok(Object::Instance(Rc::new(Instance {
+                class: class.clone(),
+                slots: RefCell::new(slots),
+            })))
+        }
+        (
+            "send",
+            [
+                receiver @ Object::Instance(instance),
+                Object::Symbol(name),
+                rest @ ..,
+            ],
+        ) => {
+            let method = instance
+                .class
+                .method(name)
+                .ok_or_else(|| format!("{} does not understand {}", instance.class.name, name))?;
+            let mut method_args = vec![receiver.clone()];
+            method_args.extend(rest.iter().cloned());
+            apply_procedure(name, &method, method_args)
+        }
+        ("slot-ref", [Object::Instance(instance), Object::Symbol(field)]) => instance
+            .slots
+            .borrow()
+            .get(field)
+            .cloned()
+            .ok_or_else(|| format!("slot-ref: unknown field: {}", field).into()),
+        ("slot-set!", [Object::Instance(instance), Object::Symbol(field), val]) => {
+            match instance.slots.borrow_mut().get_mut(field) {
+                Some(slot) => *slot = val.clone(),
+                None => return Err(format!("slot-set!: unknown field: {}", field).into()),
+            }
+            Ok(Object::Void)
+        }
+        ("class-of", [Object::Instance(instance)]) => Ok(Object::Class(instance.class.clone())),
+        ("class?", [obj]) => Ok(Object::Bool(matches!(obj, Object::Class(_)))),
+        ("instance?", [obj]) => Ok(Object::Bool(matches!(obj, Object::Instance(_)))),
+        ("is-a?", [obj, Object::Class(class)]) => Ok(Object::Bool(
+            matches!(obj, Object::Instance(instance) if instance.class.is_subclass_of(class)),
+        )),
+        _ => Err(invalid_args(op, &args)),
+    }
+}
+
+/// `(stream-cons a b)` builds a stream cell: a two element list holding the
+/// value of `a` and a promise for the rest of the stream `b`. The empty list
+/// is the empty stream.
+fn eval_stream_cons(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Object, LispError> {
+    if list.len() != 3 {
+        return Err(arity_error("stream-cons", list.len() - 1));
+    }
+
+    let head = eval(&list[1], env)?;
+    let tail = Promise::Delayed(list[2].clone(), env.clone());
+    Ok(Object::List(vec![
+        head,
+        Object::Promise(Rc::new(RefCell::new(tail))),
+    ]))
+}
+
+/// Splits a stream cell into its head and its (unforced) tail.
+fn stream_parts<'a>(
+    op: &str,
+    obj: &'a Object,
+) -> Result<Option<(&'a Object, &'a Object)>, LispError> {
+    match obj {
+        Object::List(cell) if cell.is_empty() => Ok(None),
+        Object::List(cell) if cell.len() == 2 && matches!(cell[1], Object::Promise(_)) => {
+            Ok(Some((&cell[0], &cell[1])))
+        }
+        _ => Err(type_error(op, "stream", obj)),
+    }
+}
+
+/// Builds a stream cell whose tail lazily evaluates `(op f (stream-cdr s))`.
+fn lazy_stream(op: &str, head: Object, f: &Object, s: &Object) -> Object {
+    let tail_env = Rc::new(RefCell::new(Env::default()));
+    tail_env.borrow_mut().set("f", f.clone());
+    tail_env.borrow_mut().set("s", s.clone());
+    let tail = Object::List(vec![
+        Object::Symbol(op.to_string()),
+        Object::Symbol("f".to_string()),
+        Object::List(vec![
+            Object::Symbol("stream-cdr".to_string()),
+            Object::Symbol("s".to_string()),
+        ]),
+    ]);
+    Object::List(vec![
+        head,
+        Object::Promise(Rc::new(RefCell::new(Promise::Delayed(tail, tail_env)))),
+    ])
+}
+
+fn eval_stream_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    match op {
+        "stream-car" | "stream-cdr" | "stream-null?" => {
+            check_arity(op, &args, 1)?;
+            match (op, stream_parts(op, &args[0])?) {
+                ("stream-null?", parts) => Ok(Object::Bool(parts.is_none())),
+                ("stream-car", Some((head, _))) => Ok(head.clone()),
+                ("stream-cdr", Some((_, tail))) => force(tail),
+                _ => Err(format!("{}: empty stream", op).into()),
+            }
+        }
+        "stream-map" => {
+            check_arity(op, &args, 2)?;
+            match stream_parts(op, &args[1])? {
+                None => Ok(Object::List(Vec::new())),
+                Some((head, _)) => {
+                    let head = apply_procedure(op, &args[0], vec![head.clone()])?;
+                    Ok(lazy_stream(op, head, &args[0], &args[1]))
+                }
+            }
+        }
+        "stream-filter" => {
+            check_arity(op, &args, 2)?;
+            let mut stream = args[1].clone();
+            while let Some((head, tail)) = stream_parts(op, &stream)? {
+                if is_truthy(&apply_procedure(op, &args[0], vec![head.clone()])?) {
+                    return Ok(lazy_stream(op, head.clone(), &args[0], &stream));
+                }
+                stream = force(tail)?;
+            }
+            Ok(Object::List(Vec::new()))
+        }
+        "stream-take" => {
+            check_arity(op, &args, 2)?;
+            let Object::Integer(n) = args[1] else {
+                return Err(type_error(op, "integer", &args[1]));
+            };
+            let mut items = Vec::new();
+            let mut stream = args[0].clone();
+            while (items.len() as i64) < n {
+                let Some((head, tail)) = stream_parts(op, &stream)? else {
+                    break;
+                };
+                items.push(head.clone());
+                if (items.len() as i64) < n {
+                    stream = force(tail)?;
+                }
+            }
+            Ok(Object::List(items))
+        }
+        _ => Err(format!("Unknown stream operation: {}", op).into()),
+    }
+}
+
+/// `(error "message" irritants...)` raises a condition of kind `error`
+/// located at the `error` form itself.
+fn eval_error(args: Vec<Object>) -> Result<Object, LispError> {
+    let message = match args.first() {
+        Some(Object::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+        None => return Err(invalid_args("error", &args)),
+    };
+
+    Err(LispError::Raise(Object::Condition(Rc::new(Condition {
+        kind: "error".to_string(),
+        message,
+        irritants: args[1..].to_vec(),
+        location: None,
+    }))))
+}
+
+/// `(make-condition 'kind "message" irritants...)` builds a condition without
+/// raising it.
+fn eval_make_condition(args: Vec<Object>) -> Result<Object, LispError> {
+    match (args.first(), args.get(1)) {
+        (Some(Object::Symbol(kind)), Some(Object::String(message))) => {
+            Ok(Object::Condition(Rc::new(Condition {
+                kind: kind.clone(),
+                message: message.clone(),
+                irritants: args[2..].to_vec(),
+                location: None,
+            })))
+        }
+        _ => Err(invalid_args("make-condition", &args)),
+    }
+}
+
+fn eval_condition_op(op: &str, args: Vec<Object>) -> Result<Object, LispError> {
+    check_arity(op, &args, 1)?;
+
+    let condition = match &args[0] {
+        Object::Condition(condition) => condition,
+        _ if op.ends_with('?') => return Ok(Object::Bool(false)),
+        other => return Err(type_error(op, "condition", other)),
+    };
+
+    match op {
+        "condition?" | "error-object?" => Ok(Object::Bool(true)),
+        "condition-kind" => Ok(Object::Symbol(condition.kind.clone())),
+        "condition-message" | "error-object-message" => {
+            Ok(Object::String(condition.message.clone()))
+        }
+        "condition-irritants" | "error-object-irritants" => {
+            Ok(Object::List(condition.irritants.clone()))
+        }
+        "condition-location" => Ok(condition.location.clone().unwrap_or(Object::Bool(false))),
+        "file-error?" => Ok(Object::Bool(condition.kind == "file-error")),
+        "arity-error?" => Ok(Object::Bool(condition.kind == "arity-error")),
+        "type-error?" => Ok(Object::Bool(condition.kind == "type-error")),
+        "unbound-variable-error?" => Ok(Object::Bool(condition.kind == "unbound-variable")),
+        "syntax-error?" => Ok(Object::Bool(condition.kind == "syntax-error")),
+        "divide-by-zero-error?" => Ok(Object::Bool(condition.kind == "divide-by-zero")),
+        _ => Err(format!("Unknown condition operation: {}", op).into()),
+    }
+}
+
+/// The object a `guard` sees for `err`: the value given to `raise`, or a
+/// condition describing an interpreter error.
+fn raised_object(err: &LispError) -> Object {
+    match err.cause() {
+        // A condition raised without a location, such as one made by `error`,
+        // is located at the form that raised it.
+        LispError::Raise(Object::Condition(condition)) if condition.location.is_none() => {
+            Object::Condition(Rc::new(Condition {
+                kind: condition.kind.clone(),
+                message: condition.message.clone(),
+                irritants: condition.irritants.clone(),
+                location: err.form().cloned(),
+            }))
+        }
+        LispError::Raise(obj) => obj.clone(),
+        _ => error_condition(err),
+    }
+}
+
+/// A condition with the kind and message of `err`, located at the form that
+/// raised it.
+fn error_condition(err: &LispError) -> Object {
+    Object::Condition(Rc::new(Condition {
+        kind: err.kind().to_string(),
+        message: err.to_string(),
+        irritants: Vec::new(),
+        location: err.form().cloned(),
+    }))
+}
+
+/// `(guard (var clause...) body...)` evaluates `body`; if it fails, binds
+/// the raised object to `var` and evaluates the first matching cond-style
+/// clause. The error is re-raised when no clause matches.
+fn eval_guard(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Step, LispError> {
+    let (var, clauses) = match list.get(1) {
+        Some(Object::List(spec)) => match spec.split_first() {
+            Some((Object::Symbol(var), clauses)) => (var, clauses),
+            _ => return Err(syntax_error("guard")),
+        },
+        _ => return Err(syntax_error("guard")),
+    };
+
+    let mut body_env = env.clone();
+    let err = match list[2..]
+        .iter()
+        .try_fold(Object::Void, |_, expr| eval(expr, &mut body_env))
+    {
+        Ok(val) => return Ok(Step::Done(val)),
+        Err(e @ LispError::Escape { .. }) => return Err(e),
+        Err(e) => e,
+    };
+
+    let condition = raised_object(&err);
+    let mut handler_env = Rc::new(RefCell::new(Env::extend(env.clone())));
+    handler_env.borrow_mut().set(var, condition.clone());
+    for clause in clauses {
+        let clause = match clause {
+            Object::List(clause) if !clause.is_empty() => clause,
+            _ => return Err(syntax_error(format_args!("guard clause: {}", clause))),
+        };
+
+        if clause[0] == Object::Symbol("else".to_string()) {
+            return eval_body(&clause[1..], &mut handler_env);
+        }
+        let test = eval(&clause[0], &mut handler_env)?;
+        if is_truthy(&test) {
+            if clause.len() == 1 {
+                return Ok(Step::Done(test));
+            }
+            return eval_body(&clause[1..], &mut handler_env);
+        }
+    }
+
+    Err(err)
+}
+
+fn eval_if(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Step, LispError> {
+    if list.len() != 4 {
+        return Err(arity_error("if statement", list.len() - 1));
+    }
+
+    let cond = is_truthy(&eval(&list[1], env)?);
+    let branch = if cond { &list[2] } else { &list[3] };
+    Ok(Step::Tail(branch.clone(), env.clone()))
+}
+
+fn eval_cond(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Step, LispError> {
+    for clause in &list[1..] {
+        let clause = match clause {
+            Object::List(clause) if !clause.is_empty() => clause,
+            _ => return Err(syntax_error(format_args!("cond clause: {}", clause))),
+        };
+
+        if clause[0] == Object::Symbol("else".to_string()) {
+            return eval_body(&clause[1..], env);
+        }
+
+        let test = eval(&clause[0], env)?;
+        if !is_truthy(&test) {
+            continue;
+        }
+        if clause.len() == 1 {
+            return Ok(Step::Done(test));
+        }
+        return eval_body(&clause[1..], env);
+    }
+
+    Ok(Step::Done(Object::Void))
+}
+
+fn eval_case(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Step, LispError> {
+    if list.len() < 2 {
+        return Err(arity_error("case", list.len() - 1));
+    }
+
+    let key = eval(&list[1], env)?;
+    for clause in &list[2..] {
+        let clause = match clause {
+            Object::List(clause) if !clause.is_empty() => clause,
+            _ => return Err(syntax_error(format_args!("case clause: {}", clause))),
+        };
+
+        match &clause[0] {
+            Object::Symbol(s) if s == "else" => return eval_body(&clause[1..], env),
+            Object::List(data) if data.contains(&key) => return eval_body(&clause[1..], env),
+            Object::List(_) => continue,
+            other => return Err(syntax_error(format_args!("case data: {}", other))),
+        }
+    }
+
+    Ok(Step::Done(Object::Void))
+}
+
+/// Handles the one-armed conditionals `when` and `unless`, returning `Void`
+/// when the body is skipped.
+fn eval_when(kind: &str, list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Step, LispError> {
+    if list.len() < 2 {
+        return Err(arity_error(&list[0], list.len() - 1));
+    }
+
+    let cond = is_truthy(&eval(&list[1], env)?);
+    if cond == (kind == "when") {
+        eval_body(&list[2..], env)
+    } else {
+        Ok(Step::Done(Object::Void))
+    }
+}
+
+/// Only `#f` is false, as in Scheme; every other value counts as true,
+/// including `0`, `""` and the empty list `'()` (also spelled `nil`). Use
+/// `null?` to test for the empty list.
+fn is_truthy(obj: &Object) -> bool {
+    !matches!(obj, Object::Bool(false))
+}
+
+/// Returns the first false value, or the last value if all are true.
+fn eval_and(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Step, LispError> {
+    let Some((last, init)) = list[1..].split_last() else {
+        return Ok(Step::Done(Object::Bool(true)));
+    };
+    for expr in init {
+        let result = eval(expr, env)?;
+        if !is_truthy(&result) {
+            return Ok(Step::Done(result));
+        }
+    }
+    Ok(Step::Tail(last.clone(), env.clone()))
+}
+
+/// Returns the first true value, or `#f` if none is.
+fn eval_or(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Step, LispError> {
+    let Some((last, init)) = list[1..].split_last() else {
+        return Ok(Step::Done(Object::Bool(false)));
+    };
+    for expr in init {
+        let result = eval(expr, env)?;
+        if is_truthy(&result) {
+            return Ok(Step::Done(result));
+        }
+    }
+    Ok(Step::Tail(last.clone(), env.clone()))
+}
+
+/// `(match expr (pattern [when guard] body...)...)` evaluates the body of the
+/// first clause whose pattern matches the value of `expr` and whose guard, if
+/// any, is true. Patterns are `_`, variables, literals, quoted data, and lists
+/// of patterns with an optional `. rest` tail.
+fn eval_match(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Step, LispError> {
+    if list.len() < 2 {
+        return Err(arity_error("match", list.len() - 1));
+    }
+
+    let val = eval(&list[1], env)?;
+    for clause in &list[2..] {
+        let clause = match clause {
+            Object::List(clause) if !clause.is_empty() => clause,
+            _ => return Err(syntax_error(format_args!("match clause: {}", clause))),
+        };
+
+        let mut bindings = Vec::new();
+        if !match_pattern(&clause[0], &val, &mut bindings)? {
+            continue;
+        }
+
+        let mut new_env = Rc::new(RefCell::new(Env::extend(env.clone())));
+        for (name, val) in bindings {
+            new_env.borrow_mut().set(&name, val);
+        }
+
+        let body = match clause.get(1) {
+            Some(Object::Symbol(s)) if s == "when" => {
+                let guard = clause
+                    .get(2)
+                    .ok_or_else(|| LispError::Syntax("Missing match guard".to_string()))?;
+                if !is_truthy(&eval(guard, &mut new_env)?) {
+                    continue;
+                }
+                &clause[3..]
+            }
+            _ => &clause[1..],
+        };
+        return eval_body(body, &mut new_env);
+    }
+
+    Err(format!("No match clause matches: {}", val).into())
+}
+
+fn match_pattern(
+    pattern: &Object,
+    val: &Object,
+    bindings: &mut Vec<(String, Object)>,
+) -> Result<bool, LispError> {
+    match pattern {
+        Object::Symbol(s) if s == "_" => Ok(true),
+        Object::Symbol(s) => {
+            bindings.push((s.clone(), val.clone()));
+            Ok(true)
+        }
+        Object::List(items) if items.first() == Some(&Object::Symbol("quote".to_string())) => {
+            match items.as_slice() {
+                [_, datum] => Ok(datum == val),
+                _ => Err(syntax_error(format_args!("match pattern: {}", pattern))),
+            }
+        }
+        Object::List(_) | Object::DottedList(..) => {
+            let (fixed, rest) = match pattern {
+                Object::DottedList(fixed, rest) => (fixed, Some(&**rest)),
+                Object::List(fixed) => (fixed, None),
+                _ => unreachable!(),
+            };
+            let (vals, tail) = match val {
+                Object::List(vals) => (vals, Object::List(Vec::new())),
+                Object::DottedList(vals, tail) => (vals, (**tail).clone()),
+                _ => return Ok(false),
+            };
+
+            if vals.len() < fixed.len() || (rest.is_none() && vals.len() != fixed.len()) {
+                return Ok(false);
+            }
+            for (p, v) in fixed.iter().zip(vals) {
+                if !match_pattern(p, v, bindings)? {
+                    return Ok(false);
+                }
+            }
+            match rest {
+                Some(rest) => match_pattern(
+                    rest,
+                    &Object::dotted(vals[fixed.len()..].to_vec(), tail),
+                    bindings,
+                ),
+                None => Ok(tail == Object::List(Vec::new())),
+            }
+        }
+        _ => Ok(pattern == val),
+    }
+}
+
+/// `(do ((var init step)...) (test result...) body...)` binds each `var` to
+/// its `init`, then until `test` is true runs `body` and rebinds every `var`
+/// to its `step` (or keeps it when no step is given).
+fn eval_do(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Step, LispError> {
+    if list.len() < 3 {
+        return Err(arity_error("do", list.len() - 1));
+    }
+
+    let specs = match &list[1] {
+        Object::List(specs) => specs
+            .iter()
+            .map(|spec| match spec {
+                Object::List(spec) if spec.len() == 2 || spec.len() == 3 => match &spec[0] {
+                    Object::Symbol(name) => Ok((name.clone(), &spec[1], spec.get(2))),
+                    _ => Err(syntax_error(format_args!("do variable: {}", spec[0]))),
+                },
+                _ => Err(syntax_error(format_args!("do variable spec: {}", spec))),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        _ => return Err(syntax_error("do variable specs")),
+    };
+    let (test, result) = match &list[2] {
+        Object::List(clause) if !clause.is_empty() => (&clause[0], &clause[1..]),
+        _ => return Err(syntax_error("do termination clause")),
+    };
+
+    let mut loop_env = Rc::new(RefCell::new(Env::extend(env.clone())));
+    for (name, init, _) in &specs {
+        let val = eval(init, env)?;
+        loop_env.borrow_mut().set(name, val);
+    }
+
+    while !is_truthy(&eval(test, &mut loop_env)?) {
+        for expr in &list[3..] {
+            eval(expr, &mut loop_env)?;
+        }
+
+        let next_env = Rc::new(RefCell::new(Env::extend(env.clone())));
+        for (name, _, step) in &specs {
+            let val = match step {
+                Some(step) => eval(step, &mut loop_env)?,
+                None => eval_symbol(name, &mut loop_env)?,
+            };
+            next_env.borrow_mut().set(name, val);
+        }
+        loop_env = next_env;
+    }
+
+    eval_body(result, &mut loop_env)
+}
+
+fn eval_while(list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Object, LispError> {
+    if list.len() < 2 {
+        return Err(arity_error("while", list.len() - 1));
+    }
+
+    while is_truthy(&eval(&list[1], env)?) {
+        for expr in &list[2..] {
+            eval(expr, env)?;
+        }
+    }
+
+    Ok(Object::Void)
+}
+
+/// Handles `let`, `let*` and `letrec`. Plain `let` evaluates every init
+/// expression in the outer scope, while `let*` and `letrec` evaluate them in
+/// the new scope so they can see earlier bindings and (through lambdas) refer
+/// to themselves.
+fn eval_let(kind: &str, list: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Step, LispError> {
+    if list.len() < 3 {
+        return Err(arity_error(&list[0], list.len() - 1));
+    }
+
+    let bindings = match &list[1] {
+        Object::List(bindings) => bindings,
+        _ => return Err(syntax_error(format_args!("{} bindings", list[0]))),
+    };
+
+    let sequential = kind != "let";
+    let mut new_env = Rc::new(RefCell::new(Env::extend(env.clone())));
+    for binding in bindings {
+        let (name, expr) = parse_binding(binding)?;
+        let val = if sequential {
+            eval(expr, &mut new_env)?
+        } else {
+            eval(expr, env)?
+        };
+        new_env.borrow_mut().set(name, val);
+    }
+
+    eval_body(&list[2..], &mut new_env)
+}
+
+/// Splits a `(name expr)` binding pair as used by `let` and friends.
+fn parse_binding(binding: &Object) -> Result<(&str, &Object), LispError> {
+    match binding {
+        Object::List(pair) if pair.len() == 2 => match &pair[0] {
+            Object::Symbol(name) => Ok((name, &pair[1])),
+            _ => Err(syntax_error(format_args!("binding name: {}", pair[0]))),
+        },
+        _ => Err(syntax_error(format_args!("binding: {}", binding))),
+    }
+}
+
+/// Evaluates a sequence of expressions, leaving the last one to the caller
+/// as a tail call.
+fn eval_body(body: &[Object], env: &mut Rc<RefCell<Env>>) -> Result<Step, LispError> {
+    let Some((last, init)) = body.split_last() else {
+        return Ok(Step::Done(Object::Void));
+    };
+    for expr in init {
+        eval(expr, env)?;
+    }
+    Ok(Step::Tail(last.clone(), env.clone()))
+}
+
+fn eval_function_definition(list: &[Object], env: &Rc<RefCell<Env>>) -> Result<Object, LispError> {
+    if list.len() < 3 {
+        return Err(arity_error("lambda", list.len() - 1));
+    }
+
+    let (params, rest, keys) = parse_params(&list[1])?;
+    let body = list[2..].to_vec();
+
+    Ok(Object::Lambda(Rc::new(Lambda {
+        params,
+        rest,
+        keys,
+        body,
+        env: env.clone(),
+    })))
+}
+
+/// `(case-lambda (formals body...) ...)` builds a procedure that runs the
+/// first clause whose parameter list accepts the number of arguments.
+fn eval_case_lambda(list: &[Object], env: &Rc<RefCell<Env>>) -> Result<Object, LispError> {
+    let clauses = list[1..]
+        .iter()
+        .map(|clause| match clause {
+            Object::List(clause) if clause.len() >= 2 => {
+                let mut lambda = vec![Object::Symbol("lambda".to_string())];
+                lambda.extend_from_slice(clause);
+                match eval_function_definition(&lambda, env)? {
+                    Object::Lambda(lambda) => Ok(lambda),
+                    _ => unreachable!("lambda always evaluates to a Lambda"),
+                }
+            }
+            _ => Err(syntax_error(format_args!("case-lambda clause: {}", clause))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Object::CaseLambda(clauses))
+}
+
+/// Required parameters, rest parameter and keyword parameters of a lambda.
+type Params = (Vec<String>, Option<String>, Vec<(String, Object)>);
+
+/// Parses a lambda parameter list into its required parameters, an optional
+/// rest parameter and keyword parameters. Accepts `(a b)`, `(a b . rest)`, a
+/// bare `args` symbol that collects every argument, and
+/// `(a #!key (width 80) height)` where keywords default to `#f`.
+fn parse_params(obj: &Object) -> Result<Params, LispError> {
+    let (list, rest) = match obj {
+        Object::Symbol(rest) => return Ok((Vec::new(), Some(rest.clone()), Vec::new())),
+        Object::List(list) => (list, None),
+        Object::DottedList(list, rest) => match &**rest {
+            Object::Symbol(rest) => (list, Some(rest.clone())),
+            _ => return Err(syntax_error("rest parameter")),
+        },
+        _ => return Err(syntax_error("lambda")),
+    };
+
+    let mut params = Vec::new();
+    let mut keys = Vec::new();
+    let mut in_keys = false;
+    for param in list {
+        match param {
+            Object::Symbol(s) if s == "#!key" => in_keys = true,
+            Object::Symbol(s) if in_keys => keys.push((s.clone(), Object::Bool(false))),
+            Object::List(spec) if in_keys => match spec.as_slice() {
+                [Object::Symbol(key), default] => keys.push((key.clone(), default.clone())),
+                _ => return Err(syntax_error(format_args!("keyword parameter: {}", param))),
+            },
+            Object::Symbol(s) => params.push(s.clone()),
+            _ => return Err(syntax_error("lambda parameter")),
+        }
+    }
+
+    Ok((params, rest, keys))
+}
+
+/// Applies `f`, the value of the symbol `s` heading `list`, be it a macro or
+/// a procedure.
+fn eval_function_call(
+    s: &str,
+    f: Object,
+    list: &[Object],
+    env: &mut Rc<RefCell<Env>>,
+) -> Result<Step, LispError> {
+    match f {
+        Object::Macro(lambda) => {
+            let expansion = apply_lambda(s, &lambda, list[1..].to_vec())?;
+            Ok(Step::Tail(expansion, env.clone()))
+        }
+        Object::Syntax(rules) => Ok(Step::Tail(rules.expand(list)?, env.clone())),
+        f if f.is_procedure() => {
+            let args = eval_args(list, env)?;
+            apply_procedure_step(s, &f, args)
+        }
+        other => Err(type_error(s, "procedure", other)),
+    }
+}
+
+/// Calls any procedure value with already evaluated arguments.
+pub(crate) fn apply_procedure(
+    name: &str,
+    f: &Object,
+    args: Vec<Object>,
+) -> Result<Object, LispError> {
+    let depth = call_depth();
+    let result = match apply_procedure_step(name, f, args) {
+        Ok(Step::Done(val)) => Ok(val),
+        Ok(Step::Tail(expr, mut env)) => eval(&expr, &mut env),
+        Err(e) => Err(e),
+    };
+    CALL_STACK.with(|stack| stack.borrow_mut().truncate(depth));
+    result
+}
+
+fn apply_procedure_step(name: &str, f: &Object, args: Vec<Object>) -> Result<Step, LispError> {
+    if !matches!(f, Object::Continuation(_)) {
+        CALL_STACK.with(|stack| stack.borrow_mut().push(name.to_string()));
+    }
+    match f {
+        Object::Lambda(lambda) => apply_lambda_step(name, lambda, args),
+        Object::CaseLambda(clauses) => {
+            let lambda = clauses
+                .iter()
+                .find(|lambda| accepts_arity(lambda, args.len()))
+                .ok_or_else(|| arity_error(name, args.len()))?;
+            apply_lambda_step(name, lambda, args)
+        }
+        Object::Continuation(id) => {
+            let val = match args.len() {
+                1 => args.into_iter().next().unwrap(),
+                _ => Object::Values(args),
+            };
+            Err(LispError::Escape {
+                continuation: *id,
+                value: val,
+            })
+        }
+        Object::NativeFn(native) => (native.func)(&args).map(Step::Done),
+        _ => Err(type_error(name, "procedure", f)),
+    }
+}
+
+fn accepts_arity(lambda: &Lambda, n: usize) -> bool {
+    let variadic = lambda.rest.is_some() || !lambda.keys.is_empty();
+    n == lambda.params.len() || (variadic && n > lambda.params.len())
+}
+
+/// Calls `lambda` with already evaluated arguments and runs it to completion.
+fn apply_lambda(name: &str, lambda: &Lambda, args: Vec<Object>) -> Result<Object, LispError> {
+    match apply_lambda_step(name, lambda, args)? {
+        Step::Done(val) => Ok(val),
+        Step::Tail(expr, mut env) => eval(&expr, &mut env),
+    }
+}
+
+/// Binds already evaluated arguments in a new scope extending the environment
+/// the lambda closed over, returning the body as a tail call.
+fn apply_lambda_step(name: &str, lambda: &Lambda, args: Vec<Object>) -> Result<Step, LispError> {
+    let params = &lambda.params;
+    let rest = &lambda.rest;
+    let variadic = rest.is_some() || !lambda.keys.is_empty();
+    if args.len() < params.len() || (!variadic && args.len() > params.len()) {
+        return Err(LispError::ArityMismatch {
+            name: name.to_string(),
+            expected: Some(format!(
+                "{}{}",
+                if variadic { "at least " } else { "" },
+                params.len()
+            )),
+            got: args.len(),
+        });
+    }
+
+    let mut new_env = Rc::new(RefCell::new(Env::extend(lambda.env.clone())));
+    let mut args = args.into_iter();
+    for param in params.iter() {
+        new_env.borrow_mut().set(param, args.next().unwrap());
+    }
+    let remaining: Vec<Object> = args.collect();
+    if !lambda.keys.is_empty() {
+        bind_keyword_args(name, lambda, &remaining, &mut new_env)?;
+    }
+    if let Some(rest) = rest {
+        new_env.borrow_mut().set(rest, Object::List(remaining));
+    }
+    eval_body(&lambda.body, &mut new_env)
+}
+
+/// Binds `:name value` pairs to the lambda's keyword parameters, evaluating
+/// the default of every keyword that was not passed in the new scope.
+fn bind_keyword_args(
+    name: &str,
+    lambda: &Lambda,
+    args: &[Object],
+    env: &mut Rc<RefCell<Env>>,
+) -> Result<(), LispError> {
+    if !args.len().is_multiple_of(2) {
+        return Err(format!("{}: keyword arguments must come in pairs", name).into());
+    }
+
+    let mut supplied = HashMap::new();
+    for pair in args.chunks(2) {
+        match &pair[0] {
+            Object::Keyword(key) if lambda.keys.iter().any(|(k, _)| k == key) => {
+                supplied.insert(key.clone(), pair[1].clone());
+            }
+            // Unknown keywords are left for the rest parameter.
+            Object::Keyword(_) if lambda.rest.is_some() => {}
+            other => return Err(format!("{}: unexpected keyword argument: {}", name, other).into()),
+        }
+    }
+
+    for (key, default) in &lambda.keys {
+        let val = match supplied.remove(key) {
+            Some(val) => val,
+            None => eval(default, env)?,
+        };
+        env.borrow_mut().set(key, val);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_str(src: &str, env: &mut Rc<RefCell<Env>>) -> Result<Object, String> {
+        let mut tokens = tokenize(src).map_err(|e| e.to_string())?;
+        let ast = parse(&mut tokens).map_err(|e| e.to_string())?;
+        eval(&ast, env).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_eval() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        let input = Object::List(vec![
+            Object::Symbol("+".to_string()),
+            Object::Integer(1),
+            Object::Integer(2),
+        ]);
+        let expeted = Ok(Object::Integer(3));
+
+        assert_eq!(expeted, eval(&input, &mut env));
+    }
+
+    #[test]
+    fn test_eval_float_promotion() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        let input = Object::List(vec![
+            Object::Symbol("+".to_string()),
+            Object::Integer(1),
+            Object::Float(2.5),
+        ]);
+
+        assert_eq!(Ok(Object::Float(3.5)), eval(&input, &mut env));
+    }
+
+    #[test]
+    fn test_eval_bigint_promotion() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        let input = Object::List(vec![
+            Object::Symbol("*".to_string()),
+            Object::Integer(99999999999),
+            Object::Integer(99999999999),
+        ]);
+
+        assert_eq!(
+            Ok(Object::BigInt(
+                BigInt::parse("9999999999800000000001").unwrap()
+            )),
+            eval(&input, &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_rational_division() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        let input = Object::List(vec![
+            Object::Symbol("/".to_string()),
+            Object::Integer(1),
+            Object::Integer(3),
+        ]);
+
+        assert_eq!(
+            Ok(Object::Rational(Rational::parse("1/3").unwrap())),
+            eval(&input, &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_let() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define x 10)", &mut env).unwrap();
+
+        assert_eq!(
+            Ok(Object::Integer(13)),
+            eval_str("(let ((x 1) (y (+ x 2))) (+ x y))", &mut env)
+        );
+        assert_eq!(Ok(Object::Integer(10)), eval_str("(+ x 0)", &mut env));
+        assert!(eval_str("(+ y 0)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_let_star_and_letrec() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        assert_eq!(
+            Ok(Object::Integer(3)),
+            eval_str("(let* ((x 1) (y (+ x 1))) (+ x y))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(120)),
+            eval_str(
+                "(letrec ((fact (lambda (n) (if (= n 0) 1 (* n (fact (- n 1))))))) (fact 5))",
+                &mut env
+            )
+        );
+    }
+
+    #[test]
+    fn test_eval_cond() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define x 5)", &mut env).unwrap();
+
+        assert_eq!(
+            Ok(Object::Integer(2)),
+            eval_str("(cond ((< x 0) 1) ((< x 10) 2) (else 3))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(3)),
+            eval_str("(cond ((< x 0) 1) (else 3))", &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_case() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        assert_eq!(
+            Ok(Object::String("small".to_string())),
+            eval_str(
+                "(case (+ 1 1) ((1 2 3) \"small\") ((4 5 6) \"medium\") (else \"big\"))",
+                &mut env
+            )
+        );
+        assert_eq!(
+            Ok(Object::String("big".to_string())),
+            eval_str("(case 9 ((1 2 3) \"small\") (else \"big\"))", &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_when_unless() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        assert_eq!(
+            Ok(Object::Integer(2)),
+            eval_str("(when (< 1 2) 1 2)", &mut env)
+        );
+        assert_eq!(Ok(Object::Void), eval_str("(when (> 1 2) 1)", &mut env));
+        assert_eq!(
+            Ok(Object::Integer(1)),
+            eval_str("(unless (> 1 2) 1)", &mut env)
+        );
+        assert_eq!(Ok(Object::Void), eval_str("(unless (< 1 2) 1)", &mut env));
+    }
+
+    #[test]
+    fn test_eval_begin() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        assert_eq!(
+            Ok(Object::Integer(3)),
+            eval_str("(begin (define x 1) (define y 2) (+ x y))", &mut env)
+        );
+        assert_eq!(Ok(Object::Void), eval_str("(begin)", &mut env));
+    }
+
+    #[test]
+    fn test_eval_and_or() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        assert_eq!(
+            Ok(Object::Bool(false)),
+            eval_str("(and #f (undefined-function))", &mut env)
+        );
+        assert_eq!(Ok(Object::Integer(3)), eval_str("(and 1 2 3)", &mut env));
+        assert_eq!(
+            Ok(Object::Integer(1)),
+            eval_str("(or #f 1 (crash))", &mut env)
+        );
+        assert_eq!(Ok(Object::Bool(false)), eval_str("(or)", &mut env));
+    }
+
+    #[test]
+    fn test_eval_not() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        assert_eq!(Ok(Object::Bool(true)), eval_str("(not (< 2 1))", &mut env));
+        assert_eq!(Ok(Object::Bool(false)), eval_str("(not 0)", &mut env));
+        assert_eq!(Ok(Object::Bool(true)), eval_str("(boolean? #f)", &mut env));
+        assert_eq!(Ok(Object::Bool(false)), eval_str("(boolean? 1)", &mut env));
+    }
+
+    #[test]
+    fn test_eval_set() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define counter 0)", &mut env).unwrap();
+        eval_str("(let ((step 2)) (set! counter (+ counter step)))", &mut env).unwrap();
+
+        assert_eq!(Ok(Object::Integer(2)), eval_str("counter", &mut env));
+        assert!(eval_str("(set! missing 1)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_define_function_shorthand() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define (square x) (* x x))", &mut env).unwrap();
+
+        assert_eq!(Ok(Object::Integer(49)), eval_str("(square 7)", &mut env));
+    }
+
+    #[test]
+    fn test_eval_rest_parameters() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define list-of (lambda args (begin args)))", &mut env).unwrap();
+        eval_str(
+            "(define rest-of (lambda (a . rest) (begin rest)))",
+            &mut env,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Ok(Object::List(vec![Object::Integer(1), Object::Integer(2)])),
+            eval_str("(list-of 1 2)", &mut env)
+        );
+        assert_eq!(Ok(Object::List(vec![])), eval_str("(rest-of 1)", &mut env));
+        assert!(eval_str("(rest-of)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_multi_expression_body() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define total 0)", &mut env).unwrap();
+        eval_str("(define (add! n) (set! total (+ total n)) total)", &mut env).unwrap();
+
+        assert_eq!(Ok(Object::Integer(5)), eval_str("(add! 5)", &mut env));
+        assert_eq!(Ok(Object::Integer(8)), eval_str("(add! 3)", &mut env));
+    }
+
+    #[test]
+    fn test_eval_closures() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define (make-adder n) (lambda (x) (+ x n)))", &mut env).unwrap();
+        eval_str("(define add5 (make-adder 5))", &mut env).unwrap();
+        eval_str("(define n 100)", &mut env).unwrap();
+
+        assert_eq!(Ok(Object::Integer(7)), eval_str("(add5 2)", &mut env));
+        assert_eq!(
+            Ok(Object::Integer(3)),
+            eval_str("((make-adder 1) 2)", &mut env)
+        );
+
+        // A head that is not a procedure is still evaluated only once.
+        eval_str("(define (bump) (set! n (+ n 1)) n)", &mut env).unwrap();
+        assert_eq!(
+            Ok(Object::List(vec![
+                Object::Integer(101),
+                Object::Integer(10)
+            ])),
+            eval_str("((bump) 10)", &mut env)
+        );
+        assert_eq!(Ok(Object::Integer(101)), eval_str("n", &mut env));
+    }
+
+    #[test]
+    fn test_eval_tail_calls() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str(
+            "(define loop (lambda (n) (if (= n 0) 0 (loop (- n 1)))))",
+            &mut env,
+        )
+        .unwrap();
+
+        assert_eq!(Ok(Object::Integer(0)), eval_str("(loop 100000)", &mut env));
+    }
+
+    #[test]
+    fn test_eval_define_macro() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define-macro (quote-it x) ((quote quote) x))", &mut env).unwrap();
+        eval_str(
+            "(define-macro (swap! a b) \
+             ((quote let) (((quote tmp) a)) ((quote set!) a b) ((quote set!) b (quote tmp))))",
+            &mut env,
+        )
+        .unwrap();
+        eval_str("(define x 1)", &mut env).unwrap();
+        eval_str("(define y 2)", &mut env).unwrap();
+        eval_str("(swap! x y)", &mut env).unwrap();
+
+        assert_eq!(
+            Ok(Object::List(vec![Object::Symbol("undefined".to_string())])),
+            eval_str("(quote-it (undefined))", &mut env)
+        );
+        assert_eq!(Ok(Object::Integer(2)), eval_str("x", &mut env));
+        assert_eq!(Ok(Object::Integer(1)), eval_str("y", &mut env));
+    }
+
+    #[test]
+    fn test_eval_syntax_rules() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str(
+            "(define-syntax my-or (syntax-rules () \
+               ((_) #f) \
+               ((_ e) e) \
+               ((_ e r ...) (let ((t e)) (if t t (my-or r ...))))))",
+            &mut env,
+        )
+        .unwrap();
+        eval_str(
+            "(define-syntax my-let (syntax-rules () \
+               ((_ ((name val) ...) body ...) ((lambda (name ...) body ...) val ...))))",
+            &mut env,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Ok(Object::Integer(3)),
+            eval_str("(my-or #f #f 3)", &mut env)
+        );
+        // The template's `t` must not capture the caller's `t`.
+        assert_eq!(
+            Ok(Object::Integer(5)),
+            eval_str("(let ((t 5)) (my-or #f t))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(3)),
+            eval_str("(my-let ((a 1) (b 2)) (+ a b))", &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_gensym() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        let a = eval_str("(gensym)", &mut env).unwrap();
+        let b = eval_str("(gensym \"tmp\")", &mut env).unwrap();
+
+        assert!(matches!(&a, Object::Symbol(s) if s.starts_with("g#")));
+        assert!(matches!(&b, Object::Symbol(s) if s.starts_with("tmp#")));
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_eval_eval() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define x 1)", &mut env).unwrap();
+
+        assert_eq!(
+            Ok(Object::Integer(3)),
+            eval_str("(eval '(+ x 2))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(1)),
+            eval_str(
+                "(let ((x 10)) (eval 'x (interaction-environment)))",
+                &mut env
+            )
+        );
+        assert_eq!(
+            Ok(Object::Integer(10)),
+            eval_str("(let ((x 10)) (eval 'x (the-environment)))", &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_match() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str(
+            "(define (describe x) \
+               (match x \
+                 (0 \"zero\") \
+                 (('point a (b c)) (+ a (+ b c))) \
+                 ((first . rest) when (> first 10) rest) \
+                 ((_ . rest) \"list\") \
+                 (_ \"other\")))",
+            &mut env,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Ok(Object::String("zero".to_string())),
+            eval_str("(describe 0)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(6)),
+            eval_str("(describe '(point 1 (2 3)))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::List(vec![Object::Integer(2)])),
+            eval_str("(describe '(11 2))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::String("list".to_string())),
+            eval_str("(describe '(1 2))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::String("other".to_string())),
+            eval_str("(describe 5)", &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_do() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        assert_eq!(
+            Ok(Object::Integer(10)),
+            eval_str(
+                "(do ((i 0 (+ i 1)) (sum 0 (+ sum i))) ((= i 5) sum))",
+                &mut env
+            )
+        );
+    }
+
+    #[test]
+    fn test_eval_while() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define i 0)", &mut env).unwrap();
+        eval_str("(define sum 0)", &mut env).unwrap();
+
+        assert_eq!(
+            Ok(Object::Void),
+            eval_str(
+                "(while (< i 5) (set! sum (+ sum i)) (set! i (+ i 1)))",
+                &mut env
+            )
+        );
+        assert_eq!(Ok(Object::Integer(10)), eval_str("sum", &mut env));
+    }
+
+    #[test]
+    fn test_eval_values() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        assert_eq!(
+            Ok(Object::Integer(5)),
+            eval_str(
+                "(call-with-values (lambda () (values 2 3)) (lambda (a b) (+ a b)))",
+                &mut env
+            )
+        );
+        assert_eq!(
+            Ok(Object::Integer(4)),
+            eval_str("(call-with-values (lambda () 4) (lambda (a) a))", &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_call_cc() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str(
+            "(define (find-first pred items) \
+               (call/cc (lambda (return) \
+                 (match items \
+                   ((a b c) (when (pred a) (return a)) \
+                            (when (pred b) (return b)) \
+                            (when (pred c) (return c)) \
+                            #f)))))",
+            &mut env,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Ok(Object::Integer(5)),
+            eval_str("(find-first (lambda (x) (> x 3)) '(1 5 7))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(3)),
+            eval_str("(+ 1 (call/cc (lambda (k) (+ 10 (k 2)))))", &mut env)
+        );
+        eval_str("(define saved #f)", &mut env).unwrap();
+        eval_str("(call/cc (lambda (k) (set! saved k)))", &mut env).unwrap();
+        assert!(eval_str("(saved 1)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_dynamic_wind() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define depth 0)", &mut env).unwrap();
+        eval_str(
+            "(define (wind thunk) \
+               (dynamic-wind (lambda () (set! depth (+ depth 1))) \
+                             thunk \
+                             (lambda () (set! depth (- depth 1)))))",
+            &mut env,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Ok(Object::Integer(1)),
+            eval_str("(wind (lambda () depth))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(7)),
+            eval_str("(call/cc (lambda (k) (wind (lambda () (k 7)))))", &mut env)
+        );
+        assert!(eval_str("(wind (lambda () (undefined)))", &mut env).is_err());
+        assert_eq!(Ok(Object::Integer(0)), eval_str("depth", &mut env));
+    }
+
+    #[test]
+    fn test_eval_delay_force() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define count 0)", &mut env).unwrap();
+        eval_str(
+            "(define p (delay (begin (set! count (+ count 1)) count)))",
+            &mut env,
+        )
+        .unwrap();
+
+        assert_eq!(Ok(Object::Integer(0)), eval_str("count", &mut env));
+        assert_eq!(Ok(Object::Integer(1)), eval_str("(force p)", &mut env));
+        assert_eq!(Ok(Object::Integer(1)), eval_str("(force p)", &mut env));
+        assert_eq!(Ok(Object::Integer(1)), eval_str("count", &mut env));
+        assert_eq!(Ok(Object::Integer(5)), eval_str("(force 5)", &mut env));
+    }
+
+    #[test]
+    fn test_eval_streams() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define (from n) (stream-cons n (from (+ n 1))))", &mut env).unwrap();
+        eval_str("(define naturals (from 0))", &mut env).unwrap();
+
+        assert_eq!(
+            Ok(Object::List(vec![
+                Object::Integer(4),
+                Object::Integer(6),
+                Object::Integer(8),
+            ])),
+            eval_str(
+                "(stream-take (stream-filter (lambda (n) (> n 2)) \
+                                            (stream-map (lambda (n) (* n 2)) naturals)) 3)",
+                &mut env
+            )
+        );
+        assert_eq!(
+            Ok(Object::Integer(1)),
+            eval_str("(stream-car (stream-cdr naturals))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Bool(true)),
+            eval_str("(stream-null? '())", &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_guard() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        assert_eq!(
+            Ok(Object::String("bad value".to_string())),
+            eval_str(
+                "(guard (e (#t (condition-message e))) (error \"bad value\" 42))",
+                &mut env
+            )
+        );
+        assert_eq!(
+            Ok(Object::Integer(1)),
+            eval_str("(guard (e ((boolean? e) 0) (else e)) (raise 1))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::String("Unbound symbol: nope".to_string())),
+            eval_str(
+                "(guard (e (else (condition-message e))) (+ nope 1))",
+                &mut env
+            )
+        );
+        assert!(eval_str("(guard (e (#f 0)) (raise 1))", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_conditions() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        assert_eq!(
+            Ok(Object::List(vec![Object::Integer(42)])),
+            eval_str(
+                "(guard (e ((condition? e) (condition-irritants e))) (error \"bad\" 42))",
+                &mut env
+            )
+        );
+        assert_eq!(
+            Ok(Object::Symbol("unbound-variable".to_string())),
+            eval_str("(guard (e (#t (condition-kind e))) nope)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Bool(true)),
+            eval_str(
+                "(guard (e ((arity-error? e) #t)) ((lambda (x) x) 1 2))",
+                &mut env
+            )
+        );
+        assert_eq!(
+            Ok(Object::Bool(true)),
+            eval_str(
+                "(guard (e ((divide-by-zero-error? e) #t)) (/ 1 0))",
+                &mut env
+            )
+        );
+        assert_eq!(
+            Err("bad 42".to_string()),
+            eval_str("(error \"bad\" 42)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::List(vec![
+                Object::Symbol("vector-ref".to_string()),
+                Object::Integer(1),
+                Object::Integer(2),
+            ])),
+            eval_str(
+                "(guard (e (#t (condition-location e))) (+ 1 (vector-ref 1 2)))",
+                &mut env
+            )
+        );
+        assert_eq!(
+            Ok(Object::List(vec![
+                Object::Symbol("error".to_string()),
+                Object::String("bad".to_string()),
+            ])),
+            eval_str(
+                "(guard (e (#t (condition-location e))) (list 1 (error \"bad\")))",
+                &mut env
+            )
+        );
+        for (src, kind) in [
+            ("(vector-ref 1 2)", "type-error"),
+            ("(string-upcase 5)", "type-error"),
+            ("(vector-ref #(1))", "arity-error"),
+            ("(let ((x)) x)", "syntax-error"),
+        ] {
+            assert_eq!(
+                Ok(Object::Symbol(kind.to_string())),
+                eval_str(
+                    &format!("(guard (e (#t (condition-kind e))) {})", src),
+                    &mut env
+                ),
+                "{}",
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn test_eval_modules() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str(
+            "(define-module (math utils)
+               (export square)
+               (define (helper x) (* x x))
+               (define (square x) (helper x)))",
+            &mut env,
+        )
+        .unwrap();
+
+        assert!(eval_str("(square 3)", &mut env).is_err());
+        eval_str("(import (math utils))", &mut env).unwrap();
+        assert_eq!(Ok(Object::Integer(9)), eval_str("(square 3)", &mut env));
+        assert!(eval_str("(helper 3)", &mut env).is_err());
+        assert!(eval_str("(import (missing))", &mut env).is_err());
+        assert!(eval_str("(export square)", &mut env).is_err());
+        assert!(eval_str("(define-module (inline))", &mut env).is_err());
+
+        let path = std::env::temp_dir().join(format!("lisp-rs-module-{}.lisp", std::process::id()));
+        fs::write(
+            &path,
+            "(define-module (geometry))\n(export area)\n(define pi 3)\n(define (area r) (* pi r r))\n",
+        )
+        .unwrap();
+        let result = eval_str(&format!("(load {:?})", path.to_str().unwrap()), &mut env);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(Ok(Object::Void), result);
+        assert!(eval_str("(area 2)", &mut env).is_err());
+        assert!(eval_str("pi", &mut env).is_err());
+        eval_str("(import (geometry))", &mut env).unwrap();
+        assert_eq!(Ok(Object::Integer(12)), eval_str("(area 2)", &mut env));
+        assert!(eval_str("(export area)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_load() {
+        let path = std::env::temp_dir().join(format!("lisp-rs-load-{}.lisp", std::process::id()));
+        fs::write(
+            &path,
+            "; library\n(define (double x) (* x 2))\n(define answer (double 21))\n",
+        )
+        .unwrap();
+
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        let result = eval_str(&format!("(load {:?})", path.to_str().unwrap()), &mut env);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(Ok(Object::Void), result);
+        assert_eq!(Ok(Object::Integer(42)), eval_str("answer", &mut env));
+        assert_eq!(
+            Ok(Object::Bool(true)),
+            eval_str(
+                "(guard (e ((file-error? e) #t)) (load \"/nonexistent/file.lisp\"))",
+                &mut env
+            )
+        );
+    }
+
+    #[test]
+    fn test_eval_object_system() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str(
+            "(define Point (make-class 'Point #f '(x y)
+               'norm2 (lambda (self)
+                        (+ (* (slot-ref self 'x) (slot-ref self 'x))
+                           (* (slot-ref self 'y) (slot-ref self 'y))))
+               'move! (lambda (self dx)
+                        (slot-set! self 'x (+ (slot-ref self 'x) dx)))))",
+            &mut env,
+        )
+        .unwrap();
+        eval_str(
+            "(define Point3 (make-class 'Point3 Point '(z)
+               'norm2 (lambda (self) (+ (slot-ref self 'z) 100))))",
+            &mut env,
+        )
+        .unwrap();
+        eval_str("(define p (make-instance Point 'x 3 'y 4))", &mut env).unwrap();
+        eval_str("(define q (make-instance Point3 'x 1 'z 5))", &mut env).unwrap();
+
+        assert_eq!(
+            Ok(Object::Integer(25)),
+            eval_str("(send p 'norm2)", &mut env)
+        );
+        eval_str("(send p 'move! 1)", &mut env).unwrap();
+        assert_eq!(
+            Ok(Object::Integer(4)),
+            eval_str("(slot-ref p 'x)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(105)),
+            eval_str("(send q 'norm2)", &mut env)
+        );
+        eval_str("(send q 'move! 2)", &mut env).unwrap();
+        assert_eq!(
+            Ok(Object::Integer(3)),
+            eval_str("(slot-ref q 'x)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Bool(true)),
+            eval_str("(is-a? q Point)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Bool(false)),
+            eval_str("(is-a? p Point3)", &mut env)
+        );
+        assert!(eval_str("(send p 'fly)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_keyword_parameters() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str(
+            "(define (make-window title #!key (width 80) (height (/ width 2)) border)
+               (values title width height border))",
+            &mut env,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Ok(Object::Values(vec![
+                Object::String("w".to_string()),
+                Object::Integer(100),
+                Object::Integer(24),
+                Object::Bool(false),
+            ])),
+            eval_str("(make-window \"w\" :height 24 :width 100)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Values(vec![
+                Object::String("w".to_string()),
+                Object::Integer(80),
+                Object::Integer(40),
+                Object::Bool(true),
+            ])),
+            eval_str("(make-window \"w\" :border #t)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Keyword("width".to_string())),
+            eval_str(":width", &mut env)
+        );
+        assert!(eval_str("(make-window \"w\" :depth 3)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_case_lambda() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str(
+            "(define range
+               (case-lambda
+                 ((end) (range 0 end 1))
+                 ((start end) (range start end 1))
+                 ((start end step) (values start end step))))",
+            &mut env,
+        )
+        .unwrap();
+        eval_str(
+            "(define count-args (case-lambda (() 0) ((x . rest) (+ 1 (count-args)))))",
+            &mut env,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Ok(Object::Values(vec![
+                Object::Integer(0),
+                Object::Integer(5),
+                Object::Integer(1),
+            ])),
+            eval_str("(range 5)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Values(vec![
+                Object::Integer(2),
+                Object::Integer(5),
+                Object::Integer(1),
+            ])),
+            eval_str("(range 2 5)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(1)),
+            eval_str("(count-args 7 8 9)", &mut env)
+        );
+        assert!(eval_str("(range 1 2 3 4)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_dotted_lists() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        assert_eq!(
+            Ok(Object::DottedList(
+                vec![Object::Integer(1)],
+                Box::new(Object::Integer(2))
+            )),
+            eval_str("'(1 . 2)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(3)),
+            eval_str("(match '(1 2 . 3) ((a b . c) c) (_ 0))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Bool(false)),
+            eval_str("(match '(1 . 2) ((a b) #t) (_ #f))", &mut env)
+        );
+        assert!(eval_str("(1 . 2)", &mut env).is_err());
+        assert_eq!(
+            Ok("(1 2 . 3)".to_string()),
+            eval_str("(cons 1 (cons 2 3))", &mut env).map(|obj| obj.to_string())
+        );
+        assert_eq!(
+            Ok(Object::List(vec![Object::Integer(1), Object::Integer(2)])),
+            eval_str("(cons 1 '(2))", &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_list_primitives() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define xs (list 1 2 3))", &mut env).unwrap();
+
+        assert_eq!(Ok(Object::Integer(1)), eval_str("(car xs)", &mut env));
+        assert_eq!(
+            Ok(Object::List(vec![Object::Integer(2), Object::Integer(3)])),
+            eval_str("(cdr xs)", &mut env)
+        );
+        assert_eq!(Ok(Object::Integer(3)), eval_str("(cdr '(2 . 3))", &mut env));
+        assert_eq!(
+            Ok(Object::Bool(true)),
+            eval_str("(null? (cdr '(1)))", &mut env)
+        );
+        assert_eq!(Ok(Object::Bool(false)), eval_str("(null? xs)", &mut env));
+        assert_eq!(
+            Ok(Object::Bool(true)),
+            eval_str("(pair? (cons 1 2))", &mut env)
+        );
+        assert_eq!(Ok(Object::Bool(false)), eval_str("(pair? '())", &mut env));
+        assert!(eval_str("(car '())", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_empty_list_truthiness() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        let empty = Object::List(Vec::new());
+
+        assert_eq!(Ok(empty.clone()), eval_str("()", &mut env));
+        assert_eq!(Ok(empty.clone()), eval_str("nil", &mut env));
+        assert_eq!(Ok(Object::Bool(true)), eval_str("(null? nil)", &mut env));
+        assert_eq!(Ok(Object::Bool(true)), eval_str("(null? (list))", &mut env));
+        // Only #f is false: the empty list and 0 are true.
+        assert_eq!(
+            Ok(Object::Integer(1)),
+            eval_str("(if (list) 1 2)", &mut env)
+        );
+        assert_eq!(Ok(Object::Integer(1)), eval_str("(if 0 1 2)", &mut env));
+        assert_eq!(Ok(Object::Integer(2)), eval_str("(if #f 1 2)", &mut env));
+        assert_eq!(
+            Ok(Object::Integer(3)),
+            eval_str("(cond (#f 1) (3) (else 4))", &mut env)
+        );
+        assert_eq!(Ok(Object::Void), eval_str("(when #f 1)", &mut env));
+    }
+
+    #[test]
+    fn test_eval_list_library() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        let ints = |ns: &[i64]| Object::List(ns.iter().map(|&n| Object::Integer(n)).collect());
+
+        assert_eq!(
+            Ok(Object::Integer(3)),
+            eval_str("(length '(1 2 3))", &mut env)
+        );
+        assert_eq!(
+            Ok(ints(&[1, 2, 3, 4])),
+            eval_str("(append '(1) '(2 3) '() '(4))", &mut env)
+        );
+        assert_eq!(
+            Ok("(1 . 2)".to_string()),
+            eval_str("(append '(1) 2)", &mut env).map(|obj| obj.to_string())
+        );
+        assert_eq!(
+            Ok(ints(&[3, 2, 1])),
+            eval_str("(reverse '(1 2 3))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(2)),
+            eval_str("(list-ref '(1 2 3) 1)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(3)),
+            eval_str("(last '(1 2 3))", &mut env)
+        );
+        assert_eq!(Ok(ints(&[1, 2])), eval_str("(take '(1 2 3) 2)", &mut env));
+        assert_eq!(Ok(ints(&[3])), eval_str("(drop '(1 2 3) 2)", &mut env));
+        assert_eq!(
+            Ok(ints(&[1, 2, 3, 4])),
+            eval_str("(flatten '(1 (2 (3)) () 4))", &mut env)
+        );
+        assert!(eval_str("(list-ref '(1) 5)", &mut env).is_err());
+        assert!(eval_str("(length 5)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_higher_order() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        let ints = |ns: &[i64]| Object::List(ns.iter().map(|&n| Object::Integer(n)).collect());
+
+        assert_eq!(
+            Ok(ints(&[2, 4, 6])),
+            eval_str("(map (lambda (x) (* x 2)) '(1 2 3))", &mut env)
+        );
+        assert_eq!(
+            Ok(ints(&[11, 22])),
+            eval_str("(map (lambda (a b) (+ a b)) '(1 2 3) '(10 20))", &mut env)
+        );
+        assert_eq!(
+            Ok(ints(&[3, 4])),
+            eval_str("(filter (lambda (x) (> x 2)) '(1 2 3 4))", &mut env)
+        );
+        assert_eq!(
+            Ok(ints(&[3, 2, 1])),
+            eval_str(
+                "(fold-left (lambda (acc x) (cons x acc)) '() '(1 2 3))",
+                &mut env
+            )
+        );
+        assert_eq!(
+            Ok(ints(&[1, 2, 3])),
+            eval_str(
+                "(fold-right (lambda (x acc) (cons x acc)) '() '(1 2 3))",
+                &mut env
+            )
+        );
+        assert_eq!(
+            Ok(Object::Integer(10)),
+            eval_str("(reduce (lambda (x acc) (+ x acc)) 0 '(1 2 3 4))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(0)),
+            eval_str("(reduce (lambda (x acc) (+ x acc)) 0 '())", &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_for_each() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define total 0)", &mut env).unwrap();
+
+        assert_eq!(
+            Ok(Object::Void),
+            eval_str(
+                "(for-each (lambda (a b) (set! total (+ total (* a b)))) '(1 2 3) '(4 5 6))",
+                &mut env
+            )
+        );
+        assert_eq!(Ok(Object::Integer(32)), eval_str("total", &mut env));
+    }
+
+    #[test]
+    fn test_eval_search() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define alist '((a . 1) (b . 2) (\"c\" . 3)))", &mut env).unwrap();
+
+        assert_eq!(
+            Ok("(b . 2)".to_string()),
+            eval_str("(assq 'b alist)", &mut env).map(|obj| obj.to_string())
+        );
+        assert_eq!(
+            Ok("(\"c\" . 3)".to_string()),
+            eval_str("(assoc \"c\" alist)", &mut env).map(|obj| obj.to_string())
+        );
+        assert_eq!(
+            Ok(Object::Bool(false)),
+            eval_str("(assq \"c\" alist)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::List(vec![Object::Integer(2), Object::Integer(3)])),
+            eval_str("(member 2 '(1 2 3))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Bool(false)),
+            eval_str("(memq 'd '(a b c))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::List(vec![Object::Integer(3)])),
+            eval_str("(member 2 '(1 3) (lambda (a b) (< a b)))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(4)),
+            eval_str("(find (lambda (x) (> x 3)) '(1 4 6))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(2)),
+            eval_str("(position 'c '(a b c))", &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_vectors() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define v (make-vector 3 'x))", &mut env).unwrap();
+        eval_str("(vector-set! v 1 42)", &mut env).unwrap();
+
+        assert_eq!(
+            Ok(Object::Integer(42)),
+            eval_str("(vector-ref v 1)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(3)),
+            eval_str("(vector-length v)", &mut env)
+        );
+        assert_eq!(
+            Ok("#(x 42 x)".to_string()),
+            eval_str("v", &mut env).map(|obj| obj.to_string())
+        );
+        assert_eq!(
+            Ok(Object::List(vec![Object::Integer(1), Object::Integer(2)])),
+            eval_str("(vector->list #(1 2))", &mut env)
+        );
+        assert_eq!(
+            eval_str("(vector 1 2)", &mut env),
+            eval_str("(list->vector '(1 2))", &mut env)
+        );
+        assert!(eval_str("(vector-ref v 3)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_hash_tables() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define t (make-hash-table))", &mut env).unwrap();
+        eval_str("(hash-set! t 'a 1)", &mut env).unwrap();
+        eval_str("(hash-set! t '(1 \"two\") 2)", &mut env).unwrap();
+        eval_str("(hash-set! t 'a 3)", &mut env).unwrap();
+
+        assert_eq!(
+            Ok(Object::Integer(3)),
+            eval_str("(hash-ref t 'a)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(2)),
+            eval_str("(hash-ref t (list 1 \"two\"))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(0)),
+            eval_str("(hash-ref t 'b 0)", &mut env)
+        );
+        assert!(eval_str("(hash-ref t 'b)", &mut env).is_err());
+        assert_eq!(Ok(Object::Integer(2)), eval_str("(hash-count t)", &mut env));
+
+        eval_str("(hash-remove! t 'a)", &mut env).unwrap();
+        assert_eq!(
+            Ok(Object::List(vec![Object::List(vec![
+                Object::Integer(1),
+                Object::String("two".to_string()),
+            ])])),
+            eval_str("(hash-keys t)", &mut env)
+        );
+
+        eval_str("(define sum 0)", &mut env).unwrap();
+        eval_str(
+            "(hash-for-each t (lambda (k v) (set! sum (+ sum v))))",
+            &mut env,
+        )
+        .unwrap();
+        assert_eq!(Ok(Object::Integer(2)), eval_str("sum", &mut env));
+        assert!(eval_str("(hash-set! t (lambda (x) x) 1)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_sets() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define a (set 3 1 2 1))", &mut env).unwrap();
+        eval_str("(define b (set-add (set 2 3) 4))", &mut env).unwrap();
+        let ints = |ns: &[i64]| Object::List(ns.iter().map(|&n| Object::Integer(n)).collect());
+
+        assert_eq!(Ok(ints(&[1, 2, 3])), eval_str("(set->list a)", &mut env));
+        assert_eq!(
+            Ok(Object::Bool(true)),
+            eval_str("(set-member? a 2)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Bool(false)),
+            eval_str("(set-member? a 4)", &mut env)
+        );
+        assert_eq!(
+            Ok(ints(&[1, 2, 3, 4])),
+            eval_str("(set->list (set-union a b))", &mut env)
+        );
+        assert_eq!(
+            Ok(ints(&[2, 3])),
+            eval_str("(set->list (set-intersection a b))", &mut env)
+        );
+        assert_eq!(
+            Ok(ints(&[1])),
+            eval_str("(set->list (set-difference a b))", &mut env)
+        );
+        assert_eq!(
+            Ok("Set(1 2 3)".to_string()),
+            eval_str("a", &mut env).map(|obj| obj.to_string())
+        );
+    }
+
+    #[test]
+    fn test_eval_bytevectors() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define b (string->utf8 \"hé\"))", &mut env).unwrap();
+
+        assert_eq!(
+            Ok(Object::Integer(3)),
+            eval_str("(bytevector-length b)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(104)),
+            eval_str("(bytevector-u8-ref b 0)", &mut env)
+        );
+        eval_str("(bytevector-u8-set! b 0 72)", &mut env).unwrap();
+        assert_eq!(
+            Ok(Object::String("Hé".to_string())),
+            eval_str("(utf8->string b)", &mut env)
+        );
+        assert_eq!(
+            Ok("#u8(7 7)".to_string()),
+            eval_str("(make-bytevector 2 7)", &mut env).map(|obj| obj.to_string())
+        );
+        assert!(eval_str("(bytevector 256)", &mut env).is_err());
+        assert!(eval_str("(utf8->string (bytevector 255))", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_strings() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        let string = |s: &str| Object::String(s.to_string());
+
+        assert_eq!(
+            Ok(Object::Integer(5)),
+            eval_str("(string-length \"héllo\")", &mut env)
+        );
+        assert_eq!(
+            Ok(string("él")),
+            eval_str("(substring \"héllo\" 1 3)", &mut env)
+        );
+        assert_eq!(
+            Ok(string("llo")),
+            eval_str("(substring \"héllo\" 2)", &mut env)
+        );
+        assert_eq!(
+            Ok(string("foobar")),
+            eval_str("(string-append \"foo\" \"\" \"bar\")", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::List(vec![string("a"), string("b"), string("")])),
+            eval_str("(string-split \"a,b,\" \",\")", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::List(vec![string("a"), string("b")])),
+            eval_str("(string-split \"  a  b \")", &mut env)
+        );
+        assert_eq!(
+            Ok(string("ABC")),
+            eval_str("(string-upcase \"abc\")", &mut env)
+        );
+        assert_eq!(
+            Ok(string("abc")),
+            eval_str("(string-downcase \"ABC\")", &mut env)
+        );
+        assert_eq!(
+            Ok(string("x y")),
+            eval_str("(string-trim \"  x y \")", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Bool(true)),
+            eval_str("(string-contains? \"haystack\" \"st\")", &mut env)
+        );
+        assert_eq!(
+            Ok(string("a-b-c")),
+            eval_str("(string-join '(\"a\" \"b\" \"c\") \"-\")", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(2)),
+            eval_str("(string-index \"héllo\" #\\l)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Bool(false)),
+            eval_str("(string-index \"abc\" \"z\")", &mut env)
+        );
+        assert!(eval_str("(substring \"abc\" 2 5)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_number_string_conversion() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        let string = |s: &str| Object::String(s.to_string());
+
+        assert_eq!(
+            Ok(string("255")),
+            eval_str("(number->string 255)", &mut env)
+        );
+        assert_eq!(
+            Ok(string("ff")),
+            eval_str("(number->string 255 16)", &mut env)
+        );
+        assert_eq!(
+            Ok(string("-1/1000")),
+            eval_str("(number->string (/ -1 8) 2)", &mut env)
+        );
+        assert_eq!(
+            Ok(string("1.5")),
+            eval_str("(number->string 1.5)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(42)),
+            eval_str("(string->number \"42\")", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(255)),
+            eval_str("(string->number \"ff\" 16)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(255)),
+            eval_str("(string->number \"#xff\")", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Float(2.5)),
+            eval_str("(string->number \"2.5\")", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Bool(false)),
+            eval_str("(string->number \"abc\")", &mut env)
+        );
+        assert_eq!(
+            Ok(string("foo")),
+            eval_str("(symbol->string 'foo)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Symbol("bar".to_string())),
+            eval_str("(string->symbol \"bar\")", &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_char_processing() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        assert_eq!(
+            Ok(Object::Bool(true)),
+            eval_str("(char-alphabetic? #\\é)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Bool(false)),
+            eval_str("(char-alphabetic? #\\1)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Bool(true)),
+            eval_str("(char-numeric? #\\7)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Char('A')),
+            eval_str("(char-upcase #\\a)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Char('ß')),
+            eval_str("(char-upcase #\\ß)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::List(vec![Object::Char('h'), Object::Char('i')])),
+            eval_str("(string->list \"hi\")", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::String("HI".to_string())),
+            eval_str(
+                "(list->string (map (lambda (c) (char-upcase c)) (string->list \"hi\")))",
+                &mut env
+            )
+        );
+        assert!(eval_str("(list->string '(1))", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_math() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        let int = |n: i64| Ok(Object::Integer(n));
+
+        assert_eq!(int(5), eval_str("(abs -5)", &mut env));
+        assert_eq!(int(1), eval_str("(min 3 1 2)", &mut env));
+        assert_eq!(Ok(Object::Float(3.0)), eval_str("(max 1 3 2.5)", &mut env));
+        assert_eq!(int(-3), eval_str("(quotient -7 2)", &mut env));
+        assert_eq!(int(-1), eval_str("(remainder -7 2)", &mut env));
+        assert_eq!(int(1), eval_str("(modulo -7 2)", &mut env));
+        assert_eq!(int(-1), eval_str("(modulo 7 -2)", &mut env));
+        assert_eq!(int(1024), eval_str("(expt 2 10)", &mut env));
+        assert_eq!(
+            Ok("1/8".to_string()),
+            eval_str("(expt 2 -3)", &mut env).map(|obj| obj.to_string())
+        );
+        assert_eq!(
+            Ok("1267650600228229401496703205376".to_string()),
+            eval_str("(expt 2 100)", &mut env).map(|obj| obj.to_string())
+        );
+        assert_eq!(Ok(Object::Float(2.0)), eval_str("(expt 4.0 0.5)", &mut env));
+        assert_eq!(int(6), eval_str("(gcd 12 18)", &mut env));
+        assert_eq!(int(36), eval_str("(lcm 12 18)", &mut env));
+        assert_eq!(int(-4), eval_str("(floor (/ -7 2))", &mut env));
+        assert_eq!(int(-3), eval_str("(ceiling (/ -7 2))", &mut env));
+        assert_eq!(int(-3), eval_str("(truncate (/ -7 2))", &mut env));
+        assert_eq!(int(-4), eval_str("(round (/ -7 2))", &mut env));
+        assert_eq!(int(2), eval_str("(round (/ 5 2))", &mut env));
+        assert_eq!(Ok(Object::Float(2.0)), eval_str("(round 2.5)", &mut env));
+        assert!(eval_str("(quotient 1 0)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_transcendental() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        let float = |src: &str, env: &mut Rc<RefCell<Env>>| match eval_str(src, env) {
+            Ok(Object::Float(f)) => f,
+            other => panic!("expected a float from {}, got {:?}", src, other),
+        };
+
+        assert_eq!(3.0, float("(sqrt 9)", &mut env));
+        assert_eq!(1.0, float("(exp 0)", &mut env));
+        assert!((float("(log (exp 2))", &mut env) - 2.0).abs() < 1e-12);
+        assert!((float("(log 8 2)", &mut env) - 3.0).abs() < 1e-12);
+        assert_eq!(0.0, float("(sin 0)", &mut env));
+        assert_eq!(1.0, float("(cos 0.0)", &mut env));
+        assert!((float("(tan (atan 1))", &mut env) - 1.0).abs() < 1e-12);
+        assert!((float("(atan 1 -1)", &mut env) - 3.0 * std::f64::consts::FRAC_PI_4).abs() < 1e-12);
+        assert!(eval_str("(sqrt 'x)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_bitwise() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        let int = |n: i64| Ok(Object::Integer(n));
+
+        assert_eq!(int(0b1000), eval_str("(bit-and #b1100 #b1010)", &mut env));
+        assert_eq!(int(0b1110), eval_str("(bit-or #b1100 #b1010)", &mut env));
+        assert_eq!(int(0b0110), eval_str("(bit-xor #b1100 #b1010)", &mut env));
+        assert_eq!(int(-6), eval_str("(bit-not 5)", &mut env));
+        assert_eq!(int(3), eval_str("(bit-count 7)", &mut env));
+        assert_eq!(int(40), eval_str("(arithmetic-shift 5 3)", &mut env));
+        assert_eq!(int(-3), eval_str("(arithmetic-shift -5 -1)", &mut env));
+        assert_eq!(
+            Ok("18446744073709551616".to_string()),
+            eval_str("(arithmetic-shift 1 64)", &mut env).map(|obj| obj.to_string())
+        );
+        assert!(eval_str("(bit-and 1.5 1)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_variadic_arithmetic() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        let int = |n: i64| Ok(Object::Integer(n));
+
+        assert_eq!(int(10), eval_str("(+ 1 2 3 4)", &mut env));
+        assert_eq!(int(0), eval_str("(+)", &mut env));
+        assert_eq!(int(24), eval_str("(* 1 2 3 4)", &mut env));
+        assert_eq!(int(1), eval_str("(*)", &mut env));
+        assert_eq!(int(-5), eval_str("(- 5)", &mut env));
+        assert_eq!(int(4), eval_str("(- 10 3 2 1)", &mut env));
+        assert_eq!(
+            Ok("1/4".to_string()),
+            eval_str("(/ 4)", &mut env).map(|obj| obj.to_string())
+        );
+        assert_eq!(int(2), eval_str("(/ 24 3 4)", &mut env));
+        assert_eq!(Ok(Object::Float(6.5)), eval_str("(+ 1 2 3.5)", &mut env));
+        assert!(eval_str("(-)", &mut env).is_err());
+        assert!(eval_str("(+ 1 'a)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_nary_comparison() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define x 5)", &mut env).unwrap();
+
+        assert_eq!(Ok(Object::Bool(true)), eval_str("(< 1 x 10)", &mut env));
+        assert_eq!(Ok(Object::Bool(false)), eval_str("(< 1 x 3)", &mut env));
+        assert_eq!(Ok(Object::Bool(true)), eval_str("(<= 1 1 2)", &mut env));
+        assert_eq!(Ok(Object::Bool(false)), eval_str("(>= 3 4 1)", &mut env));
+        assert_eq!(
+            Ok(Object::Bool(true)),
+            eval_str("(> 3 2.5 (/ 1 2))", &mut env)
+        );
+        assert_eq!(Ok(Object::Bool(true)), eval_str("(= 2 2 2.0)", &mut env));
+        assert_eq!(Ok(Object::Bool(true)), eval_str("(< 1)", &mut env));
+        assert!(eval_str("(< 2 1 'a)", &mut env).is_err());
+        assert!(eval_str("(<)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_equality_predicates() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        eval_str("(define v (vector 1 2))", &mut env).unwrap();
+
+        assert_eq!(Ok(Object::Bool(true)), eval_str("(eq? 'a 'a)", &mut env));
+        assert_eq!(Ok(Object::Bool(true)), eval_str("(eqv? 2 2)", &mut env));
+        assert_eq!(Ok(Object::Bool(false)), eval_str("(eqv? 2 2.0)", &mut env));
+        assert_eq!(Ok(Object::Bool(true)), eval_str("(eq? '() '())", &mut env));
+        assert_eq!(
+            Ok(Object::Bool(false)),
+            eval_str("(eq? '(1) '(1))", &mut env)
+        );
+        assert_eq!(Ok(Object::Bool(true)), eval_str("(eq? v v)", &mut env));
+        assert_eq!(
+            Ok(Object::Bool(false)),
+            eval_str("(eqv? v (vector 1 2))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Bool(true)),
+            eval_str("(equal? v (vector 1 2))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Bool(true)),
+            eval_str(
+                "(equal? '(1 (\"a\" . 2)) (list 1 (cons \"a\" 2)))",
+                &mut env
+            )
+        );
+        assert_eq!(
+            Ok(Object::Bool(false)),
+            eval_str("(equal? \"a\" \"b\")", &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_type_predicates() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        let yes = Ok(Object::Bool(true));
+        let no = Ok(Object::Bool(false));
+        let symbol = |s: &str| Ok(Object::Symbol(s.to_string()));
+
+        assert_eq!(yes, eval_str("(integer? 99999999999999999999)", &mut env));
+        assert_eq!(yes, eval_str("(integer? 2.0)", &mut env));
+        assert_eq!(no, eval_str("(integer? (/ 1 2))", &mut env));
+        assert_eq!(yes, eval_str("(rational? (/ 1 2))", &mut env));
+        assert_eq!(yes, eval_str("(number? 1.5)", &mut env));
+        assert_eq!(yes, eval_str("(symbol? 'a)", &mut env));
+        assert_eq!(yes, eval_str("(string? \"a\")", &mut env));
+        assert_eq!(yes, eval_str("(list? '(1 2))", &mut env));
+        assert_eq!(no, eval_str("(list? '(1 . 2))", &mut env));
+        assert_eq!(yes, eval_str("(procedure? (lambda (x) x))", &mut env));
+        assert_eq!(no, eval_str("(procedure? 'car)", &mut env));
+        assert_eq!(symbol("integer"), eval_str("(type-of 1)", &mut env));
+        assert_eq!(symbol("pair"), eval_str("(type-of '(1))", &mut env));
+        assert_eq!(symbol("null"), eval_str("(type-of '())", &mut env));
+        assert_eq!(symbol("string"), eval_str("(type-of \"s\")", &mut env));
+        assert_eq!(
+            symbol("procedure"),
+            eval_str("(type-of (lambda () 1))", &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_print() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        assert_eq!(Ok(Object::Void), eval_str("(display \"hi\")", &mut env));
+        assert_eq!(Ok(Object::Void), eval_str("(write '(1 \"two\"))", &mut env));
+        assert_eq!(Ok(Object::Void), eval_str("(newline)", &mut env));
+        assert!(eval_str("(display)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_format() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        assert_eq!(
+            Ok(Object::String("x = 42, s = \"hi\" hi~\n".to_string())),
+            eval_str(
+                "(format #f \"x = ~d, s = ~s ~a~~~%\" 42 \"hi\" \"hi\")",
+                &mut env
+            )
+        );
+        assert_eq!(
+            Ok(Object::String("<1><2><3>".to_string())),
+            eval_str("(format \"~{<~a>~}\" '(1 2 3))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::String("a=1 b=2 ".to_string())),
+            eval_str("(format #f \"~{~a=~a ~}\" '(a 1 b 2))", &mut env)
+        );
+        assert_eq!(Ok(Object::Void), eval_str("(format #t \"\")", &mut env));
+        assert!(eval_str("(format #f \"~d\" 'x)", &mut env).is_err());
+        assert!(eval_str("(format #f \"~a\")", &mut env).is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_eval_json() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        let program =
+            "(let ((config (json-parse \"{\\\"name\\\": \\\"lisp\\\", \\\"tags\\\": [1, null]}\")))
+            (list (hash-ref config \"name\") (hash-ref config \"tags\")))";
+        assert_eq!(
+            Ok(Object::List(vec![
+                Object::String("lisp".to_string()),
+                Object::List(vec![Object::Integer(1), Object::Symbol("null".to_string())]),
+            ])),
+            eval_str(program, &mut env)
+        );
+
+        let program = "(let ((table (make-hash-table)))
+            (hash-set! table 'ok #t)
+            (hash-set! table \"items\" (list 1.5 \"a\" (vector)))
+            (json-stringify table))";
+        assert_eq!(
+            Ok(Object::String(
+                "{\"items\":[1.5,\"a\",[]],\"ok\":true}".to_string()
+            )),
+            eval_str(program, &mut env)
+        );
+        assert!(eval_str("(json-parse \"[1,]\")", &mut env).is_err());
+        assert!(eval_str("(json-parse \"1e400\")", &mut env).is_err());
+        assert!(eval_str("(json-stringify (lambda () 1))", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_csv() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        let program = "(let ((out (open-output-string)))
+            (write-csv '((name age) (\"Smith, J\" 42)) out)
+            (get-output-string out))";
+        assert_eq!(
+            Ok(Object::String("name,age\n\"Smith, J\",42\n".to_string())),
+            eval_str(program, &mut env)
+        );
+
+        let program = "(read-csv (open-input-string \"a,b\\n1,2\\n\"))";
+        assert_eq!(
+            Ok(Object::List(vec![
+                Object::List(vec![
+                    Object::String("a".to_string()),
+                    Object::String("b".to_string()),
+                ]),
+                Object::List(vec![
+                    Object::String("1".to_string()),
+                    Object::String("2".to_string()),
+                ]),
+            ])),
+            eval_str(program, &mut env)
+        );
+
+        let program =
+            "(cdr (assoc \"b\" (car (read-csv (open-input-string \"a,b\\n1,2\\n\") #t))))";
+        assert_eq!(
+            Ok(Object::String("2".to_string())),
+            eval_str(program, &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_regex() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        assert_eq!(
+            Ok(Object::Bool(true)),
+            eval_str("(regex-match? \"^\\\\d+$\" \"123\")", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::List(vec![
+                Object::String("key=val".to_string()),
+                Object::String("key".to_string()),
+                Object::Bool(false),
+                Object::String("val".to_string()),
+            ])),
+            eval_str(
+                "(regex-find \"(\\\\w+)(:)?=(\\\\w+)\" \"a key=val\")",
+                &mut env
+            )
+        );
+        assert_eq!(
+            Ok(Object::Bool(false)),
+            eval_str("(regex-find \"x\" \"abc\")", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::String("b$a $$ d$c".to_string())),
+            eval_str(
+                "(regex-replace \"(\\\\w)(\\\\w)\" \"ab $$ cd\" \"$2$$$1\")",
+                &mut env
+            )
+        );
+        assert_eq!(
+            Ok(Object::List(vec![
+                Object::String("a".to_string()),
+                Object::String("b".to_string()),
+                Object::String("".to_string()),
+                Object::String("c".to_string()),
+            ])),
+            eval_str("(regex-split \", *\" \"a, b,,c\")", &mut env)
+        );
+        assert!(eval_str("(regex-match? \"(\" \"x\")", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_read_write_string() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        assert_eq!(
+            Ok(Object::Integer(6)),
+            eval_str("(eval (read-from-string \"(+ 1 2 3)\"))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::String("(1 \"a\\nb\" #\\c)".to_string())),
+            eval_str("(write-to-string '(1 \"a\\nb\" #\\c))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Bool(true)),
+            eval_str(
+                "(equal? '(x #(1 2.5) \"s\") (read-from-string (write-to-string '(x #(1 2.5) \"s\"))))",
+                &mut env
+            )
+        );
+        assert_eq!(
+            Ok(Object::Eof),
+            eval_str("(read-from-string \"  \")", &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_ports() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        let path = std::env::temp_dir().join(format!("lisp-rs-ports-{}.txt", std::process::id()));
+        env.borrow_mut()
+            .set("path", Object::String(path.display().to_string()));
+
+        let program = "(begin
+            (define out (open-output-file path))
+            (display \"héllo\" out)
+            (write-char #\\! out)
+            (newline out)
+            (write '(1 \"two\") out)
+            (close-port out)
+            (define in (open-input-file path))
+            (define results
+              (list (read-char in) (read-line in) (read in) (read in) (read-line in)))
+            (close-port in)
+            results)";
+        let result = eval_str(program, &mut env);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            Ok(Object::List(vec![
+                Object::Char('h'),
+                Object::String("éllo!".to_string()),
+                Object::List(vec![Object::Integer(1), Object::String("two".to_string())]),
+                Object::Eof,
+                Object::Eof,
+            ])),
+            result
+        );
+        assert!(eval_str("(read-line out)", &mut env).is_err());
+        assert!(eval_str("(display 1 in)", &mut env).is_err());
+        assert_eq!(
+            Ok(Object::Bool(true)),
+            eval_str(
+                "(file-error? (guard (e (#t e)) (open-input-file \"/nonexistent/x\")))",
+                &mut env
+            )
+        );
+    }
+
+    #[test]
+    fn test_eval_string_ports() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        let program = "(let ((out (open-output-string)))
+            (write 'sym out)
+            (display \" \" out)
+            (write \"s\" out)
+            (write-char #\\. out)
+            (get-output-string out))";
+        assert_eq!(
+            Ok(Object::String("sym \"s\".".to_string())),
+            eval_str(program, &mut env)
+        );
+
+        let program = "(let ((in (open-input-string \"(a b) line\\nrest\")))
+            (list (read in) (read-char in) (read-line in) (read-line in) (read-line in)))";
+        assert_eq!(
+            Ok(Object::List(vec![
+                Object::List(vec![
+                    Object::Symbol("a".to_string()),
+                    Object::Symbol("b".to_string()),
+                ]),
+                Object::Char(' '),
+                Object::String("line".to_string()),
+                Object::String("rest".to_string()),
+                Object::Eof,
+            ])),
+            eval_str(program, &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_sleep() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        let start = std::time::Instant::now();
+        assert_eq!(Ok(Object::Void), eval_str("(sleep 20)", &mut env));
+        assert_eq!(Ok(Object::Void), eval_str("(sleep 0.5)", &mut env));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert!(eval_str("(sleep -1)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_environment_variables() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        assert_eq!(
+            Ok(Object::Void),
+            eval_str("(setenv \"LISP_RS_TEST_VAR\" \"value\")", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::String("value".to_string())),
+            eval_str("(getenv \"LISP_RS_TEST_VAR\")", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Bool(false)),
+            eval_str("(getenv \"LISP_RS_UNSET_VAR\")", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::String("value\n".to_string())),
+            eval_str("(process-output \"echo $LISP_RS_TEST_VAR\")", &mut env)
+        );
+        assert!(std::env::var("LISP_RS_TEST_VAR").is_err());
+        assert!(eval_str("(setenv \"A=B\" \"x\")", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_shell_commands() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        assert_eq!(
+            Ok(Object::Integer(0)),
+            eval_str("(system \"true\")", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(3)),
+            eval_str("(system \"exit 3\")", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::String("a b\n".to_string())),
+            eval_str("(process-output \"echo a b\")", &mut env)
+        );
+        assert!(eval_str("(exit \"now\")", &mut env).is_err());
+        assert_eq!(
+            Err("exit: code out of range: 4294967296".to_string()),
+            eval_str("(exit 4294967296)", &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_command_line() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        let expected = std::env::args().skip(1).map(Object::String).collect();
+        assert_eq!(
+            Ok(Object::List(expected)),
+            eval_str("(command-line)", &mut env)
+        );
+        assert!(eval_str("(command-line 1)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_threads() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        let program = "(begin
+            (define (fib n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))
+            (define counter 0)
+            (define threads
+              (map (lambda (n) (spawn (lambda () (set! counter (+ counter 1)) (list n (fib n)))))
+                   '(10 15)))
+            (list (map (lambda (t) (join t)) threads) counter))";
+        assert_eq!(
+            Ok(Object::List(vec![
+                Object::List(vec![
+                    Object::List(vec![Object::Integer(10), Object::Integer(55)]),
+                    Object::List(vec![Object::Integer(15), Object::Integer(610)]),
+                ]),
+                Object::Integer(0),
+            ])),
+            eval_str(program, &mut env)
+        );
+
+        assert!(eval_str("(join (spawn (lambda () (car '()))))", &mut env).is_err());
+        assert!(
+            eval_str(
+                "(let ((t (spawn (lambda () 1)))) (join t) (join t))",
+                &mut env
+            )
+            .is_err()
+        );
+        assert!(eval_str("(spawn (open-output-string))", &mut env).is_err());
+
+        // Only the bindings a thunk refers to travel with it.
+        eval_str("(define port (open-output-string))", &mut env).unwrap();
+        assert_eq!(
+            Ok(Object::Integer(1)),
+            eval_str("(join (spawn (lambda () 1)))", &mut env)
+        );
+        assert_eq!(
+            Err("Cannot send Port(string) to another thread (bound to port)".to_string()),
+            eval_str("(spawn (lambda () (display 1 port)))", &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_futures() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        let program = "(begin
+            (define (fib n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))
+            (define n 15)
+            (define a (future (fib n)))
+            (define b (future (list n (fib 10))))
+            (list (await a) (await b) (await a) (type-of a)))";
+        assert_eq!(
+            Ok(Object::List(vec![
+                Object::Integer(610),
+                Object::List(vec![Object::Integer(15), Object::Integer(55)]),
+                Object::Integer(610),
+                Object::Symbol("future".to_string()),
+            ])),
+            eval_str(program, &mut env)
+        );
+        assert!(
+            eval_str(
+                "(let ((f (future (car '())))) (list (guard (e (#t 'failed)) (await f)) (await f)))",
+                &mut env
+            )
+            .is_err()
+        );
+        assert!(eval_str("(future)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_timers() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        let program = "(let ((log '()) (ticks 0))
+            (define t (every 10 (lambda ()
+              (set! ticks (+ ticks 1))
+              (when (= ticks 3) (cancel-timer t)))))
+            (after 5 (lambda () (set! log (cons 'after log))))
+            (define late (after 60000 (lambda () (set! log (cons 'late log)))))
+            (sleep 100)
+            (list log ticks (cancel-timer t) (cancel-timer late) (type-of t)))";
+        assert_eq!(
+            Ok(Object::List(vec![
+                Object::List(vec![Object::Symbol("after".to_string())]),
+                Object::Integer(3),
+                Object::Bool(false),
+                Object::Bool(true),
+                Object::Symbol("timer".to_string()),
+            ])),
+            eval_str(program, &mut env)
+        );
+
+        eval_str(
+            "(begin (define fired #f) (after 10 (lambda () (set! fired #t))))",
+            &mut env,
+        )
+        .unwrap();
+        assert_eq!(Some(Object::Bool(false)), env.borrow().get("fired"));
+        run_timers().unwrap();
+        assert_eq!(Some(Object::Bool(true)), env.borrow().get("fired"));
+        assert_eq!(None, next_timer_deadline());
+
+        assert!(eval_str("(every 0 (lambda () 1))", &mut env).is_err());
+        assert!(eval_str("(after -5 (lambda () 1))", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_native_fn() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        env.borrow_mut().register_native("sum-ints", |args| {
+            args.iter()
+                .map(|arg| match arg {
+                    Object::Integer(n) => Ok(*n),
+                    _ => Err(LispError::Eval(format!(
+                        "sum-ints: not an integer: {}",
+                        arg
+                    ))),
+                })
+                .sum::<Result<i64, _>>()
+                .map(Object::Integer)
+        });
+
+        assert_eq!(
+            Ok(Object::Integer(6)),
+            eval_str("(sum-ints 1 (+ 1 1) 3)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::List(vec![Object::Integer(3), Object::Integer(7)])),
+            eval_str("(map sum-ints '(1 3) '(2 4))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Bool(true)),
+            eval_str("(procedure? sum-ints)", &mut env)
+        );
+        assert_eq!(
+            Err("sum-ints: not an integer: a".to_string()),
+            eval_str("(sum-ints 1 'a)", &mut env)
+        );
+
+        // Builtins are procedures too, and natives the host binds under a
+        // builtin's name replace it.
+        assert_eq!(
+            Ok(Object::List(vec![Object::Integer(1), Object::Integer(3)])),
+            eval_str("(map car '((1 2) (3 4)))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(5)),
+            eval_str("(let ((f +)) (f 2 3))", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Bool(true)),
+            eval_str("(procedure? car)", &mut env)
+        );
+        assert_eq!(
+            Ok(Object::Integer(3)),
+            eval_str(
+                "((lambda (f) (f (lambda () (values 1 2)) +)) call-with-values)",
+                &mut env
+            )
+        );
+        env.borrow_mut()
+            .register_native("car", |_| Ok(Object::Symbol("host".to_string())));
+        assert_eq!(
+            Ok(Object::Symbol("host".to_string())),
+            eval_str("(car '(1))", &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_foreign() {
+        struct Account {
+            balance: i64,
+        }
+
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        env.borrow_mut().set(
+            "account",
+            Object::foreign("account", Account { balance: 7 }),
+        );
+        env.borrow_mut().register_native("balance", |args| {
+            match args.first().and_then(Object::downcast_foreign::<Account>) {
+                Some(account) => Ok(Object::Integer(account.balance)),
+                None => Err(LispError::Eval("balance: not an account".to_string())),
+            }
+        });
+
+        assert_eq!(
+            Ok(Object::List(vec![
+                Object::Symbol("account".to_string()),
+                Object::Integer(7),
+                Object::Bool(true),
+            ])),
+            eval_str(
+                "(let ((a account)) (list (type-of a) (balance a) (eq? a account)))",
+                &mut env
+            )
+        );
+        assert!(eval_str("(balance 1)", &mut env).is_err());
+        assert!(eval_str("(join (spawn (lambda () account)))", &mut env).is_err());
+        assert_eq!(None, Object::foreign("n", 1u8).downcast_foreign::<i32>());
+    }
+
+    #[test]
+    fn test_eval_backtrace() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        let program = "(begin
+            (define (inner x) (car x))
+            (define (middle x) (+ 1 (inner x)))
+            (define (outer x) (middle x))
+            (define (spin n) (if (= n 0) (outer 5) (spin (- n 1))))
+            (spin 1000))";
+        let eval_err = |src: &str, env: &mut Rc<RefCell<Env>>| {
+            let ast = parse(&mut tokenize(src).unwrap()).unwrap();
+            eval(&ast, env).unwrap_err()
+        };
+        let err = eval_err(program, &mut env);
+        assert_eq!("car: not a pair: 5", err.to_string());
+        assert_eq!(["inner", "middle", "spin"], err.backtrace());
+
+        // The same error raised elsewhere carries its own backtrace.
+        let err = eval_err("(car 5)", &mut env);
+        assert_eq!("car: not a pair: 5", err.to_string());
+        assert!(err.backtrace().is_empty());
+
+        assert_eq!(
+            Ok(Object::Integer(0)),
+            eval_str("(guard (e (#t 0)) (outer 1))", &mut env)
+        );
+        assert_eq!(0, call_depth());
+    }
+
+    #[test]
+    fn test_eval_load_plugin() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        assert!(eval_str("(load-plugin \"/nonexistent/libplugin.so\")", &mut env).is_err());
+        assert!(eval_str("(load-plugin 1)", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_pmap() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        let program = "(begin
+            (define (fib n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))
+            (pmap (lambda (n) (fib n)) '(1 2 3 4 5 6 7 8 9 10)))";
+        assert_eq!(
+            Ok(Object::List(
+                [1, 1, 2, 3, 5, 8, 13, 21, 34, 55]
+                    .into_iter()
+                    .map(Object::Integer)
+                    .collect()
+            )),
+            eval_str(program, &mut env)
+        );
+        assert_eq!(
+            Ok(Object::List(vec![])),
+            eval_str("(pmap (lambda (x) x) '())", &mut env)
+        );
+        assert!(eval_str("(pmap (lambda (x) (car x)) '((1) 2))", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_eval_channels() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        let program = "(let ((requests (make-channel)) (replies (make-channel)))
+            (define (serve)
+              (let ((n (channel-recv requests)))
+                (if (eq? n 'done)
+                    'finished
+                    (begin (channel-send replies (* n n)) (serve)))))
+            (define worker (spawn serve))
+            (for-each (lambda (n) (channel-send requests n)) '(1 2 3))
+            (channel-send requests 'done)
+            (list (channel-recv replies) (channel-recv replies) (channel-recv replies) (join worker)))";
+        assert_eq!(
+            Ok(Object::List(vec![
+                Object::Integer(1),
+                Object::Integer(4),
+                Object::Integer(9),
+                Object::Symbol("finished".to_string()),
+            ])),
+            eval_str(program, &mut env)
+        );
+    }
+
+    #[test]
+    fn test_eval_atoms() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        let program = "(let ((counter (atom 0)))
+            (define (bump n) (when (> n 0) (swap! counter (lambda (x) (+ x 1))) (bump (- n 1))))
+            (define workers (map (lambda (i) (spawn (lambda () (bump 50)))) '(1 2 3 4)))
+            (for-each (lambda (t) (join t)) workers)
+            (list (deref counter) (reset! counter 'x) (deref counter)))";
+        assert_eq!(
+            Ok(Object::List(vec![
+                Object::Integer(200),
+                Object::Symbol("x".to_string()),
+                Object::Symbol("x".to_string()),
+            ])),
+            eval_str(program, &mut env)
+        );
+        assert_eq!(
+            Ok(Object::List(vec![Object::Integer(1), Object::Integer(2)])),
+            eval_str(
+                "(deref (let ((a (atom '(2)))) (swap! a (lambda (l x) (cons x l)) 1) a))",
+                &mut env
+            )
+        );
+        // Local bindings shadow the builtin.
+        assert_eq!(
+            Ok(Object::Integer(3)),
+            eval_str(
+                "(let ((swap! (lambda (a b) (+ a b)))) (swap! 1 2))",
+                &mut env
+            )
+        );
+    }
+
+    #[test]
+    fn test_eval_tcp() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+
+        let Ok(Object::Port(listener)) = eval_str("(define server (tcp-listen 0))", &mut env)
+            .and_then(|_| eval_str("server", &mut env))
+        else {
+            panic!("tcp-listen should return a port");
+        };
+        let addr = listener.name.clone();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(b"(ping 1)\n").unwrap();
+            let mut reply = String::new();
+            io::BufReader::new(stream).read_line(&mut reply).unwrap();
+            reply
+        });
+
+        let program = "(let ((conn (tcp-accept server)))
+            (let ((msg (read conn)))
+              (write (list 'pong (list-ref msg 1)) conn)
+              (newline conn)
+              (close-port conn)
+              msg))";
+        assert_eq!(
+            Ok(Object::List(vec![
+                Object::Symbol("ping".to_string()),
+                Object::Integer(1),
+            ])),
+            eval_str(program, &mut env)
+        );
+        assert_eq!("(pong 1)\n", client.join().unwrap());
+        assert!(eval_str("(tcp-accept (open-output-string))", &mut env).is_err());
+    }
+
+    #[test]
+    fn test_read_datum() {
+        let mut input = io::Cursor::new("(1 2\n 'x) next\n42\n\"a\nb\"\n");
+
+        assert_eq!(
+            Ok(Object::List(vec![
+                Object::Integer(1),
+                Object::Integer(2),
+                Object::List(vec![
+                    Object::Symbol("quote".to_string()),
+                    Object::Symbol("x".to_string()),
+                ]),
+            ])),
+            read_datum(&mut input)
+        );
+        assert_eq!(
+            Ok(Object::Symbol("next".to_string())),
+            read_datum(&mut input)
+        );
+        assert_eq!(Ok(Object::Integer(42)), read_datum(&mut input));
+        assert_eq!(
+            Ok(Object::String("a\nb".to_string())),
+            read_datum(&mut input)
+        );
+        assert_eq!(Ok(Object::Eof), read_datum(&mut input));
+        assert!(read_datum(&mut io::Cursor::new("(1 2")).is_err());
     }
 }
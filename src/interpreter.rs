@@ -0,0 +1,221 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    Diagnostic, LispError, Plugin,
+    diagnostic::error_span,
+    eval::{self, Env},
+    lexer::tokenize_spanned,
+    parser::{Object, parse_program_spanned},
+};
+
+/// An interpreter with its own global environment, for embedding without
+/// handling the environment directly.
+pub struct Interpreter {
+    env: Rc<RefCell<Env>>,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter {
+            env: Rc::new(RefCell::new(Env::new())),
+        }
+    }
+
+    /// Evaluates every form in `src` and returns the value of the last one.
+    pub fn eval_str(&mut self, src: &str) -> Result<Object, LispError> {
+        crate::eval_str(src, &mut self.env)
+    }
+
+    /// Like `eval_str`, but on failure reports where in `src` the error
+    /// arose, for showing to the user.
+    pub fn eval_source(&mut self, src: &str) -> Result<Object, Box<Diagnostic>> {
+        let syntax_error = |message: String, span| {
+            Box::new(Diagnostic {
+                error: LispError::ParseError(message),
+                span,
+            })
+        };
+        let tokens =
+            tokenize_spanned(src).map_err(|e| syntax_error(e.to_string(), Some(e.span())))?;
+        let program =
+            parse_program_spanned(&tokens).map_err(|e| syntax_error(e.to_string(), e.span()))?;
+
+        let mut val = Object::Void;
+        for form in &program {
+            val = eval::eval(&form.object, &mut self.env).map_err(|error| {
+                Box::new(Diagnostic {
+                    span: Some(error_span(error.forms(), form, &program)),
+                    error,
+                })
+            })?;
+        }
+        Ok(val)
+    }
+
+    /// Evaluates an already parsed form.
+    pub fn eval(&mut self, obj: &Object) -> Result<Object, LispError> {
+        eval::eval(obj, &mut self.env)
+    }
+
+    /// Calls the procedure or builtin named `name` with already evaluated
+    /// arguments.
+    pub fn call(&mut self, name: &str, args: &[Object]) -> Result<Object, LispError> {
+        let f = self
+            .get(name)
+            .ok_or_else(|| LispError::UnboundSymbol(name.to_string()))?;
+        if !f.is_procedure() {
+            return Err(LispError::Eval(format!("not a procedure: {}", f)));
+        }
+        eval::apply_procedure(name, &f, args.to_vec())
+    }
+
+    /// Looks up a global variable, falling back to the builtin procedure of
+    /// that name.
+    pub fn get(&self, name: &str) -> Option<Object> {
+        let val = self.env.borrow().get(name);
+        val.or_else(|| eval::builtin(name).map(Object::NativeFn))
+    }
+
+    /// Binds `name` to a procedure implemented in Rust.
+    pub fn register_native(
+        &mut self,
+        name: &str,
+        func: impl Fn(&[Object]) -> Result<Object, LispError> + 'static,
+    ) {
+        self.env.borrow_mut().register_native(name, func);
+    }
+
+    /// Registers the procedures of a plugin linked into the host.
+    pub fn add_plugin(&mut self, plugin: &dyn Plugin) {
+        plugin.register(&mut self.env.borrow_mut());
+    }
+
+    /// Defines or replaces a global variable.
+    pub fn set_global(&mut self, name: &str, val: Object) {
+        self.env.borrow_mut().set(name, val);
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A procedure value held by the host, such as a lambda a script handed
+/// back as a hook. It keeps the environment it closed over alive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Procedure(Object);
+
+impl Procedure {
+    pub fn call(&self, args: &[Object]) -> Result<Object, LispError> {
+        eval::apply_procedure("procedure", &self.0, args.to_vec())
+    }
+}
+
+impl TryFrom<Object> for Procedure {
+    type Error = LispError;
+
+    fn try_from(obj: Object) -> Result<Self, Self::Error> {
+        if obj.is_procedure() {
+            Ok(Procedure(obj))
+        } else {
+            Err(LispError::Eval(format!("not a procedure: {}", obj)))
+        }
+    }
+}
+
+impl From<Procedure> for Object {
+    fn from(procedure: Procedure) -> Self {
+        procedure.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpreter() {
+        let mut interp = Interpreter::new();
+        interp.set_global("base", Object::Integer(40));
+        assert_eq!(
+            Ok(Object::Integer(42)),
+            interp.eval_str("(define answer (+ base 2)) answer")
+        );
+        assert_eq!(Some(Object::Integer(42)), interp.get("answer"));
+        assert_eq!(None, interp.get("missing"));
+
+        let form = Object::List(vec![
+            Object::Symbol("*".to_string()),
+            Object::Symbol("answer".to_string()),
+            Object::Integer(2),
+        ]);
+        assert_eq!(Ok(Object::Integer(84)), interp.eval(&form));
+
+        assert!(matches!(
+            interp.eval_str(")"),
+            Err(LispError::ParseError(_))
+        ));
+        assert_eq!(
+            &LispError::UnboundSymbol("missing".to_string()),
+            interp.eval_str("missing").unwrap_err().cause()
+        );
+    }
+
+    #[test]
+    fn test_interpreter_call() {
+        let mut interp = Interpreter::new();
+        interp
+            .eval_str("(define (greet name) (string-append \"hello \" name))")
+            .unwrap();
+        assert_eq!(
+            Ok(Object::from("hello lisp")),
+            interp.call("greet", &[Object::from("lisp")])
+        );
+        assert_eq!(
+            Ok(Object::from(vec![
+                Object::from("a"),
+                Object::Symbol("b".to_string())
+            ])),
+            interp.call(
+                "list",
+                &[Object::from("a"), Object::Symbol("b".to_string())]
+            )
+        );
+        assert!(interp.call("greet", &[]).is_err());
+        interp.set_global("answer", Object::Integer(42));
+        assert!(interp.call("missing", &[]).is_err());
+        assert_eq!(
+            Err(LispError::Eval("not a procedure: 42".to_string())),
+            interp.call("answer", &[])
+        );
+
+        let counter = interp
+            .eval_str("(let ((n 0)) (lambda (step) (set! n (+ n step)) n))")
+            .unwrap();
+        let counter = Procedure::try_from(counter).unwrap();
+        assert_eq!(Ok(Object::from(2)), counter.call(&[Object::from(2)]));
+        assert_eq!(Ok(Object::from(5)), counter.call(&[Object::from(3)]));
+        assert!(Procedure::try_from(Object::from(1)).is_err());
+    }
+
+    #[test]
+    fn test_interpreter_add_plugin() {
+        struct Greeter;
+
+        impl Plugin for Greeter {
+            fn name(&self) -> &str {
+                "greeter"
+            }
+
+            fn register(&self, env: &mut Env) {
+                env.register_native("greeting", |_| Ok(Object::from("hi")));
+            }
+        }
+
+        let mut interp = Interpreter::new();
+        interp.add_plugin(&Greeter);
+        assert_eq!(Ok(Object::from("hi")), interp.eval_str("(greeting)"));
+    }
+}
@@ -0,0 +1,160 @@
+use std::{cell::RefCell, rc::Rc};
+
+use serde_json::Value;
+
+use crate::{LispError, bigint::BigInt, parser::Object};
+
+/// The symbol JSON `null` reads as and is written from.
+pub const NULL: &str = "null";
+
+/// Parses a JSON document. Objects become hash tables keyed by strings,
+/// arrays become lists and `null` becomes the symbol `null`.
+/// Numbers outside the range of a float are rejected.
+pub fn parse(source: &str) -> Result<Object, String> {
+    let value = serde_json::from_str(source).map_err(|e| format!("json-parse: {}", e))?;
+    from_value(value)
+}
+
+/// Serializes `obj` as compact JSON. Hash table entries are written in key
+/// order so the output is deterministic.
+pub fn stringify(obj: &Object) -> Result<String, LispError> {
+    let value = obj.to_json()?;
+    Ok(serde_json::to_string(&value).expect("JSON values always serialize"))
+}
+
+fn from_value(value: Value) -> Result<Object, String> {
+    Ok(match value {
+        Value::Null => Object::Symbol(NULL.to_string()),
+        Value::Bool(b) => Object::Bool(b),
+        Value::Number(n) => {
+            let text = n.as_str();
+            match n.as_i64() {
+                Some(n) => Object::Integer(n),
+                None if !text.contains(['.', 'e', 'E']) => BigInt::parse(text)
+                    .map(Object::BigInt)
+                    .ok_or_else(|| format!("json-parse: invalid number: {}", text))?,
+                None => match n.as_f64() {
+                    Some(f) if f.is_finite() => Object::Float(f),
+                    _ => return Err(format!("json-parse: number out of range: {}", text)),
+                },
+            }
+        }
+        Value::String(s) => Object::String(s),
+        Value::Array(items) => Object::List(
+            items
+                .into_iter()
+                .map(from_value)
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(entries) => {
+            let table = entries
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = Object::String(key);
+                    let hash_key = key.hash_key().expect("strings are hashable");
+                    Ok((hash_key, (key, from_value(value)?)))
+                })
+                .collect::<Result<_, String>>()?;
+            Object::HashTable(Rc::new(RefCell::new(table)))
+        }
+    })
+}
+
+/// Conversions to and from `serde_json::Value`, mapping values the same way
+/// as `parse` and `stringify`.
+impl Object {
+    pub fn from_json(value: serde_json::Value) -> Result<Object, LispError> {
+        from_value(value).map_err(LispError::Eval)
+    }
+
+    pub fn to_json(&self) -> Result<serde_json::Value, LispError> {
+        use serde_json::{Map, Number};
+
+        let invalid = || LispError::Eval(format!("to-json: cannot serialize {}", self));
+        let float = |n: f64| Number::from_f64(n).map(Value::Number).ok_or_else(invalid);
+        let array = |items: &[Object]| {
+            items
+                .iter()
+                .map(Object::to_json)
+                .collect::<Result<_, _>>()
+                .map(Value::Array)
+        };
+        Ok(match self {
+            Object::Symbol(s) if s == NULL => Value::Null,
+            Object::Bool(b) => Value::Bool(*b),
+            Object::Integer(n) => Value::Number((*n).into()),
+            Object::BigInt(n) => Value::Number(n.to_string().parse().map_err(|_| invalid())?),
+            Object::Rational(n) => float(n.to_f64())?,
+            Object::Float(n) => float(*n)?,
+            Object::String(s) | Object::Symbol(s) | Object::Keyword(s) => Value::String(s.clone()),
+            Object::List(items) => array(items)?,
+            Object::Vector(items) => array(&items.borrow())?,
+            Object::HashTable(table) => Value::Object(
+                table
+                    .borrow()
+                    .values()
+                    .map(|(key, value)| match key {
+                        Object::String(s) | Object::Symbol(s) | Object::Keyword(s) => {
+                            Ok((s.clone(), value.to_json()?))
+                        }
+                        _ => Err(LispError::Eval(format!(
+                            "to-json: invalid object key: {}",
+                            key
+                        ))),
+                    })
+                    .collect::<Result<Map<_, _>, _>>()?,
+            ),
+            _ => return Err(invalid()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let source = r#" {"b": [1, -2.5e3, true, null], "a": "x\"é😀\n", "c": {}} "#;
+        let value = parse(source).unwrap();
+        assert_eq!(
+            r#"{"a":"x\"é😀\n","b":[1,-2500.0,true,null],"c":{}}"#,
+            stringify(&value).unwrap()
+        );
+
+        assert_eq!(
+            Object::BigInt(BigInt::parse("123456789012345678901234567890").unwrap()),
+            parse("123456789012345678901234567890").unwrap()
+        );
+        assert!(parse("[1, 2").is_err());
+        assert!(parse("01").is_err());
+        assert!(parse("1.").is_err());
+        assert!(parse("{\"a\" 1}").is_err());
+        assert!(parse("[1] x").is_err());
+        assert!(parse("1e400").is_err());
+        assert!(parse("[-1e400]").is_err());
+        assert_eq!(Object::Float(0.0), parse("1e-400").unwrap());
+        assert!(stringify(&Object::Float(f64::NAN)).is_err());
+    }
+
+    #[test]
+    fn test_json_value() {
+        let value = serde_json::json!({"a": [1, 2.5, null, "s"], "b": {"c": true}});
+        let obj = Object::from_json(value.clone()).unwrap();
+        assert_eq!(
+            r#"{"a":[1,2.5,null,"s"],"b":{"c":true}}"#,
+            stringify(&obj).unwrap()
+        );
+        assert_eq!(value, obj.to_json().unwrap());
+
+        assert_eq!(
+            serde_json::json!(18446744073709551615u64),
+            Object::from_json(serde_json::json!(18446744073709551615u64))
+                .unwrap()
+                .to_json()
+                .unwrap()
+        );
+        assert!(Object::Float(f64::INFINITY).to_json().is_err());
+        assert!(Object::Char('c').to_json().is_err());
+    }
+}
@@ -1,9 +1,21 @@
 use std::{error::Error, fmt};
 
+use crate::{bigint::BigInt, rational::Rational};
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Integer(i64),
+    BigInt(BigInt),
+    Rational(Rational),
+    Float(f64),
+    Bool(bool),
     Symbol(String),
+    Keyword(String),
+    String(String),
+    Char(char),
+    DatumComment,
+    Quote,
+    VectorStart,
     LParen,
     RParen,
 }
@@ -12,16 +24,69 @@ impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Token::Integer(n) => write!(f, "{}", n),
+            Token::BigInt(n) => write!(f, "{}", n),
+            Token::Rational(n) => write!(f, "{}", n),
+            Token::Float(n) => write!(f, "{}", n),
+            Token::Bool(b) => write!(f, "{}", if *b { "#t" } else { "#f" }),
             Token::Symbol(s) => write!(f, "{}", s),
+            Token::Keyword(s) => write!(f, ":{}", s),
+            Token::String(s) => write!(f, "\"{}\"", s.escape_debug()),
+            Token::Char(c) => write!(f, "#\\{}", c),
+            Token::DatumComment => write!(f, "#;"),
+            Token::Quote => write!(f, "'"),
+            Token::VectorStart => write!(f, "#("),
             Token::LParen => write!(f, "("),
             Token::RParen => write!(f, ")"),
         }
     }
 }
 
+/// Where a token or datum sits in the source: the 1-based line and column of
+/// its first character and of the character just past its end. Columns count
+/// characters, not bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl Span {
+    /// The span from the start of `self` to the end of `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end_line: other.end_line,
+            end_column: other.end_column,
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
 #[derive(Debug)]
 pub struct TokenError {
     ch: char,
+    span: Span,
+}
+
+impl TokenError {
+    fn new(ch: char) -> Self {
+        TokenError {
+            ch,
+            span: Span::default(),
+        }
+    }
+
+    /// The span of the token that could not be read.
+    pub fn span(&self) -> Span {
+        self.span
+    }
 }
 
 impl Error for TokenError {}
@@ -33,21 +98,306 @@ impl fmt::Display for TokenError {
 }
 
 pub fn tokenize(program: &str) -> Result<Vec<Token>, TokenError> {
-    let program = program.replace("(", " ( ").replace(")", " ) ");
-    let words = program.split_whitespace();
-
-    let tokens: Result<Vec<Token>, TokenError> = words
+    Ok(tokenize_spanned(program)?
         .into_iter()
-        .map(|word| match word {
-            "(" => Ok(Token::LParen),
-            ")" => Ok(Token::RParen),
-            _ => match word.parse::<i64>() {
-                Ok(n) => Ok(Token::Integer(n)),
-                Err(_) => Ok(Token::Symbol(word.to_string())),
-            },
-        })
-        .collect();
-    tokens
+        .map(|(token, _)| token)
+        .collect())
+}
+
+/// Like `tokenize`, but pairs each token with its span in `program`.
+pub fn tokenize_spanned(program: &str) -> Result<Vec<(Token, Span)>, TokenError> {
+    let mut tokens = Vec::new();
+    let mut chars = Reader::new(program);
+    while let Some(token) = next_token(&mut chars)? {
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Returns the byte length of the shortest prefix of `source` holding one
+/// complete datum, or `None` if the datum is not finished yet. A datum that
+/// runs to the very end of `source` counts as finished.
+pub fn datum_end(source: &str) -> Result<Option<usize>, TokenError> {
+    let mut chars = Reader::new(source);
+    let mut depth = 0;
+    let mut needed = 1;
+    while let Some((token, _)) = next_token(&mut chars)? {
+        match token {
+            Token::LParen | Token::VectorStart => depth += 1,
+            Token::RParen => depth -= 1,
+            Token::Quote => continue,
+            // A datum comment at the top level hides the next datum.
+            Token::DatumComment if depth == 0 => {
+                needed += 1;
+                continue;
+            }
+            _ => {}
+        }
+        if depth <= 0 {
+            needed -= 1;
+            if needed == 0 {
+                return Ok(Some(source.len() - chars.rest.len()));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// A cursor over the source text that keeps track of its line and column.
+#[derive(Clone)]
+struct Reader<'a> {
+    rest: &'a str,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(source: &'a str) -> Self {
+        Reader {
+            rest: source,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest.chars().next()
+    }
+
+    /// The span from `start`, an earlier `(line, column)`, to the cursor.
+    fn span_from(&self, (line, column): (usize, usize)) -> Span {
+        Span {
+            line,
+            column,
+            end_line: self.line,
+            end_column: self.column,
+        }
+    }
+}
+
+impl Iterator for Reader<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.rest = &self.rest[c.len_utf8()..];
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+}
+
+fn next_token(chars: &mut Reader) -> Result<Option<(Token, Span)>, TokenError> {
+    while let Some(ch) = chars.peek() {
+        let start = (chars.line, chars.column);
+        let token = read_token(ch, chars).map_err(|err| TokenError {
+            span: chars.span_from(start),
+            ..err
+        })?;
+        if let Some(token) = token {
+            return Ok(Some((token, chars.span_from(start))));
+        }
+    }
+    Ok(None)
+}
+
+/// Reads the token starting with `ch`, or skips whitespace or a comment and
+/// returns `None`.
+fn read_token(ch: char, chars: &mut Reader) -> Result<Option<Token>, TokenError> {
+    let token = match ch {
+        '(' => {
+            chars.next();
+            Token::LParen
+        }
+        ')' => {
+            chars.next();
+            Token::RParen
+        }
+        '"' => {
+            chars.next();
+            tokenize_string(chars)?
+        }
+        '\'' => {
+            chars.next();
+            Token::Quote
+        }
+        '#' if chars.clone().nth(1) == Some('\\') => {
+            chars.next();
+            chars.next();
+            tokenize_char(chars)?
+        }
+        ';' => {
+            chars.by_ref().take_while(|&c| c != '\n').for_each(drop);
+            return Ok(None);
+        }
+        '#' if chars.clone().nth(1) == Some('|') => {
+            chars.next();
+            chars.next();
+            skip_block_comment(chars)?;
+            return Ok(None);
+        }
+        '#' if chars.clone().nth(1) == Some('(') => {
+            chars.next();
+            chars.next();
+            Token::VectorStart
+        }
+        '#' if chars.clone().nth(1) == Some(';') => {
+            chars.next();
+            chars.next();
+            Token::DatumComment
+        }
+        c if c.is_whitespace() => {
+            chars.next();
+            return Ok(None);
+        }
+        _ => tokenize_word(&read_word(chars)),
+    };
+    Ok(Some(token))
+}
+
+fn read_word(chars: &mut Reader) -> String {
+    let mut word = String::new();
+    while let Some(c) = chars.peek() {
+        if c.is_whitespace() || matches!(c, '(' | ')' | '"' | ';' | '\'') {
+            break;
+        }
+        word.push(c);
+        chars.next();
+    }
+    word
+}
+
+fn tokenize_word(word: &str) -> Token {
+    match word {
+        "#t" | "true" => return Token::Bool(true),
+        "#f" | "false" => return Token::Bool(false),
+        _ => {}
+    }
+    if let Some(name) = word.strip_prefix(':')
+        && !name.is_empty()
+    {
+        return Token::Keyword(name.to_string());
+    }
+    if let Some(token) = tokenize_radix_integer(word) {
+        return token;
+    }
+    if let Ok(n) = word.parse::<i64>() {
+        return Token::Integer(n);
+    }
+    if let Some(n) = BigInt::parse(word) {
+        return Token::BigInt(n);
+    }
+    if let Some(n) = Rational::parse(word) {
+        // `Rational::new` already reduces; `4/2` is just an integer.
+        return match (n.is_integer(), n.numer().to_i64()) {
+            (true, Some(i)) => Token::Integer(i),
+            (true, None) => Token::BigInt(n.numer().clone()),
+            (false, _) => Token::Rational(n),
+        };
+    }
+    if word.contains(|c: char| c.is_ascii_digit())
+        && let Ok(n) = word.parse::<f64>()
+    {
+        return Token::Float(n);
+    }
+    Token::Symbol(word.to_string())
+}
+
+/// Parses `#x1F`, `#o17`, `#b1010` and their `0x`/`0o`/`0b` spellings.
+fn tokenize_radix_integer(word: &str) -> Option<Token> {
+    let (radix, digits) = match word.get(..2)? {
+        "#x" | "#X" | "0x" | "0X" => (16, &word[2..]),
+        "#o" | "#O" | "0o" | "0O" => (8, &word[2..]),
+        "#b" | "#B" | "0b" | "0B" => (2, &word[2..]),
+        _ => return None,
+    };
+
+    match i64::from_str_radix(digits, radix) {
+        Ok(n) => Some(Token::Integer(n)),
+        Err(_) => BigInt::parse_radix(digits, radix).map(Token::BigInt),
+    }
+}
+
+/// Skips a `#| ... |#` comment whose opening delimiter was already consumed.
+/// Block comments nest.
+fn skip_block_comment(chars: &mut Reader) -> Result<(), TokenError> {
+    let mut depth = 1;
+    while depth > 0 {
+        match chars.next() {
+            Some('|') if chars.peek() == Some('#') => {
+                chars.next();
+                depth -= 1;
+            }
+            Some('#') if chars.peek() == Some('|') => {
+                chars.next();
+                depth += 1;
+            }
+            Some(_) => {}
+            None => return Err(TokenError::new('#')),
+        }
+    }
+    Ok(())
+}
+
+fn tokenize_char(chars: &mut Reader) -> Result<Token, TokenError> {
+    let first = chars.next().ok_or(TokenError::new('\\'))?;
+    let mut name = first.to_string();
+    if first.is_alphanumeric() {
+        name.push_str(&read_word(chars));
+    }
+
+    let mut iter = name.chars();
+    if let (Some(c), None) = (iter.next(), iter.next()) {
+        return Ok(Token::Char(c));
+    }
+    match name.as_str() {
+        "space" => Ok(Token::Char(' ')),
+        "newline" => Ok(Token::Char('\n')),
+        "tab" => Ok(Token::Char('\t')),
+        "return" => Ok(Token::Char('\r')),
+        "nul" => Ok(Token::Char('\0')),
+        _ => Err(TokenError::new(first)),
+    }
+}
+
+fn tokenize_string(chars: &mut Reader) -> Result<Token, TokenError> {
+    let mut s = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Ok(Token::String(s)),
+            '\\' => s.push(tokenize_escape(chars)?),
+            _ => s.push(c),
+        }
+    }
+    Err(TokenError::new('"'))
+}
+
+fn tokenize_escape(chars: &mut Reader) -> Result<char, TokenError> {
+    match chars.next() {
+        Some('n') => Ok('\n'),
+        Some('t') => Ok('\t'),
+        Some('r') => Ok('\r'),
+        Some('0') => Ok('\0'),
+        Some('"') => Ok('"'),
+        Some('\'') => Ok('\''),
+        Some('\\') => Ok('\\'),
+        Some('u') => {
+            if chars.next() != Some('{') {
+                return Err(TokenError::new('u'));
+            }
+            let hex: String = chars.by_ref().take_while(|&c| c != '}').collect();
+            u32::from_str_radix(&hex, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or(TokenError::new('u'))
+        }
+        Some(c) => Err(TokenError::new(c)),
+        None => Err(TokenError::new('\\')),
+    }
 }
 
 #[cfg(test)]
@@ -68,4 +418,226 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_tokenize_float() {
+        let tokens = tokenize("(+ 1.5 -2.5)").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::Symbol("+".to_string()),
+                Token::Float(1.5),
+                Token::Float(-2.5),
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_string() {
+        let tokens = tokenize("(define s \"hello world\")").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::Symbol("define".to_string()),
+                Token::Symbol("s".to_string()),
+                Token::String("hello world".to_string()),
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_char() {
+        let tokens = tokenize("(#\\a #\\space #\\( #\\newline)").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::Char('a'),
+                Token::Char(' '),
+                Token::Char('('),
+                Token::Char('\n'),
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_bool() {
+        let tokens = tokenize("(#t #f true false)").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::Bool(true),
+                Token::Bool(false),
+                Token::Bool(true),
+                Token::Bool(false),
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_line_comment() {
+        let tokens = tokenize("; comment\n(+ 1 2) ; trailing\n;").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::Symbol("+".to_string()),
+                Token::Integer(1),
+                Token::Integer(2),
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_block_comment() {
+        let tokens = tokenize("(1 #| outer #| inner |# still |# #;2 3)").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::Integer(1),
+                Token::DatumComment,
+                Token::Integer(2),
+                Token::Integer(3),
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_radix_integer() {
+        let tokens = tokenize("(#x1F #o17 #b1010 0xff #x-a)").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::Integer(31),
+                Token::Integer(15),
+                Token::Integer(10),
+                Token::Integer(255),
+                Token::Integer(-10),
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_rational() {
+        let tokens = tokenize("(4/6 -6/4 2/2 10/-5 1/0)").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::Rational(Rational::parse("2/3").unwrap()),
+                Token::Rational(Rational::parse("-3/2").unwrap()),
+                Token::Integer(1),
+                Token::Integer(-2),
+                Token::Symbol("1/0".to_string()),
+                Token::RParen,
+            ]
+        );
+        assert_eq!(
+            tokenize("100000000000000000000/1").unwrap(),
+            vec![Token::BigInt(
+                BigInt::parse("100000000000000000000").unwrap()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_string_escapes() {
+        let tokens = tokenize(r#""a\n\t\"b\\ \u{3bb}""#).unwrap();
+        assert_eq!(tokens, vec![Token::String("a\n\t\"b\\ λ".to_string())]);
+    }
+
+    #[test]
+    fn test_tokenize_quote() {
+        let tokens = tokenize("'(a 'b)").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Quote,
+                Token::LParen,
+                Token::Symbol("a".to_string()),
+                Token::Quote,
+                Token::Symbol("b".to_string()),
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_keyword() {
+        let tokens = tokenize("(f :width 80 :)").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::Symbol("f".to_string()),
+                Token::Keyword("width".to_string()),
+                Token::Integer(80),
+                Token::Symbol(":".to_string()),
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_vector() {
+        let tokens = tokenize("#(1 #t)").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::VectorStart,
+                Token::Integer(1),
+                Token::Bool(true),
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_spanned() {
+        let span = |line, column, end_line, end_column| Span {
+            line,
+            column,
+            end_line,
+            end_column,
+        };
+        let tokens = tokenize_spanned("(f \"é\"\n  'x) ; done").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                (Token::LParen, span(1, 1, 1, 2)),
+                (Token::Symbol("f".to_string()), span(1, 2, 1, 3)),
+                (Token::String("é".to_string()), span(1, 4, 1, 7)),
+                (Token::Quote, span(2, 3, 2, 4)),
+                (Token::Symbol("x".to_string()), span(2, 4, 2, 5)),
+                (Token::RParen, span(2, 5, 2, 6)),
+            ]
+        );
+        assert_eq!(
+            span(2, 1, 2, 6),
+            tokenize_spanned("1\n#\\bad").unwrap_err().span()
+        );
+    }
+
+    #[test]
+    fn test_datum_end() {
+        assert_eq!(Some(5), datum_end("(a b) c").unwrap());
+        assert_eq!(Some(5), datum_end("  '42 x").unwrap());
+        assert_eq!(Some(10), datum_end("#;(x) \"é\" y").unwrap());
+        assert_eq!(Some(3), datum_end("abc\n").unwrap());
+        assert_eq!(None, datum_end("(a (b)").unwrap());
+        assert_eq!(None, datum_end("; only a comment\n").unwrap());
+        assert!(datum_end("\"open").is_err());
+    }
 }
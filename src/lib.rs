@@ -0,0 +1,74 @@
+//! A Lisp interpreter that can be embedded in other programs.
+//!
+//! ```
+//! use lisp_rs::{Interpreter, parser::Object};
+//!
+//! let mut interp = Interpreter::new();
+//! let val = interp.eval_str("(define (sq x) (* x x)) (sq 12)").unwrap();
+//! assert_eq!(Object::Integer(144), val);
+//! ```
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    eval::{Env, eval},
+    lexer::tokenize,
+    parser::{Object, parse_program},
+};
+
+mod bigint;
+mod csv;
+mod diagnostic;
+mod error;
+pub mod eval;
+mod interpreter;
+#[cfg(feature = "json")]
+mod json;
+pub mod lexer;
+pub mod parser;
+mod plugin;
+mod rational;
+mod regex;
+mod sendable;
+#[cfg(feature = "serde")]
+mod serialize;
+mod syntax_rules;
+
+pub use diagnostic::Diagnostic;
+pub use error::{LispError, Traced};
+pub use interpreter::{Interpreter, Procedure};
+pub use plugin::{PLUGIN_ENTRY, Plugin, PluginEntry};
+
+/// Evaluates every form in `src` in `env` and returns the value of the last
+/// one, or `Void` if there are none.
+pub fn eval_str(src: &str, env: &mut Rc<RefCell<Env>>) -> Result<Object, LispError> {
+    let mut tokens = tokenize(src).map_err(|e| LispError::ParseError(e.to_string()))?;
+    let program = parse_program(&mut tokens).map_err(|e| LispError::ParseError(e.to_string()))?;
+    program
+        .iter()
+        .try_fold(Object::Void, |_, form| eval(form, env))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval_str() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        assert_eq!(Ok(Object::Void), eval_str("", &mut env));
+        assert_eq!(
+            Ok(Object::Integer(3)),
+            eval_str("(define x 1) (define y 2) (+ x y)", &mut env)
+        );
+        assert_eq!(Some(Object::Integer(1)), env.borrow().get("x"));
+        assert!(matches!(
+            eval_str("(+ x y))", &mut env),
+            Err(LispError::ParseError(_))
+        ));
+        assert!(matches!(
+            eval_str("(car '())", &mut env).unwrap_err().cause(),
+            LispError::TypeMismatch { .. }
+        ));
+    }
+}
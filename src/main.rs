@@ -1,36 +1,93 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{fs, process, time::Instant};
 
 use linefeed::{Interface, ReadResult};
-
-use crate::{
-    eval::{Env, eval},
-    lexer::tokenize,
-    parser::parse,
+use lisp_rs::{
+    Diagnostic, Interpreter, LispError,
+    eval::{next_timer_deadline, run_due_timers, run_timers},
+    parser::Object,
 };
 
-mod eval;
-mod lexer;
-mod parser;
-
 const PROMPT: &str = "lisp-rs> ";
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut interp = Interpreter::new();
+
+    // `lisp-rs script.lisp args...` runs the script with `*args*` bound to
+    // the arguments following it.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (script, script_args) = match args.split_first() {
+        Some((script, rest)) => (Some(script), rest),
+        None => (None, &[][..]),
+    };
+    interp.set_global(
+        "*args*",
+        Object::List(script_args.iter().cloned().map(Object::String).collect()),
+    );
+    if let Some(script) = script {
+        let source = fs::read_to_string(script).unwrap_or_else(|e| {
+            let error = LispError::FileError {
+                path: script.clone(),
+                reason: e.to_string(),
+            };
+            fail(&Diagnostic::from(error), "", script)
+        });
+        if let Err(diagnostic) = interp.eval_source(&source) {
+            fail(&diagnostic, &source, script);
+        }
+        if let Err(err) = run_timers() {
+            fail(&Diagnostic::from(err), "", "<timer>");
+        }
+        return Ok(());
+    }
+
     let reader = Interface::new(PROMPT).unwrap();
     reader.set_prompt(PROMPT.to_string().as_ref()).unwrap();
 
-    let mut env = Rc::new(RefCell::new(Env::new()));
-
-    while let ReadResult::Input(input) = reader.read_line().unwrap() {
+    loop {
+        // Wait for input only until the next timer is due.
+        let timeout = next_timer_deadline()
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        let input = match reader.read_line_step(timeout)? {
+            Some(ReadResult::Input(input)) => input,
+            Some(_) => break,
+            None => {
+                // The wait may end slightly early; only disturb the prompt
+                // once a timer is really due.
+                if next_timer_deadline().is_some_and(|deadline| deadline <= Instant::now()) {
+                    // Erase the prompt while callbacks print; it is redrawn
+                    // when the writer is dropped.
+                    let mut writer = reader.lock_writer_erase()?;
+                    if let Err(err) = run_due_timers() {
+                        write!(writer, "{}", Diagnostic::from(err).render("", "<timer>"))?;
+                    }
+                }
+                continue;
+            }
+        };
         if input.eq("exit") {
             break;
         }
 
-        let mut tokens = tokenize(input.as_ref())?;
-        let ast = parse(&mut tokens)?;
-        let val = eval(&ast, &mut env)?;
-        println!("{}", val);
+        // Report errors and carry on; the session keeps its definitions.
+        match interp.eval_source(&input) {
+            // Side-effecting forms such as `display` return Void; don't echo it.
+            Ok(Object::Void) => {}
+            Ok(val) => println!("{}", val),
+            Err(diagnostic) => report(&diagnostic, &input, "<repl>"),
+        }
     }
 
     println!("Good bye");
     Ok(())
 }
+
+/// Prints an error in `source`, read from `origin`.
+fn report(diagnostic: &Diagnostic, source: &str, origin: &str) {
+    eprint!("{}", diagnostic.render(source, origin));
+}
+
+/// Reports an error in `source`, read from `origin`, and exits.
+fn fail(diagnostic: &Diagnostic, source: &str, origin: &str) -> ! {
+    report(diagnostic, source, origin);
+    process::exit(1)
+}
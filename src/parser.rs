@@ -1,13 +1,41 @@
 use std::{
+    any::Any,
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
     error::Error,
     fmt::{self},
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    rc::Rc,
+    sync::{
+        Arc, Mutex,
+        mpsc::{Receiver, Sender},
+    },
+    thread::JoinHandle,
 };
 
-use crate::lexer::Token;
+use crate::{
+    LispError,
+    bigint::BigInt,
+    eval::Env,
+    lexer::{Span, Token},
+    rational::Rational,
+    sendable::{Sendable, ThreadResult},
+    syntax_rules::SyntaxRules,
+};
 
 #[derive(Debug, PartialEq)]
 pub struct ParseError {
     err: String,
+    span: Option<Span>,
+}
+
+impl ParseError {
+    /// The span of the offending token, or of the opening parenthesis of a
+    /// list left unclosed. `None` if the input ran out elsewhere.
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
 }
 
 impl fmt::Display for ParseError {
@@ -18,31 +46,714 @@ impl fmt::Display for ParseError {
 
 impl Error for ParseError {}
 
+/// A datum read from source together with the spans of text it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned {
+    pub object: Object,
+    pub spans: SpanTree,
+}
+
+impl Spanned {
+    /// The span of the first expression, in reading order, equal to
+    /// `target`.
+    pub fn locate(&self, target: &Object) -> Option<Span> {
+        self.spans
+            .find(&self.object, target)
+            .map(|(_, spans)| spans.span)
+    }
+}
+
+/// The span of a datum. For lists, dotted lists and vectors, `elements`
+/// holds the spans of the items in order, a dotted tail last; atoms have no
+/// elements.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanTree {
+    pub span: Span,
+    pub elements: Vec<SpanTree>,
+}
+
+impl SpanTree {
+    fn atom(span: Span) -> Self {
+        SpanTree {
+            span,
+            elements: Vec::new(),
+        }
+    }
+
+    /// The first expression within `obj`, which these are the spans of,
+    /// equal to `target`, with its spans. Quoted data and vector literals
+    /// are not looked into, as they are never evaluated.
+    pub fn find<'a>(
+        &'a self,
+        obj: &'a Object,
+        target: &Object,
+    ) -> Option<(&'a Object, &'a SpanTree)> {
+        if obj == target {
+            return Some((obj, self));
+        }
+        match obj {
+            Object::List(items) if !matches!(items.first(), Some(Object::Symbol(s)) if s == "quote") => {
+                items
+                    .iter()
+                    .zip(&self.elements)
+                    .find_map(|(item, spans)| spans.find(item, target))
+            }
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Object {
     Void,
+    Eof,
     Integer(i64),
+    BigInt(BigInt),
+    Rational(Rational),
+    Float(f64),
     Bool(bool),
     Symbol(String),
-    Lambda(Vec<String>, Vec<Object>),
+    Keyword(String),
+    String(String),
+    Char(char),
+    Lambda(Rc<Lambda>),
+    CaseLambda(Vec<Rc<Lambda>>),
+    Macro(Rc<Lambda>),
+    Syntax(Rc<SyntaxRules>),
+    Environment(EnvRef),
+    Values(Vec<Object>),
+    Continuation(usize),
+    NativeFn(Rc<NativeFn>),
+    Foreign(Rc<Foreign>),
+    Promise(Rc<RefCell<Promise>>),
+    Condition(Rc<Condition>),
+    Class(Rc<Class>),
+    Instance(Rc<Instance>),
+    Port(Rc<Port>),
+    Thread(Rc<ThreadHandle>),
+    Future(Rc<RefCell<Future>>),
+    /// A timer scheduled by `after` or `every`, by id.
+    Timer(usize),
+    Channel(Arc<Channel>),
+    Atom(Arc<Atom>),
+    Vector(Rc<RefCell<Vec<Object>>>),
+    Bytevector(Rc<RefCell<Vec<u8>>>),
+    HashTable(Rc<RefCell<HashMap<HashKey, (Object, Object)>>>),
+    /// An immutable set, ordered by key so it prints deterministically.
+    Set(Rc<BTreeMap<HashKey, Object>>),
     List(Vec<Object>),
+    /// An improper list `(a b . tail)`: one or more elements chained onto a
+    /// final cdr that is not a list. Proper lists are always `List`.
+    DottedList(Vec<Object>, Box<Object>),
+}
+
+/// The hashable part of an `Object`, used to key hash tables and sets.
+/// Procedures and mutable containers have no key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum HashKey {
+    Integer(i64),
+    BigInt(BigInt),
+    Rational(Rational),
+    Float(u64),
+    Bool(bool),
+    Symbol(String),
+    Keyword(String),
+    String(String),
+    Char(char),
+    List(Vec<HashKey>),
+    DottedList(Vec<HashKey>, Box<HashKey>),
+}
+
+impl Object {
+    /// Whether the object can be applied to arguments.
+    pub fn is_procedure(&self) -> bool {
+        matches!(
+            self,
+            Object::Lambda(_)
+                | Object::CaseLambda(_)
+                | Object::Continuation(_)
+                | Object::NativeFn(_)
+        )
+    }
+
+    /// Returns the key this object hashes as, or `None` if it is unhashable.
+    pub fn hash_key(&self) -> Option<HashKey> {
+        match self {
+            Object::Integer(n) => Some(HashKey::Integer(*n)),
+            Object::BigInt(n) => Some(HashKey::BigInt(n.clone())),
+            Object::Rational(n) => Some(HashKey::Rational(n.clone())),
+            // Normalize -0.0 so it hashes like 0.0, which it equals.
+            Object::Float(n) => Some(HashKey::Float((n + 0.0).to_bits())),
+            Object::Bool(b) => Some(HashKey::Bool(*b)),
+            Object::Symbol(s) => Some(HashKey::Symbol(s.clone())),
+            Object::Keyword(s) => Some(HashKey::Keyword(s.clone())),
+            Object::String(s) => Some(HashKey::String(s.clone())),
+            Object::Char(c) => Some(HashKey::Char(*c)),
+            Object::List(items) => items
+                .iter()
+                .map(Object::hash_key)
+                .collect::<Option<_>>()
+                .map(HashKey::List),
+            Object::DottedList(items, tail) => Some(HashKey::DottedList(
+                items.iter().map(Object::hash_key).collect::<Option<_>>()?,
+                Box::new(tail.hash_key()?),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Formats the object for humans, as `display` prints it: like `Display`
+    /// but with strings and characters written raw, including inside lists.
+    pub fn display_string(&self) -> String {
+        let join = |items: &[Object]| {
+            items
+                .iter()
+                .map(Object::display_string)
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        match self {
+            Object::String(s) => s.clone(),
+            Object::Char(c) => c.to_string(),
+            Object::List(items) => format!("({})", join(items)),
+            Object::DottedList(items, tail) => {
+                format!("({} . {})", join(items), tail.display_string())
+            }
+            Object::Vector(items) => format!("#({})", join(&items.borrow())),
+            Object::Values(vals) => join(vals),
+            _ => self.to_string(),
+        }
+    }
+
+    /// Wraps a Rust value, tagged with `type_name`, for passing through Lisp
+    /// code.
+    pub fn foreign<T: Any>(type_name: &'static str, value: T) -> Object {
+        Object::Foreign(Rc::new(Foreign {
+            type_name,
+            value: Rc::new(value),
+        }))
+    }
+
+    /// Returns the wrapped value if this is a foreign object holding a `T`.
+    pub fn downcast_foreign<T: Any>(&self) -> Option<&T> {
+        match self {
+            Object::Foreign(foreign) => foreign.downcast(),
+            _ => None,
+        }
+    }
+
+    /// Builds the pair `(car . cdr)`.
+    pub fn cons(car: Object, cdr: Object) -> Object {
+        Object::dotted(vec![car], cdr)
+    }
+
+    /// Builds `(items... . tail)`, flattening `tail` into a proper `List` or
+    /// a longer `DottedList` when it is itself a list.
+    pub fn dotted(mut items: Vec<Object>, tail: Object) -> Object {
+        match tail {
+            Object::List(rest) => {
+                items.extend(rest);
+                Object::List(items)
+            }
+            Object::DottedList(rest, tail) => {
+                items.extend(rest);
+                Object::DottedList(items, tail)
+            }
+            tail if items.is_empty() => tail,
+            tail => Object::DottedList(items, Box::new(tail)),
+        }
+    }
+}
+
+impl From<i64> for Object {
+    fn from(n: i64) -> Self {
+        Object::Integer(n)
+    }
+}
+
+impl From<f64> for Object {
+    fn from(n: f64) -> Self {
+        Object::Float(n)
+    }
+}
+
+impl From<bool> for Object {
+    fn from(b: bool) -> Self {
+        Object::Bool(b)
+    }
+}
+
+impl From<&str> for Object {
+    fn from(s: &str) -> Self {
+        Object::String(s.to_string())
+    }
+}
+
+impl From<String> for Object {
+    fn from(s: String) -> Self {
+        Object::String(s)
+    }
+}
+
+impl From<Vec<Object>> for Object {
+    fn from(items: Vec<Object>) -> Self {
+        Object::List(items)
+    }
+}
+
+impl TryFrom<Object> for i64 {
+    type Error = LispError;
+
+    fn try_from(obj: Object) -> Result<Self, Self::Error> {
+        match obj {
+            Object::Integer(n) => Ok(n),
+            _ => Err(LispError::Eval(format!("not an integer: {}", obj))),
+        }
+    }
+}
+
+/// Integers are converted too, since Lisp code freely mixes them with floats.
+impl TryFrom<Object> for f64 {
+    type Error = LispError;
+
+    fn try_from(obj: Object) -> Result<Self, Self::Error> {
+        match obj {
+            Object::Float(n) => Ok(n),
+            Object::Integer(n) => Ok(n as f64),
+            _ => Err(LispError::Eval(format!("not a number: {}", obj))),
+        }
+    }
+}
+
+impl TryFrom<Object> for bool {
+    type Error = LispError;
+
+    fn try_from(obj: Object) -> Result<Self, Self::Error> {
+        match obj {
+            Object::Bool(b) => Ok(b),
+            _ => Err(LispError::Eval(format!("not a boolean: {}", obj))),
+        }
+    }
+}
+
+impl TryFrom<Object> for String {
+    type Error = LispError;
+
+    fn try_from(obj: Object) -> Result<Self, Self::Error> {
+        match obj {
+            Object::String(s) => Ok(s),
+            _ => Err(LispError::Eval(format!("not a string: {}", obj))),
+        }
+    }
+}
+
+impl TryFrom<Object> for Vec<Object> {
+    type Error = LispError;
+
+    fn try_from(obj: Object) -> Result<Self, Self::Error> {
+        match obj {
+            Object::List(items) => Ok(items),
+            _ => Err(LispError::Eval(format!("not a list: {}", obj))),
+        }
+    }
+}
+
+/// A user-defined procedure together with the environment it was created in.
+pub struct Lambda {
+    pub params: Vec<String>,
+    pub rest: Option<String>,
+    /// Keyword parameters declared after `#!key`, with their default
+    /// expressions.
+    pub keys: Vec<(String, Object)>,
+    pub body: Vec<Object>,
+    pub env: Rc<RefCell<Env>>,
+}
+
+impl PartialEq for Lambda {
+    fn eq(&self, other: &Self) -> bool {
+        self.params == other.params
+            && self.rest == other.rest
+            && self.keys == other.keys
+            && self.body == other.body
+            && Rc::ptr_eq(&self.env, &other.env)
+    }
+}
+
+// The captured environment usually contains the lambda itself, so it is left
+// out to avoid infinite recursion.
+impl fmt::Debug for Lambda {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lambda")
+            .field("params", &self.params)
+            .field("rest", &self.rest)
+            .field("keys", &self.keys)
+            .field("body", &self.body)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A first-class environment, as returned by `the-environment`. Compared by
+/// identity, and not printed in full since it usually contains itself.
+#[derive(Clone)]
+pub struct EnvRef(pub Rc<RefCell<Env>>);
+
+impl PartialEq for EnvRef {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for EnvRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "EnvRef({:p})", Rc::as_ptr(&self.0))
+    }
+}
+
+/// A procedure implemented in Rust, bound with `Env::register_native`. It
+/// receives its arguments already evaluated.
+pub struct NativeFn {
+    pub name: String,
+    pub func: Box<NativeFunc>,
+}
+
+pub type NativeFunc = dyn Fn(&[Object]) -> Result<Object, LispError>;
+
+impl fmt::Debug for NativeFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NativeFn({})", self.name)
+    }
+}
+
+impl PartialEq for NativeFn {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// A Rust value passed through Lisp code, such as a database handle.
+/// `type-of` reports `type_name`; only native functions can look inside.
+pub struct Foreign {
+    pub type_name: &'static str,
+    pub value: Rc<dyn Any>,
+}
+
+impl Foreign {
+    /// Returns the value if it is a `T`.
+    pub fn downcast<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+}
+
+impl fmt::Debug for Foreign {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Foreign({})", self.type_name)
+    }
+}
+
+impl PartialEq for Foreign {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// The state of a `delay`ed expression: unevaluated with the environment to
+/// evaluate it in, or memoized after the first `force`.
+pub enum Promise {
+    Delayed(Object, Rc<RefCell<Env>>),
+    Forced(Object),
+}
+
+impl PartialEq for Promise {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Promise::Delayed(a, a_env), Promise::Delayed(b, b_env)) => {
+                a == b && Rc::ptr_eq(a_env, b_env)
+            }
+            (Promise::Forced(a), Promise::Forced(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for Promise {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Promise::Delayed(expr, _) => f.debug_tuple("Delayed").field(expr).finish(),
+            Promise::Forced(val) => f.debug_tuple("Forced").field(val).finish(),
+        }
+    }
+}
+
+/// A raised error: its kind (`error`, `arity-error`, ...), message and
+/// irritants, plus the form that raised it when known.
+#[derive(Debug, PartialEq)]
+pub struct Condition {
+    pub kind: String,
+    pub message: String,
+    pub irritants: Vec<Object>,
+    pub location: Option<Object>,
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        for irritant in &self.irritants {
+            write!(f, " {}", irritant)?;
+        }
+        Ok(())
+    }
+}
+
+/// A class created by `make-class`: its own slot names and methods, with
+/// everything else inherited from `parent`.
+#[derive(Debug, PartialEq)]
+pub struct Class {
+    pub name: String,
+    pub parent: Option<Rc<Class>>,
+    pub fields: Vec<String>,
+    pub methods: HashMap<String, Object>,
+}
+
+impl Class {
+    /// Slot names including inherited ones, parent slots first.
+    pub fn all_fields(&self) -> Vec<String> {
+        let mut fields = match &self.parent {
+            Some(parent) => parent.all_fields(),
+            None => Vec::new(),
+        };
+        fields.extend(self.fields.iter().cloned());
+        fields
+    }
+
+    /// Finds a method on this class or the nearest ancestor defining it.
+    pub fn method(&self, name: &str) -> Option<Object> {
+        match self.methods.get(name) {
+            Some(method) => Some(method.clone()),
+            None => self.parent.as_ref().and_then(|parent| parent.method(name)),
+        }
+    }
+
+    pub fn is_subclass_of(&self, other: &Class) -> bool {
+        std::ptr::eq(self, other)
+            || self
+                .parent
+                .as_ref()
+                .is_some_and(|parent| parent.is_subclass_of(other))
+    }
+}
+
+/// An object created by `make-instance`. Instances have identity, so two
+/// instances are only equal if they are the same object.
+#[derive(Debug)]
+pub struct Instance {
+    pub class: Rc<Class>,
+    pub slots: RefCell<HashMap<String, Object>>,
+}
+
+impl PartialEq for Instance {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// An I/O port. Ports have identity and are closed in place, after which
+/// reads and writes through them fail.
+pub struct Port {
+    pub name: String,
+    pub state: RefCell<PortState>,
+}
+
+pub enum PortState {
+    Input(Box<dyn BufRead>),
+    Output(Box<dyn Write>),
+    /// An output port accumulating into memory, read by `get-output-string`.
+    OutputString(String),
+    /// A connected TCP socket, both readable and writable.
+    Socket(BufReader<TcpStream>),
+    /// A listening TCP socket whose connections are taken by `tcp-accept`.
+    Listener(TcpListener),
+    Closed,
+}
+
+impl Port {
+    pub fn new(name: &str, state: PortState) -> Self {
+        Port {
+            name: name.to_string(),
+            state: RefCell::new(state),
+        }
+    }
+}
+
+impl fmt::Debug for Port {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Port({})", self.name)
+    }
+}
+
+impl PartialEq for Port {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// A thread started by `spawn`. It can be joined once, which takes its
+/// result out of the handle.
+pub struct ThreadHandle {
+    pub handle: RefCell<Option<JoinHandle<ThreadResult>>>,
+}
+
+impl fmt::Debug for ThreadHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Thread")
+    }
+}
+
+impl PartialEq for ThreadHandle {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// The state of a `future`: running on a worker thread, or memoized after the
+/// first `await`.
+pub enum Future {
+    Running(JoinHandle<ThreadResult>),
+    Done(Result<Object, LispError>),
+}
+
+impl fmt::Debug for Future {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Future::Running(_) => write!(f, "Running"),
+            Future::Done(result) => f.debug_tuple("Done").field(result).finish(),
+        }
+    }
+}
+
+impl PartialEq for Future {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// A queue created by `make-channel`. Values are copied into it, and the
+/// channel itself crosses into spawned threads by reference, so threads can
+/// use it to talk to each other.
+pub struct Channel {
+    pub sender: Sender<Sendable>,
+    pub receiver: Mutex<Receiver<Sendable>>,
+}
+
+impl fmt::Debug for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Channel")
+    }
+}
+
+impl PartialEq for Channel {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// A mutable cell created by `atom`, shared by reference with spawned
+/// threads. `version` counts updates so `swap!` can detect a concurrent
+/// change and retry.
+pub struct Atom {
+    pub state: Mutex<(u64, Sendable)>,
+}
+
+impl fmt::Debug for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Atom")
+    }
+}
+
+impl PartialEq for Atom {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Display for Lambda {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(")?;
+        for param in &self.params {
+            write!(f, "{} ", param)?;
+        }
+        if !self.keys.is_empty() {
+            write!(f, "#!key ")?;
+            for (key, default) in &self.keys {
+                write!(f, "({} {}) ", key, default)?;
+            }
+        }
+        if let Some(rest) = &self.rest {
+            write!(f, ". {} ", rest)?;
+        }
+        write!(f, ")")?;
+        for expr in &self.body {
+            write!(f, " {}", expr)?;
+        }
+        Ok(())
+    }
 }
 
 impl fmt::Display for Object {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Object::Void => write!(f, "Void"),
+            Object::Eof => write!(f, "Eof"),
             Object::Integer(n) => write!(f, "{}", n),
+            Object::BigInt(n) => write!(f, "{}", n),
+            Object::Rational(n) => write!(f, "{}", n),
+            Object::Float(n) => write!(f, "{:?}", n),
             Object::Bool(b) => write!(f, "{}", b),
             Object::Symbol(s) => write!(f, "{}", s),
-            Object::Lambda(params, body) => {
-                write!(f, "Lambda(")?;
-                for param in params {
-                    write!(f, "{} ", param)?;
+            Object::Keyword(s) => write!(f, ":{}", s),
+            Object::String(s) => write!(f, "\"{}\"", s.escape_debug()),
+            Object::Char(c) => match c {
+                ' ' => write!(f, "#\\space"),
+                '\n' => write!(f, "#\\newline"),
+                '\t' => write!(f, "#\\tab"),
+                '\r' => write!(f, "#\\return"),
+                '\0' => write!(f, "#\\nul"),
+                _ => write!(f, "#\\{}", c),
+            },
+            Object::Lambda(lambda) => write!(f, "Lambda{}", lambda),
+            Object::CaseLambda(clauses) => {
+                write!(f, "CaseLambda(")?;
+                for (i, lambda) in clauses.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "({})", lambda)?;
                 }
-                write!(f, ")")?;
-                for expr in body {
-                    write!(f, " {}", expr)?;
+                write!(f, ")")
+            }
+            Object::Macro(lambda) => write!(f, "Macro{}", lambda),
+            Object::Syntax(_) => write!(f, "Syntax"),
+            Object::Environment(_) => write!(f, "Environment"),
+            Object::Continuation(_) => write!(f, "Continuation"),
+            Object::NativeFn(native) => write!(f, "NativeFn({})", native.name),
+            Object::Foreign(foreign) => write!(f, "Foreign({})", foreign.type_name),
+            Object::Promise(_) => write!(f, "Promise"),
+            Object::Class(class) => write!(f, "Class({})", class.name),
+            Object::Instance(instance) => write!(f, "Instance({})", instance.class.name),
+            Object::Port(port) => write!(f, "Port({})", port.name),
+            Object::Thread(_) => write!(f, "Thread"),
+            Object::Future(_) => write!(f, "Future"),
+            Object::Timer(_) => write!(f, "Timer"),
+            Object::Channel(_) => write!(f, "Channel"),
+            Object::Atom(_) => write!(f, "Atom"),
+            Object::Condition(condition) => {
+                write!(f, "Condition({}: {})", condition.kind, condition)
+            }
+            Object::Values(vals) => {
+                for (i, val) in vals.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", val)?;
                 }
                 Ok(())
             }
@@ -56,53 +767,216 @@ impl fmt::Display for Object {
                 }
                 write!(f, ")")
             }
+            Object::HashTable(table) => write!(f, "HashTable({})", table.borrow().len()),
+            Object::Set(items) => {
+                write!(f, "Set(")?;
+                for (i, obj) in items.values().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", obj)?;
+                }
+                write!(f, ")")
+            }
+            Object::Bytevector(bytes) => {
+                write!(f, "#u8(")?;
+                for (i, byte) in bytes.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", byte)?;
+                }
+                write!(f, ")")
+            }
+            Object::Vector(items) => {
+                write!(f, "#(")?;
+                for (i, obj) in items.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", obj)?;
+                }
+                write!(f, ")")
+            }
+            Object::DottedList(list, tail) => {
+                write!(f, "(")?;
+                for obj in list {
+                    write!(f, "{} ", obj)?;
+                }
+                write!(f, ". {})", tail)
+            }
         }
     }
 }
 
 pub fn parse(tokens: &mut [Token]) -> Result<Object, ParseError> {
-    let mut rev_tokens = tokens.iter().cloned().rev().collect();
-    parse_list(&mut rev_tokens)
+    let mut rev_tokens = unspanned(tokens);
+    while matches!(rev_tokens.last(), Some((Token::DatumComment, _))) {
+        rev_tokens.pop();
+        parse_datum(&mut rev_tokens)?;
+    }
+    Ok(parse_datum(&mut rev_tokens)?.0)
 }
 
-fn parse_list(tokens: &mut Vec<Token>) -> Result<Object, ParseError> {
-    let token = tokens.pop();
-    if token != Some(Token::LParen) {
-        return Err(ParseError {
-            err: format!("Expected LParen, found {:?}", token),
-        });
+/// Parses every top-level datum in `tokens`, e.g. the contents of a file.
+pub fn parse_program(tokens: &mut [Token]) -> Result<Vec<Object>, ParseError> {
+    let program = parse_spanned(unspanned(tokens))?;
+    Ok(program.into_iter().map(|datum| datum.object).collect())
+}
+
+/// Like `parse_program`, but keeps the span of every datum, as read by
+/// `tokenize_spanned`.
+pub fn parse_program_spanned(tokens: &[(Token, Span)]) -> Result<Vec<Spanned>, ParseError> {
+    parse_spanned(tokens.iter().cloned().rev().collect())
+}
+
+/// Tokens without spans, reversed so the parser can pop them in order.
+fn unspanned(tokens: &[Token]) -> Vec<(Token, Span)> {
+    tokens
+        .iter()
+        .rev()
+        .map(|token| (token.clone(), Span::default()))
+        .collect()
+}
+
+fn parse_spanned(mut rev_tokens: Vec<(Token, Span)>) -> Result<Vec<Spanned>, ParseError> {
+    let mut program = Vec::new();
+    while let Some((token, span)) = rev_tokens.pop() {
+        match token {
+            Token::DatumComment => {
+                parse_datum(&mut rev_tokens)?;
+            }
+            t => {
+                rev_tokens.push((t, span));
+                let (object, spans) = parse_datum(&mut rev_tokens)?;
+                program.push(Spanned { object, spans });
+            }
+        }
     }
+    Ok(program)
+}
 
-    let mut list: Vec<Object> = Vec::new();
-    while !tokens.is_empty() {
-        let token = tokens.pop();
-        if token.is_none() {
+fn parse_list(tokens: &mut Vec<(Token, Span)>) -> Result<(Object, SpanTree), ParseError> {
+    let start = match tokens.pop() {
+        Some((Token::LParen, span)) => span,
+        other => {
+            let span = other.as_ref().map(|(_, span)| *span);
             return Err(ParseError {
-                err: "Insufficient tokens".to_string(),
+                err: format!("Expected LParen, found {:?}", other.map(|(token, _)| token)),
+                span,
             });
         }
+    };
 
-        let t = token.unwrap();
-        match t {
-            Token::Integer(n) => list.push(Object::Integer(n)),
-            Token::Symbol(s) => list.push(Object::Symbol(s)),
-            Token::LParen => {
-                tokens.push(Token::LParen);
-                let sub_list = parse_list(tokens)?;
-                list.push(sub_list);
-            }
+    let mut items = Vec::new();
+    let mut elements: Vec<SpanTree> = Vec::new();
+    while let Some((token, span)) = tokens.pop() {
+        match token {
             Token::RParen => {
-                return Ok(Object::List(list));
+                let span = start.to(span);
+                return Ok((Object::List(items), SpanTree { span, elements }));
+            }
+            Token::DatumComment => {
+                parse_datum(tokens)?;
+            }
+            Token::Symbol(s) if s == "." => {
+                if elements.is_empty() {
+                    return Err(ParseError {
+                        err: "Unexpected dot".to_string(),
+                        span: Some(span),
+                    });
+                }
+                let (tail, tail_spans) = parse_datum(tokens)?;
+                let span = match tokens.pop() {
+                    Some((Token::RParen, span)) => start.to(span),
+                    None => return Err(unclosed_list(start)),
+                    Some((token, span)) => {
+                        return Err(ParseError {
+                            err: format!("Expected RParen after dotted tail, found {}", token),
+                            span: Some(span),
+                        });
+                    }
+                };
+                elements.push(tail_spans);
+                return Ok((Object::dotted(items, tail), SpanTree { span, elements }));
+            }
+            t => {
+                tokens.push((t, span));
+                let (item, spans) = parse_datum(tokens)?;
+                items.push(item);
+                elements.push(spans);
             }
         }
     }
 
-    Ok(Object::List(list))
+    Err(unclosed_list(start))
+}
+
+/// The error for a list opened at `start` whose `)` never came.
+fn unclosed_list(start: Span) -> ParseError {
+    ParseError {
+        err: "Unclosed list".to_string(),
+        span: Some(start),
+    }
+}
+
+fn parse_datum(tokens: &mut Vec<(Token, Span)>) -> Result<(Object, SpanTree), ParseError> {
+    let (token, span) = tokens.pop().ok_or_else(|| ParseError {
+        err: "Insufficient tokens".to_string(),
+        span: None,
+    })?;
+
+    let object = match token {
+        Token::Integer(n) => Object::Integer(n),
+        Token::BigInt(n) => Object::BigInt(n),
+        Token::Rational(n) => Object::Rational(n),
+        Token::Float(n) => Object::Float(n),
+        Token::Bool(b) => Object::Bool(b),
+        Token::Symbol(s) => Object::Symbol(s),
+        Token::Keyword(s) => Object::Keyword(s),
+        Token::String(s) => Object::String(s),
+        Token::Char(c) => Object::Char(c),
+        Token::LParen => {
+            tokens.push((Token::LParen, span));
+            return parse_list(tokens);
+        }
+        Token::VectorStart => {
+            tokens.push((Token::LParen, span));
+            return match parse_list(tokens)? {
+                (Object::List(items), spans) => {
+                    Ok((Object::Vector(Rc::new(RefCell::new(items))), spans))
+                }
+                (_, spans) => Err(ParseError {
+                    err: "Unexpected dot in vector literal".to_string(),
+                    span: Some(spans.span),
+                }),
+            };
+        }
+        Token::DatumComment => {
+            parse_datum(tokens)?;
+            return parse_datum(tokens);
+        }
+        Token::Quote => {
+            let (datum, spans) = parse_datum(tokens)?;
+            let quote = Object::List(vec![Object::Symbol("quote".to_string()), datum]);
+            let elements = vec![SpanTree::atom(span), spans];
+            let span = span.to(elements[1].span);
+            return Ok((quote, SpanTree { span, elements }));
+        }
+        Token::RParen => {
+            return Err(ParseError {
+                err: "Unexpected RParen".to_string(),
+                span: Some(span),
+            });
+        }
+    };
+    Ok((object, SpanTree::atom(span)))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lexer::{tokenize, tokenize_spanned};
 
     #[test]
     fn test_parse() {
@@ -122,4 +996,172 @@ mod tests {
 
         assert_eq!(expected, parse(&mut input));
     }
+
+    #[test]
+    fn test_parse_datum_comment() {
+        let mut input = vec![
+            Token::DatumComment,
+            Token::LParen,
+            Token::Symbol("skipped".to_string()),
+            Token::RParen,
+            Token::LParen,
+            Token::Integer(1),
+            Token::DatumComment,
+            Token::LParen,
+            Token::Integer(2),
+            Token::RParen,
+            Token::Integer(3),
+            Token::RParen,
+        ];
+
+        let expected = Ok(Object::List(vec![Object::Integer(1), Object::Integer(3)]));
+
+        assert_eq!(expected, parse(&mut input));
+    }
+
+    #[test]
+    fn test_parse_quote() {
+        let mut input = vec![
+            Token::Quote,
+            Token::LParen,
+            Token::Integer(1),
+            Token::Integer(2),
+            Token::RParen,
+        ];
+
+        let expected = Ok(Object::List(vec![
+            Object::Symbol("quote".to_string()),
+            Object::List(vec![Object::Integer(1), Object::Integer(2)]),
+        ]));
+
+        assert_eq!(expected, parse(&mut input));
+    }
+
+    #[test]
+    fn test_parse_program() {
+        let mut input = vec![
+            Token::LParen,
+            Token::Symbol("define".to_string()),
+            Token::Symbol("x".to_string()),
+            Token::Integer(1),
+            Token::RParen,
+            Token::DatumComment,
+            Token::Integer(2),
+            Token::Symbol("x".to_string()),
+        ];
+
+        let expected = Ok(vec![
+            Object::List(vec![
+                Object::Symbol("define".to_string()),
+                Object::Symbol("x".to_string()),
+                Object::Integer(1),
+            ]),
+            Object::Symbol("x".to_string()),
+        ]);
+
+        assert_eq!(expected, parse_program(&mut input));
+    }
+
+    #[test]
+    fn test_parse_dotted_list() {
+        let mut input = vec![
+            Token::LParen,
+            Token::Integer(1),
+            Token::Integer(2),
+            Token::Symbol(".".to_string()),
+            Token::Integer(3),
+            Token::RParen,
+        ];
+
+        let expected = Object::DottedList(
+            vec![Object::Integer(1), Object::Integer(2)],
+            Box::new(Object::Integer(3)),
+        );
+        let parsed = parse(&mut input).unwrap();
+        assert_eq!(expected, parsed);
+        assert_eq!("(1 2 . 3)", parsed.to_string());
+
+        let mut input = vec![
+            Token::LParen,
+            Token::Integer(1),
+            Token::Symbol(".".to_string()),
+            Token::LParen,
+            Token::Integer(2),
+            Token::RParen,
+            Token::RParen,
+        ];
+        assert_eq!(
+            Ok(Object::List(vec![Object::Integer(1), Object::Integer(2)])),
+            parse(&mut input)
+        );
+    }
+
+    #[test]
+    fn test_parse_program_spanned() {
+        let tokens = tokenize_spanned("1\n(define (f x)\n  (+ x y))").unwrap();
+        let program = parse_program_spanned(&tokens).unwrap();
+        assert_eq!(2, program.len());
+        assert_eq!(Object::Integer(1), program[0].object);
+
+        let define = &program[1];
+        let span = Span {
+            line: 2,
+            column: 1,
+            end_line: 3,
+            end_column: 11,
+        };
+        assert_eq!(span, define.spans.span);
+        let y = define.locate(&Object::Symbol("y".to_string())).unwrap();
+        assert_eq!((3, 8), (y.line, y.column));
+        let sum = parse(&mut tokenize("(+ x y)").unwrap()).unwrap();
+        assert_eq!(Some(define.spans.elements[2].span), define.locate(&sum));
+        assert_eq!(None, define.locate(&Object::Symbol("z".to_string())));
+
+        let tokens = tokenize_spanned("(car '(x) x)").unwrap();
+        let call = &parse_program_spanned(&tokens).unwrap()[0];
+        let x = call.locate(&Object::Symbol("x".to_string())).unwrap();
+        assert_eq!(11, x.column);
+
+        let err = parse_program_spanned(&tokenize_spanned("(a . )").unwrap()).unwrap_err();
+        assert_eq!(Some(6), err.span().map(|span| span.column));
+
+        for (source, line, column) in [("1\n(define (f x)\n  (+ x 1)", 2, 1), ("1 (a . b", 1, 3)] {
+            let err = parse_program_spanned(&tokenize_spanned(source).unwrap()).unwrap_err();
+            assert_eq!("Parse error: Unclosed list", err.to_string());
+            let span = err.span().unwrap();
+            assert_eq!((line, column), (span.line, span.column));
+        }
+    }
+
+    #[test]
+    fn test_display_string() {
+        let obj = Object::List(vec![
+            Object::String("a b".to_string()),
+            Object::Char('c'),
+            Object::DottedList(
+                vec![Object::Integer(1)],
+                Box::new(Object::String("d".to_string())),
+            ),
+        ]);
+
+        assert_eq!("(a b c (1 . d))", obj.display_string());
+        assert_eq!("(\"a b\" #\\c (1 . \"d\"))", obj.to_string());
+    }
+
+    #[test]
+    fn test_conversions() {
+        let obj = Object::from(vec![Object::from(1), Object::from("a"), Object::from(true)]);
+        assert_eq!("(1 \"a\" true)", obj.to_string());
+
+        let items = Vec::<Object>::try_from(obj).unwrap();
+        assert_eq!(Ok(1), i64::try_from(items[0].clone()));
+        assert_eq!(Ok("a".to_string()), String::try_from(items[1].clone()));
+        assert_eq!(Ok(true), bool::try_from(items[2].clone()));
+        assert_eq!(Ok(1.0), f64::try_from(items[0].clone()));
+        assert_eq!(
+            Err(LispError::Eval("not an integer: \"a\"".to_string())),
+            i64::try_from(items[1].clone())
+        );
+        assert!(Vec::<Object>::try_from(Object::from(2.5)).is_err());
+    }
 }
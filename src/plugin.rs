@@ -0,0 +1,30 @@
+use crate::eval::Env;
+
+/// A set of native procedures shipped separately from the interpreter.
+///
+/// A plugin is either added directly with `Interpreter::add_plugin` or built
+/// as a `cdylib` and loaded with `(load-plugin "libfoo.so")` when the
+/// `plugins` feature is enabled. A dynamic plugin exports its entry point,
+/// of type `PluginEntry`, under the name `lisp_rs_plugin`:
+///
+/// ```ignore
+/// #[unsafe(no_mangle)]
+/// pub fn lisp_rs_plugin() -> Box<dyn lisp_rs::Plugin> {
+///     Box::new(MyPlugin)
+/// }
+/// ```
+///
+/// The entry point uses the Rust ABI, so the plugin must be built with the
+/// same compiler and lisp-rs version as the interpreter loading it.
+pub trait Plugin {
+    fn name(&self) -> &str;
+
+    /// Binds the plugin's procedures, typically with `Env::register_native`.
+    fn register(&self, env: &mut Env);
+}
+
+/// The signature of a dynamic plugin's entry point.
+pub type PluginEntry = fn() -> Box<dyn Plugin>;
+
+/// The symbol a dynamic plugin exports its entry point under.
+pub const PLUGIN_ENTRY: &str = "lisp_rs_plugin";
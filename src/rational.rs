@@ -0,0 +1,116 @@
+use std::{cmp::Ordering, fmt};
+
+use crate::bigint::BigInt;
+
+/// Exact fraction kept in lowest terms with a positive denominator.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Rational {
+    numer: BigInt,
+    denom: BigInt,
+}
+
+impl Rational {
+    /// Builds a reduced fraction. Returns `None` when `denom` is zero.
+    pub fn new(numer: BigInt, denom: BigInt) -> Option<Self> {
+        if denom.is_zero() {
+            return None;
+        }
+        let g = numer.gcd(&denom);
+        let (mut numer, _) = numer.div_rem(&g)?;
+        let (mut denom, _) = denom.div_rem(&g)?;
+        if denom.is_negative() {
+            numer = numer.neg();
+            denom = denom.neg();
+        }
+        Some(Rational { numer, denom })
+    }
+
+    pub fn from_bigint(n: BigInt) -> Self {
+        Rational {
+            numer: n,
+            denom: BigInt::from_i64(1),
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        let (numer, denom) = s.split_once('/')?;
+        Rational::new(BigInt::parse(numer)?, BigInt::parse(denom)?)
+    }
+
+    pub fn numer(&self) -> &BigInt {
+        &self.numer
+    }
+
+    pub fn denom(&self) -> &BigInt {
+        &self.denom
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.denom == BigInt::from_i64(1)
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.numer.to_f64() / self.denom.to_f64()
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        let numer = self
+            .numer
+            .mul(&other.denom)
+            .add(&other.numer.mul(&self.denom));
+        Rational::new(numer, self.denom.mul(&other.denom)).expect("denominator is non-zero")
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        let numer = self
+            .numer
+            .mul(&other.denom)
+            .sub(&other.numer.mul(&self.denom));
+        Rational::new(numer, self.denom.mul(&other.denom)).expect("denominator is non-zero")
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        Rational::new(self.numer.mul(&other.numer), self.denom.mul(&other.denom))
+            .expect("denominator is non-zero")
+    }
+
+    /// Returns `None` on division by zero.
+    pub fn div(&self, other: &Self) -> Option<Self> {
+        Rational::new(self.numer.mul(&other.denom), self.denom.mul(&other.numer))
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.numer
+            .mul(&other.denom)
+            .cmp(&other.numer.mul(&self.denom))
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.numer, self.denom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rational_arithmetic() {
+        let third = Rational::parse("1/3").unwrap();
+        let sixth = Rational::parse("-2/-12").unwrap();
+        assert_eq!("1/6", sixth.to_string());
+        assert_eq!("1/2", third.add(&sixth).to_string());
+        assert_eq!("-1/6", sixth.sub(&third).to_string());
+        assert_eq!(None, third.div(&Rational::from_bigint(BigInt::zero())));
+    }
+}
@@ -0,0 +1,558 @@
+use std::{iter::Peekable, str::Chars};
+
+/// A compiled regular expression, matched by a Pike VM that runs every
+/// alternative in lockstep, so matching takes time linear in the text.
+///
+/// Supports literals, `.`, classes such as `[a-z]` and `[^,]`, the escapes
+/// `\d \w \s \D \W \S \b \B`, anchors `^` and `$`, capturing `(...)` and
+/// non-capturing `(?:...)` groups, alternation `|` and the greedy or lazy
+/// (`?`-suffixed) quantifiers `* + ? {m} {m,} {m,n}`.
+#[derive(Debug)]
+pub struct Regex {
+    program: Vec<Inst>,
+    groups: usize,
+}
+
+/// The span of the whole match followed by the span of each group, as char
+/// indices. Groups that did not take part in the match are `None`.
+pub type Captures = Vec<Option<(usize, usize)>>;
+
+#[derive(Debug)]
+enum Node {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    Start,
+    End,
+    WordBoundary(bool),
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+        greedy: bool,
+    },
+}
+
+#[derive(Debug, Clone)]
+enum ClassItem {
+    Range(char, char),
+    Perl(Perl, bool),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Perl {
+    Digit,
+    Word,
+    Space,
+}
+
+impl Perl {
+    fn matches(self, c: char) -> bool {
+        match self {
+            Perl::Digit => c.is_ascii_digit(),
+            Perl::Word => c.is_alphanumeric() || c == '_',
+            Perl::Space => c.is_whitespace(),
+        }
+    }
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: pattern.chars().peekable(),
+            groups: 0,
+        };
+        let node = parser.parse_alt()?;
+        if let Some(c) = parser.chars.next() {
+            return Err(format!("regex: unexpected {}", c));
+        }
+        let mut program = vec![Inst::Save(0)];
+        compile(&node, &mut program);
+        program.extend([Inst::Save(1), Inst::Match]);
+        Ok(Regex {
+            program,
+            groups: parser.groups,
+        })
+    }
+
+    /// Finds the leftmost match in `text` starting at or after char index
+    /// `start`.
+    pub fn find_at(&self, text: &[char], start: usize) -> Option<Captures> {
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        let mut found = None;
+        for pos in start..=text.len() {
+            // Until a match is found, a thread starting here joins with the
+            // lowest priority, so earlier starts win.
+            if found.is_none() {
+                let slots = vec![None; 2 * (self.groups + 1)];
+                self.add_thread(&mut current, 0, text, pos, slots);
+            }
+            if current.threads.is_empty() && found.is_some() {
+                break;
+            }
+            for (pc, slots) in current.threads.drain(..) {
+                let c = text.get(pos);
+                let advances = match &self.program[pc] {
+                    Inst::Char(expected) => c == Some(expected),
+                    Inst::Any => c.is_some_and(|&c| c != '\n'),
+                    Inst::Class(items, negated) => {
+                        c.is_some_and(|&c| class_matches(items, *negated, c))
+                    }
+                    // Lower-priority threads are cut off by a match.
+                    Inst::Match => {
+                        found = Some(slots);
+                        break;
+                    }
+                    _ => unreachable!("only consuming instructions are queued"),
+                };
+                if advances {
+                    self.add_thread(&mut next, pc + 1, text, pos + 1, slots);
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+        }
+        found.map(|slots| slots.chunks(2).map(|span| span[0].zip(span[1])).collect())
+    }
+
+    /// All non-overlapping matches, left to right.
+    pub fn find_all(&self, text: &[char]) -> Vec<Captures> {
+        let mut matches = Vec::new();
+        let mut start = 0;
+        while start <= text.len() {
+            let Some(caps) = self.find_at(text, start) else {
+                break;
+            };
+            let (begin, end) = caps[0].expect("the whole match is always set");
+            // Step past empty matches so the scan makes progress.
+            start = if end == begin { end + 1 } else { end };
+            matches.push(caps);
+        }
+        matches
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    groups: usize,
+}
+
+impl Parser<'_> {
+    fn parse_alt(&mut self) -> Result<Node, String> {
+        let mut alternatives = vec![self.parse_concat()?];
+        while self.chars.next_if_eq(&'|').is_some() {
+            alternatives.push(self.parse_concat()?);
+        }
+        Ok(match alternatives.len() {
+            1 => alternatives.pop().expect("one alternative"),
+            _ => Node::Alt(alternatives),
+        })
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(&c) = self.chars.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            nodes.push(self.parse_quantifier(atom)?);
+        }
+        Ok(Node::Concat(nodes))
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, String> {
+        let c = self.chars.next().expect("caller peeked a char");
+        match c {
+            '.' => Ok(Node::Any),
+            '^' => Ok(Node::Start),
+            '$' => Ok(Node::End),
+            '[' => self.parse_class(),
+            '(' => {
+                let index = if self.chars.next_if_eq(&'?').is_some() {
+                    if self.chars.next() != Some(':') {
+                        return Err("regex: unsupported group syntax".to_string());
+                    }
+                    None
+                } else {
+                    self.groups += 1;
+                    Some(self.groups)
+                };
+                let node = self.parse_alt()?;
+                if self.chars.next() != Some(')') {
+                    return Err("regex: unclosed group".to_string());
+                }
+                Ok(Node::Group(Box::new(node), index))
+            }
+            '\\' => match self.chars.next() {
+                Some('b') => Ok(Node::WordBoundary(true)),
+                Some('B') => Ok(Node::WordBoundary(false)),
+                Some(c) => Ok(match perl_class(c) {
+                    Some(item) => Node::Class(vec![item], false),
+                    None => Node::Char(escaped_char(c)),
+                }),
+                None => Err("regex: trailing backslash".to_string()),
+            },
+            '*' | '+' | '?' => Err(format!("regex: nothing to repeat before {}", c)),
+            c => Ok(Node::Char(c)),
+        }
+    }
+
+    fn parse_class(&mut self) -> Result<Node, String> {
+        let negated = self.chars.next_if_eq(&'^').is_some();
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let c = match self.chars.next() {
+                Some(']') if !first => return Ok(Node::Class(items, negated)),
+                Some(c) => c,
+                None => return Err("regex: unclosed character class".to_string()),
+            };
+            first = false;
+
+            let low = match c {
+                '\\' => {
+                    let c = self
+                        .chars
+                        .next()
+                        .ok_or_else(|| "regex: trailing backslash".to_string())?;
+                    if let Some(item) = perl_class(c) {
+                        items.push(item);
+                        continue;
+                    }
+                    escaped_char(c)
+                }
+                c => c,
+            };
+            let is_range = self.chars.peek() == Some(&'-')
+                && self.chars.clone().nth(1).is_some_and(|c| c != ']');
+            if !is_range {
+                items.push(ClassItem::Range(low, low));
+                continue;
+            }
+            self.chars.next();
+            let high = match self.chars.next() {
+                Some('\\') => escaped_char(
+                    self.chars
+                        .next()
+                        .ok_or_else(|| "regex: trailing backslash".to_string())?,
+                ),
+                Some(c) => c,
+                None => return Err("regex: unclosed character class".to_string()),
+            };
+            if high < low {
+                return Err(format!("regex: invalid range {}-{}", low, high));
+            }
+            items.push(ClassItem::Range(low, high));
+        }
+    }
+
+    fn parse_quantifier(&mut self, node: Node) -> Result<Node, String> {
+        let (min, max) = match self.chars.peek().copied() {
+            Some('{') => match self.parse_bounds() {
+                Some(bounds) => bounds,
+                None => return Ok(node),
+            },
+            Some(c @ ('*' | '+' | '?')) => {
+                self.chars.next();
+                match c {
+                    '*' => (0, None),
+                    '+' => (1, None),
+                    _ => (0, Some(1)),
+                }
+            }
+            _ => return Ok(node),
+        };
+        if !matches!(
+            node,
+            Node::Char(_) | Node::Any | Node::Class(..) | Node::Group(..)
+        ) {
+            return Err("regex: nothing to repeat".to_string());
+        }
+        let greedy = self.chars.next_if_eq(&'?').is_none();
+        Ok(Node::Repeat {
+            node: Box::new(node),
+            min,
+            max,
+            greedy,
+        })
+    }
+
+    /// Parses `{m}`, `{m,}` or `{m,n}`, consuming it only if well formed so
+    /// that any other `{` is an ordinary character.
+    fn parse_bounds(&mut self) -> Option<(usize, Option<usize>)> {
+        let mut lookahead = self.chars.clone();
+        lookahead.next();
+        let body: String = lookahead.by_ref().take_while(|&c| c != '}').collect();
+        let (min, max) = match body.split_once(',') {
+            None => {
+                let n = body.parse().ok()?;
+                (n, Some(n))
+            }
+            Some((min, "")) => (min.parse().ok()?, None),
+            Some((min, max)) => (min.parse().ok()?, Some(max.parse().ok()?)),
+        };
+        if max.is_some_and(|max| max < min) {
+            return None;
+        }
+        self.chars = lookahead;
+        Some((min, max))
+    }
+}
+
+fn perl_class(c: char) -> Option<ClassItem> {
+    let perl = match c.to_ascii_lowercase() {
+        'd' => Perl::Digit,
+        'w' => Perl::Word,
+        's' => Perl::Space,
+        _ => return None,
+    };
+    Some(ClassItem::Perl(perl, c.is_ascii_uppercase()))
+}
+
+fn escaped_char(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        c => c,
+    }
+}
+
+fn class_matches(items: &[ClassItem], negated: bool, c: char) -> bool {
+    let found = items.iter().any(|item| match *item {
+        ClassItem::Range(low, high) => (low..=high).contains(&c),
+        ClassItem::Perl(perl, negated) => perl.matches(c) != negated,
+    });
+    found != negated
+}
+
+/// An instruction of a compiled program. `Split` tries its first target
+/// before its second, which is how greedy and lazy repeats differ.
+#[derive(Debug)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    Start,
+    End,
+    WordBoundary(bool),
+    Save(usize),
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+fn compile(node: &Node, program: &mut Vec<Inst>) {
+    match node {
+        Node::Char(c) => program.push(Inst::Char(*c)),
+        Node::Any => program.push(Inst::Any),
+        Node::Class(items, negated) => program.push(Inst::Class(items.clone(), *negated)),
+        Node::Start => program.push(Inst::Start),
+        Node::End => program.push(Inst::End),
+        Node::WordBoundary(expected) => program.push(Inst::WordBoundary(*expected)),
+        Node::Group(node, None) => compile(node, program),
+        Node::Group(node, Some(index)) => {
+            program.push(Inst::Save(2 * index));
+            compile(node, program);
+            program.push(Inst::Save(2 * index + 1));
+        }
+        Node::Concat(nodes) => nodes.iter().for_each(|node| compile(node, program)),
+        Node::Alt(alternatives) => {
+            let mut jumps = Vec::new();
+            for (i, alt) in alternatives.iter().enumerate() {
+                if i + 1 == alternatives.len() {
+                    compile(alt, program);
+                    break;
+                }
+                let split = program.len();
+                program.push(Inst::Split(split + 1, 0));
+                compile(alt, program);
+                jumps.push(program.len());
+                program.push(Inst::Jump(0));
+                program[split] = Inst::Split(split + 1, program.len());
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+        }
+        Node::Repeat {
+            node,
+            min,
+            max,
+            greedy,
+        } => {
+            for _ in 0..*min {
+                compile(node, program);
+            }
+            let split = |program: &mut Vec<Inst>, at: usize, end: usize| {
+                program[at] = match greedy {
+                    true => Inst::Split(at + 1, end),
+                    false => Inst::Split(end, at + 1),
+                };
+            };
+            match max {
+                None => {
+                    let at = program.len();
+                    program.push(Inst::Jump(0));
+                    compile(node, program);
+                    program.push(Inst::Jump(at));
+                    split(program, at, program.len());
+                }
+                // Each optional copy nests inside the one before it, so
+                // skipping one skips the rest.
+                Some(max) => {
+                    let splits: Vec<usize> = (*min..*max)
+                        .map(|_| {
+                            let at = program.len();
+                            program.push(Inst::Jump(0));
+                            compile(node, program);
+                            at
+                        })
+                        .collect();
+                    let end = program.len();
+                    for at in splits {
+                        split(program, at, end);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The threads live at one text position, in priority order. A program
+/// counter is only added once per position, which bounds the work per
+/// character by the program's length.
+struct Threads {
+    threads: Vec<(usize, Vec<Option<usize>>)>,
+    seen: Vec<bool>,
+}
+
+impl Threads {
+    fn new(len: usize) -> Self {
+        Threads {
+            threads: Vec::new(),
+            seen: vec![false; len],
+        }
+    }
+
+    fn clear(&mut self) {
+        self.threads.clear();
+        self.seen.iter_mut().for_each(|seen| *seen = false);
+    }
+}
+
+impl Regex {
+    /// Adds a thread at `pc` to `list`, following jumps, splits, saves and
+    /// assertions at `pos` until it reaches an instruction that consumes a
+    /// char or matches.
+    fn add_thread(
+        &self,
+        list: &mut Threads,
+        pc: usize,
+        text: &[char],
+        pos: usize,
+        mut slots: Vec<Option<usize>>,
+    ) {
+        if list.seen[pc] {
+            return;
+        }
+        list.seen[pc] = true;
+        match &self.program[pc] {
+            Inst::Jump(to) => self.add_thread(list, *to, text, pos, slots),
+            Inst::Split(first, second) => {
+                self.add_thread(list, *first, text, pos, slots.clone());
+                self.add_thread(list, *second, text, pos, slots);
+            }
+            Inst::Save(slot) => {
+                slots[*slot] = Some(pos);
+                self.add_thread(list, pc + 1, text, pos, slots);
+            }
+            Inst::Start if pos == 0 => self.add_thread(list, pc + 1, text, pos, slots),
+            Inst::End if pos == text.len() => self.add_thread(list, pc + 1, text, pos, slots),
+            Inst::WordBoundary(expected) => {
+                let is_word = |c: Option<&char>| c.is_some_and(|&c| Perl::Word.matches(c));
+                let before = is_word(pos.checked_sub(1).and_then(|i| text.get(i)));
+                if (before != is_word(text.get(pos))) == *expected {
+                    self.add_thread(list, pc + 1, text, pos, slots);
+                }
+            }
+            Inst::Start | Inst::End => {}
+            _ => list.threads.push((pc, slots)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, text: &str) -> Option<Vec<Option<String>>> {
+        let text: Vec<char> = text.chars().collect();
+        let caps = Regex::new(pattern).unwrap().find_at(&text, 0)?;
+        Some(
+            caps.iter()
+                .map(|span| span.map(|(start, end)| text[start..end].iter().collect()))
+                .collect(),
+        )
+    }
+
+    fn strings(items: &[Option<&str>]) -> Option<Vec<Option<String>>> {
+        Some(items.iter().map(|s| s.map(str::to_string)).collect())
+    }
+
+    #[test]
+    fn test_regex() {
+        assert_eq!(
+            strings(&[Some("2024-01-15"), Some("2024"), Some("01"), Some("15")]),
+            find(r"(\d{4})-(\d\d)-(\d+)", "on 2024-01-15.")
+        );
+        assert_eq!(
+            strings(&[Some("<a>"), Some("a")]),
+            find("<(.+?)>", "<a><b>")
+        );
+        assert_eq!(
+            strings(&[Some("cat"), None, Some("cat")]),
+            find("(dog)|(cat)", "a cat")
+        );
+        assert_eq!(
+            strings(&[Some("foo_1")]),
+            find(r"\b[a-z_]\w*\b", "  foo_1 ")
+        );
+        assert_eq!(strings(&[Some("aaa")]), find("^(?:a|b)*$", "aaa"));
+        assert_eq!(
+            strings(&[Some("")]),
+            find("(a*)*", "b").map(|c| c[..1].to_vec())
+        );
+        assert_eq!(strings(&[Some("x{y")]), find("x{y", "x{y"));
+        assert_eq!(None, find("^b", "ab"));
+        assert_eq!(None, find("[^,]+x", "ab,c"));
+
+        let text: Vec<char> = "a1b22c".chars().collect();
+        let digits = Regex::new(r"\d+").unwrap().find_all(&text);
+        assert_eq!(
+            vec![Some((1, 2)), Some((3, 5))],
+            digits.iter().map(|caps| caps[0]).collect::<Vec<_>>()
+        );
+
+        // Patterns that take exponential time to backtrack through.
+        let text = format!("{}b", "a".repeat(37));
+        assert_eq!(None, find("(a|aa)*c", &text));
+        assert_eq!(None, find("(a*)*b", &"a".repeat(28)));
+        assert_eq!(
+            Some(text.clone()),
+            find("(a|aa)*b", &text).and_then(|caps| caps[0].clone())
+        );
+        assert_eq!(strings(&[Some("ab"), Some("")]), find("a(x{0,3}?)b", "ab"));
+
+        assert!(Regex::new("(a").is_err());
+        assert!(Regex::new("*a").is_err());
+        assert!(Regex::new("[a").is_err());
+        assert!(Regex::new("[z-a]").is_err());
+    }
+}
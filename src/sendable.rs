@@ -0,0 +1,439 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+    rc::Rc,
+    sync::Arc,
+};
+
+use crate::{
+    bigint::BigInt,
+    error::LispError,
+    eval::{Env, builtin, is_builtin},
+    parser::{Atom, Channel, Condition, EnvRef, Lambda, Object},
+    rational::Rational,
+    syntax_rules::unalias,
+};
+
+/// What a worker thread hands back: its value, or the object a `guard`
+/// would see for its error.
+pub type ThreadResult = Result<Sendable, Box<Sendable>>;
+
+/// A deep copy of an `Object` that shares nothing with the interpreter it
+/// came from, so it can be moved to another thread.
+///
+/// Procedures take a copy of their environment chain with them. Each scope
+/// is copied once, so procedures sharing a scope still share it on the other
+/// side and recursive definitions keep working. Only the bindings whose names
+/// appear in the copied code are taken along, except that a first-class
+/// environment takes all of them. Channels and atoms are shared rather than
+/// copied, and builtin procedures are sent by name. Sending fails if a value
+/// that can be none of these, such as a port, is reachable.
+pub struct Sendable {
+    value: Datum,
+    frames: Vec<Frame>,
+}
+
+enum Datum {
+    Void,
+    Eof,
+    Integer(i64),
+    BigInt(BigInt),
+    Rational(Rational),
+    Float(f64),
+    Bool(bool),
+    Symbol(String),
+    Keyword(String),
+    String(String),
+    Char(char),
+    Lambda(LambdaDatum),
+    CaseLambda(Vec<LambdaDatum>),
+    Macro(LambdaDatum),
+    Environment(usize),
+    Values(Vec<Datum>),
+    Condition {
+        kind: String,
+        message: String,
+        irritants: Vec<Datum>,
+        location: Option<Box<Datum>>,
+    },
+    Vector(Vec<Datum>),
+    Bytevector(Vec<u8>),
+    HashTable(Vec<(Datum, Datum)>),
+    Set(Vec<Datum>),
+    List(Vec<Datum>),
+    DottedList(Vec<Datum>, Box<Datum>),
+    Channel(Arc<Channel>),
+    Atom(Arc<Atom>),
+    /// A builtin procedure, sent by name.
+    Builtin(String),
+}
+
+struct LambdaDatum {
+    params: Vec<String>,
+    rest: Option<String>,
+    keys: Vec<(String, Datum)>,
+    body: Vec<Datum>,
+    env: usize,
+}
+
+/// A copied scope; `parent` indexes `Sendable::frames`.
+struct Frame {
+    vars: Vec<(String, Datum)>,
+    parent: Option<usize>,
+}
+
+impl Sendable {
+    pub fn new(obj: &Object) -> Result<Self, LispError> {
+        let mut copier = Copier {
+            frames: Vec::new(),
+            seen: HashMap::new(),
+            scopes: Vec::new(),
+            names: HashSet::new(),
+        };
+        let value = copier.copy(obj)?;
+        copier.copy_bindings()?;
+        Ok(Sendable {
+            value,
+            frames: copier.frames,
+        })
+    }
+
+    /// Rebuilds the object, with fresh scopes and containers, in the
+    /// interpreter of the current thread.
+    pub fn to_object(&self) -> Object {
+        let mut envs = vec![None; self.frames.len()];
+        for i in 0..self.frames.len() {
+            make_env(i, &self.frames, &mut envs);
+        }
+        let envs: Vec<_> = envs
+            .into_iter()
+            .map(|env| env.expect("every frame was built"))
+            .collect();
+
+        for (frame, env) in self.frames.iter().zip(&envs) {
+            for (name, datum) in &frame.vars {
+                let val = restore(datum, &envs);
+                env.borrow_mut().set(name, val);
+            }
+        }
+        restore(&self.value, &envs)
+    }
+}
+
+struct Copier {
+    frames: Vec<Frame>,
+    /// Frame indices of the scopes copied so far, by address.
+    seen: HashMap<*const RefCell<Env>, usize>,
+    /// The scope behind each frame.
+    scopes: Vec<Scope>,
+    /// Symbols appearing in the copied code.
+    names: HashSet<String>,
+}
+
+struct Scope {
+    env: Rc<RefCell<Env>>,
+    /// Whether every binding is needed rather than just the named ones.
+    all: bool,
+    copied: HashSet<String>,
+}
+
+impl Copier {
+    fn copy(&mut self, obj: &Object) -> Result<Datum, LispError> {
+        let copy_all = |copier: &mut Self, items: &[Object]| {
+            items
+                .iter()
+                .map(|item| copier.copy(item))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(match obj {
+            Object::Void => Datum::Void,
+            Object::Eof => Datum::Eof,
+            Object::Integer(n) => Datum::Integer(*n),
+            Object::BigInt(n) => Datum::BigInt(n.clone()),
+            Object::Rational(n) => Datum::Rational(n.clone()),
+            Object::Float(n) => Datum::Float(*n),
+            Object::Bool(b) => Datum::Bool(*b),
+            Object::Symbol(s) => Datum::Symbol(s.clone()),
+            Object::Keyword(s) => Datum::Keyword(s.clone()),
+            Object::String(s) => Datum::String(s.clone()),
+            Object::Char(c) => Datum::Char(*c),
+            Object::Lambda(lambda) => Datum::Lambda(self.copy_lambda(lambda)?),
+            Object::CaseLambda(clauses) => Datum::CaseLambda(
+                clauses
+                    .iter()
+                    .map(|lambda| self.copy_lambda(lambda))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Object::Macro(lambda) => Datum::Macro(self.copy_lambda(lambda)?),
+            Object::Environment(EnvRef(env)) => Datum::Environment(self.copy_frame(env, true)),
+            Object::Values(vals) => Datum::Values(copy_all(self, vals)?),
+            Object::Condition(condition) => Datum::Condition {
+                kind: condition.kind.clone(),
+                message: condition.message.clone(),
+                irritants: copy_all(self, &condition.irritants)?,
+                location: match &condition.location {
+                    Some(location) => Some(Box::new(self.copy(location)?)),
+                    None => None,
+                },
+            },
+            Object::Vector(items) => Datum::Vector(copy_all(self, &items.borrow())?),
+            Object::Bytevector(bytes) => Datum::Bytevector(bytes.borrow().clone()),
+            Object::HashTable(table) => Datum::HashTable(
+                table
+                    .borrow()
+                    .values()
+                    .map(|(k, v)| Ok((self.copy(k)?, self.copy(v)?)))
+                    .collect::<Result<_, LispError>>()?,
+            ),
+            Object::Set(set) => Datum::Set(
+                set.values()
+                    .map(|v| self.copy(v))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Object::List(items) => Datum::List(copy_all(self, items)?),
+            Object::DottedList(items, tail) => {
+                Datum::DottedList(copy_all(self, items)?, Box::new(self.copy(tail)?))
+            }
+            Object::Channel(channel) => Datum::Channel(channel.clone()),
+            Object::Atom(atom) => Datum::Atom(atom.clone()),
+            Object::NativeFn(f) if is_builtin(f) => Datum::Builtin(f.name.clone()),
+            _ => return Err(format!("Cannot send {} to another thread", obj).into()),
+        })
+    }
+
+    fn copy_lambda(&mut self, lambda: &Lambda) -> Result<LambdaDatum, LispError> {
+        lambda.body.iter().for_each(|expr| self.note_names(expr));
+        lambda
+            .keys
+            .iter()
+            .for_each(|(_, default)| self.note_names(default));
+        Ok(LambdaDatum {
+            params: lambda.params.clone(),
+            rest: lambda.rest.clone(),
+            keys: lambda
+                .keys
+                .iter()
+                .map(|(key, default)| Ok((key.clone(), self.copy(default)?)))
+                .collect::<Result<_, LispError>>()?,
+            body: lambda
+                .body
+                .iter()
+                .map(|expr| self.copy(expr))
+                .collect::<Result<_, _>>()?,
+            env: self.copy_frame(&lambda.env, false),
+        })
+    }
+
+    /// Records the symbols in `expr`, and those they are renamed from by
+    /// syntax-rules, as names the copied code may look up.
+    fn note_names(&mut self, expr: &Object) {
+        match expr {
+            Object::Symbol(s) => {
+                self.names.insert(unalias(s).to_string());
+                self.names.insert(s.clone());
+            }
+            Object::List(items) => items.iter().for_each(|item| self.note_names(item)),
+            Object::DottedList(items, tail) => {
+                items.iter().for_each(|item| self.note_names(item));
+                self.note_names(tail);
+            }
+            Object::Vector(items) => items.borrow().iter().for_each(|item| self.note_names(item)),
+            _ => {}
+        }
+    }
+
+    /// Returns the frame index of `env`, registering it and its parents.
+    /// Their bindings are copied later by `copy_bindings`; `all` marks the
+    /// whole chain as needing every binding.
+    fn copy_frame(&mut self, env: &Rc<RefCell<Env>>, all: bool) -> usize {
+        if let Some(&index) = self.seen.get(&Rc::as_ptr(env)) {
+            if all && !self.scopes[index].all {
+                self.scopes[index].all = true;
+                if let Some(parent) = env.borrow().parent() {
+                    self.copy_frame(parent, true);
+                }
+            }
+            return index;
+        }
+        let index = self.frames.len();
+        self.seen.insert(Rc::as_ptr(env), index);
+        self.frames.push(Frame {
+            vars: Vec::new(),
+            parent: None,
+        });
+        self.scopes.push(Scope {
+            env: env.clone(),
+            all,
+            copied: HashSet::new(),
+        });
+        let parent = env
+            .borrow()
+            .parent()
+            .map(|parent| self.copy_frame(parent, all));
+        self.frames[index].parent = parent;
+        index
+    }
+
+    /// Copies the bindings of the registered scopes that the copied code may
+    /// need, repeating until the values copied need nothing more.
+    fn copy_bindings(&mut self) -> Result<(), LispError> {
+        loop {
+            let mut pending = Vec::new();
+            for (index, scope) in self.scopes.iter().enumerate() {
+                for (name, val) in scope.env.borrow().bindings() {
+                    if (scope.all || self.names.contains(name)) && !scope.copied.contains(name) {
+                        pending.push((index, name.clone(), val.clone()));
+                    }
+                }
+            }
+            if pending.is_empty() {
+                return Ok(());
+            }
+            for (index, name, val) in pending {
+                let datum = self.copy(&val).map_err(|_| {
+                    format!("Cannot send {} to another thread (bound to {})", val, name)
+                })?;
+                self.scopes[index].copied.insert(name.clone());
+                self.frames[index].vars.push((name, datum));
+            }
+        }
+    }
+}
+
+fn make_env(
+    index: usize,
+    frames: &[Frame],
+    envs: &mut [Option<Rc<RefCell<Env>>>],
+) -> Rc<RefCell<Env>> {
+    if let Some(env) = &envs[index] {
+        return env.clone();
+    }
+    let env = match frames[index].parent {
+        Some(parent) => Env::extend(make_env(parent, frames, envs)),
+        None => Env::default(),
+    };
+    let env = Rc::new(RefCell::new(env));
+    envs[index] = Some(env.clone());
+    env
+}
+
+fn restore(datum: &Datum, envs: &[Rc<RefCell<Env>>]) -> Object {
+    let restore_all = |items: &[Datum]| items.iter().map(|item| restore(item, envs)).collect();
+    match datum {
+        Datum::Void => Object::Void,
+        Datum::Eof => Object::Eof,
+        Datum::Integer(n) => Object::Integer(*n),
+        Datum::BigInt(n) => Object::BigInt(n.clone()),
+        Datum::Rational(n) => Object::Rational(n.clone()),
+        Datum::Float(n) => Object::Float(*n),
+        Datum::Bool(b) => Object::Bool(*b),
+        Datum::Symbol(s) => Object::Symbol(s.clone()),
+        Datum::Keyword(s) => Object::Keyword(s.clone()),
+        Datum::String(s) => Object::String(s.clone()),
+        Datum::Char(c) => Object::Char(*c),
+        Datum::Lambda(lambda) => Object::Lambda(restore_lambda(lambda, envs)),
+        Datum::CaseLambda(clauses) => Object::CaseLambda(
+            clauses
+                .iter()
+                .map(|lambda| restore_lambda(lambda, envs))
+                .collect(),
+        ),
+        Datum::Macro(lambda) => Object::Macro(restore_lambda(lambda, envs)),
+        Datum::Environment(index) => Object::Environment(EnvRef(envs[*index].clone())),
+        Datum::Values(vals) => Object::Values(restore_all(vals)),
+        Datum::Condition {
+            kind,
+            message,
+            irritants,
+            location,
+        } => Object::Condition(Rc::new(Condition {
+            kind: kind.clone(),
+            message: message.clone(),
+            irritants: restore_all(irritants),
+            location: location.as_ref().map(|location| restore(location, envs)),
+        })),
+        Datum::Vector(items) => Object::Vector(Rc::new(RefCell::new(restore_all(items)))),
+        Datum::Bytevector(bytes) => Object::Bytevector(Rc::new(RefCell::new(bytes.clone()))),
+        Datum::HashTable(entries) => {
+            let table = entries
+                .iter()
+                .map(|(k, v)| {
+                    let (k, v) = (restore(k, envs), restore(v, envs));
+                    (k.hash_key().expect("copied keys stay hashable"), (k, v))
+                })
+                .collect::<HashMap<_, _>>();
+            Object::HashTable(Rc::new(RefCell::new(table)))
+        }
+        Datum::Set(items) => {
+            let set = items
+                .iter()
+                .map(|item| {
+                    let item = restore(item, envs);
+                    (item.hash_key().expect("copied keys stay hashable"), item)
+                })
+                .collect::<BTreeMap<_, _>>();
+            Object::Set(Rc::new(set))
+        }
+        Datum::List(items) => Object::List(restore_all(items)),
+        Datum::DottedList(items, tail) => {
+            Object::DottedList(restore_all(items), Box::new(restore(tail, envs)))
+        }
+        Datum::Channel(channel) => Object::Channel(channel.clone()),
+        Datum::Atom(atom) => Object::Atom(atom.clone()),
+        Datum::Builtin(name) => {
+            Object::NativeFn(builtin(name).expect("every thread has the same builtins"))
+        }
+    }
+}
+
+fn restore_lambda(lambda: &LambdaDatum, envs: &[Rc<RefCell<Env>>]) -> Rc<Lambda> {
+    Rc::new(Lambda {
+        params: lambda.params.clone(),
+        rest: lambda.rest.clone(),
+        keys: lambda
+            .keys
+            .iter()
+            .map(|(key, default)| (key.clone(), restore(default, envs)))
+            .collect(),
+        body: lambda.body.iter().map(|expr| restore(expr, envs)).collect(),
+        env: envs[lambda.env].clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Port, PortState};
+
+    #[test]
+    fn test_sendable_round_trip() {
+        let vector = Object::Vector(Rc::new(RefCell::new(vec![
+            Object::Integer(1),
+            Object::DottedList(vec![Object::Char('a')], Box::new(Object::Float(2.5))),
+        ])));
+        let copy = Sendable::new(&vector).unwrap().to_object();
+        assert_eq!(vector, copy);
+        let (Object::Vector(original), Object::Vector(copied)) = (&vector, &copy) else {
+            unreachable!();
+        };
+        assert!(!Rc::ptr_eq(original, copied));
+
+        let port = Object::Port(Rc::new(Port::new("string", PortState::Closed)));
+        assert!(Sendable::new(&Object::List(vec![port.clone()])).is_err());
+
+        // A port bound in a captured scope cannot be silently dropped.
+        let env = Rc::new(RefCell::new(Env::default()));
+        env.borrow_mut().set("p", port);
+        let lambda = Object::Lambda(Rc::new(Lambda {
+            params: Vec::new(),
+            rest: None,
+            keys: Vec::new(),
+            body: vec![Object::Symbol("p".to_string())],
+            env,
+        }));
+        assert_eq!(
+            "Cannot send Port(string) to another thread (bound to p)",
+            Sendable::new(&lambda).err().unwrap().to_string()
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! `Serialize` and `Deserialize` for `Object`, enabled by the `serde`
+//! feature. Only data can be serialized: procedures, environments, ports and
+//! other runtime objects are rejected.
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de, ser};
+
+use crate::{bigint::BigInt, parser::Object, rational::Rational};
+
+/// The serialized form of an `Object`. Big numbers are written as their
+/// decimal text so any format can hold them.
+#[derive(Serialize, Deserialize)]
+enum Repr {
+    Void,
+    Eof,
+    Integer(i64),
+    BigInt(String),
+    Rational(String),
+    Float(f64),
+    Bool(bool),
+    Symbol(String),
+    Keyword(String),
+    String(String),
+    Char(char),
+    Vector(Vec<Repr>),
+    Bytevector(Vec<u8>),
+    HashTable(Vec<(Repr, Repr)>),
+    Set(Vec<Repr>),
+    List(Vec<Repr>),
+    DottedList(Vec<Repr>, Box<Repr>),
+}
+
+impl Repr {
+    fn new(obj: &Object) -> Result<Repr, String> {
+        let all = |items: &[Object]| items.iter().map(Repr::new).collect::<Result<_, _>>();
+        Ok(match obj {
+            Object::Void => Repr::Void,
+            Object::Eof => Repr::Eof,
+            Object::Integer(n) => Repr::Integer(*n),
+            Object::BigInt(n) => Repr::BigInt(n.to_string()),
+            Object::Rational(n) => Repr::Rational(n.to_string()),
+            Object::Float(n) => Repr::Float(*n),
+            Object::Bool(b) => Repr::Bool(*b),
+            Object::Symbol(s) => Repr::Symbol(s.clone()),
+            Object::Keyword(s) => Repr::Keyword(s.clone()),
+            Object::String(s) => Repr::String(s.clone()),
+            Object::Char(c) => Repr::Char(*c),
+            Object::Vector(items) => Repr::Vector(all(&items.borrow())?),
+            Object::Bytevector(bytes) => Repr::Bytevector(bytes.borrow().clone()),
+            Object::HashTable(table) => Repr::HashTable(
+                table
+                    .borrow()
+                    .values()
+                    .map(|(k, v)| Ok((Repr::new(k)?, Repr::new(v)?)))
+                    .collect::<Result<_, String>>()?,
+            ),
+            Object::Set(set) => Repr::Set(set.values().map(Repr::new).collect::<Result<_, _>>()?),
+            Object::List(items) => Repr::List(all(items)?),
+            Object::DottedList(items, tail) => {
+                Repr::DottedList(all(items)?, Box::new(Repr::new(tail)?))
+            }
+            _ => return Err(format!("cannot serialize {}", obj)),
+        })
+    }
+
+    fn into_object(self) -> Result<Object, String> {
+        let all = |items: Vec<Repr>| {
+            items
+                .into_iter()
+                .map(Repr::into_object)
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let key = |obj: &Object| {
+            obj.hash_key()
+                .ok_or_else(|| format!("unhashable key: {}", obj))
+        };
+        Ok(match self {
+            Repr::Void => Object::Void,
+            Repr::Eof => Object::Eof,
+            Repr::Integer(n) => Object::Integer(n),
+            Repr::BigInt(s) => {
+                Object::BigInt(BigInt::parse(&s).ok_or_else(|| format!("invalid integer: {}", s))?)
+            }
+            Repr::Rational(s) => Object::Rational(
+                Rational::parse(&s).ok_or_else(|| format!("invalid rational: {}", s))?,
+            ),
+            Repr::Float(n) => Object::Float(n),
+            Repr::Bool(b) => Object::Bool(b),
+            Repr::Symbol(s) => Object::Symbol(s),
+            Repr::Keyword(s) => Object::Keyword(s),
+            Repr::String(s) => Object::String(s),
+            Repr::Char(c) => Object::Char(c),
+            Repr::Vector(items) => Object::Vector(Rc::new(RefCell::new(all(items)?))),
+            Repr::Bytevector(bytes) => Object::Bytevector(Rc::new(RefCell::new(bytes))),
+            Repr::HashTable(entries) => {
+                let mut table = HashMap::new();
+                for (k, v) in entries {
+                    let (k, v) = (k.into_object()?, v.into_object()?);
+                    table.insert(key(&k)?, (k, v));
+                }
+                Object::HashTable(Rc::new(RefCell::new(table)))
+            }
+            Repr::Set(items) => Object::Set(Rc::new(
+                all(items)?
+                    .into_iter()
+                    .map(|item| Ok((key(&item)?, item)))
+                    .collect::<Result<_, String>>()?,
+            )),
+            Repr::List(items) => Object::List(all(items)?),
+            Repr::DottedList(items, tail) => Object::dotted(all(items)?, tail.into_object()?),
+        })
+    }
+}
+
+impl Serialize for Object {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Repr::new(self)
+            .map_err(ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Object {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Repr::deserialize(deserializer)?
+            .into_object()
+            .map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        eval::Env,
+        parser::{Port, PortState},
+    };
+
+    #[test]
+    fn test_serde_round_trip() {
+        let mut env = Rc::new(RefCell::new(Env::new()));
+        let value = crate::eval_str(
+            "(list 1 (expt 10 30) 1/3 2.5 #t 'sym \"str\" #\\c (vector 1 2) '(a . b)
+                   (let ((h (make-hash-table))) (hash-set! h \"k\" 'v) h))",
+            &mut env,
+        )
+        .unwrap();
+        let json = serde_json::to_string(&value).unwrap();
+        let copy: Object = serde_json::from_str(&json).unwrap();
+        assert_eq!(value.to_string(), copy.to_string());
+
+        let port = Object::Port(Rc::new(Port::new("string", PortState::Closed)));
+        assert!(serde_json::to_string(&Object::List(vec![port])).is_err());
+        assert!(serde_json::from_str::<Object>(r#"{"BigInt":"x"}"#).is_err());
+    }
+}
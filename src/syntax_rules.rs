@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+
+use crate::{eval::fresh_symbol, parser::Object};
+
+const ELLIPSIS: &str = "...";
+
+/// Symbols with a fixed syntactic meaning inside other forms. They are never
+/// renamed, since `cond`, `case`, `lambda` etc. compare against them by name.
+const SYNTACTIC_KEYWORDS: &[&str] = &["else", "=>", "_", ELLIPSIS];
+
+/// A `syntax-rules` transformer: a list of `(pattern template)` rules and the
+/// literal identifiers that must match exactly.
+#[derive(Debug, PartialEq)]
+pub struct SyntaxRules {
+    literals: Vec<String>,
+    rules: Vec<(Object, Object)>,
+}
+
+#[derive(Clone)]
+enum Binding {
+    One(Object),
+    Many(Vec<Binding>),
+}
+
+impl SyntaxRules {
+    /// Builds a transformer from the arguments of `(syntax-rules (literals...) rules...)`.
+    pub fn new(list: &[Object]) -> Result<Self, String> {
+        let literals = match list.get(1) {
+            Some(Object::List(literals)) => literals
+                .iter()
+                .map(|lit| match lit {
+                    Object::Symbol(s) => Ok(s.clone()),
+                    _ => Err(format!("Invalid syntax-rules literal: {}", lit)),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => return Err("Invalid syntax-rules literals".to_string()),
+        };
+
+        let rules = list[2..]
+            .iter()
+            .map(|rule| match rule {
+                Object::List(rule) if rule.len() == 2 => match &rule[0] {
+                    Object::List(pattern) | Object::DottedList(pattern, _)
+                        if !pattern.is_empty() =>
+                    {
+                        Ok((rule[0].clone(), rule[1].clone()))
+                    }
+                    _ => Err(format!("Invalid syntax-rules pattern: {}", rule[0])),
+                },
+                _ => Err(format!("Invalid syntax-rules rule: {}", rule)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(SyntaxRules { literals, rules })
+    }
+
+    /// Rewrites `form` using the first rule whose pattern matches it. Symbols
+    /// introduced by the template are renamed to fresh names so they cannot
+    /// capture or be captured by identifiers at the use site.
+    pub fn expand(&self, form: &[Object]) -> Result<Object, String> {
+        let args = Object::List(form[1..].to_vec());
+        for (pattern, template) in &self.rules {
+            // The keyword position is ignored.
+            let pattern = match pattern {
+                Object::List(pattern) => Object::List(pattern[1..].to_vec()),
+                Object::DottedList(pattern, tail) => {
+                    Object::dotted(pattern[1..].to_vec(), (**tail).clone())
+                }
+                _ => continue,
+            };
+            let mut bindings = HashMap::new();
+            if self.match_pattern(&pattern, &args, &mut bindings) {
+                let mut renames = HashMap::new();
+                return expand_template(template, &bindings, Some(&mut renames));
+            }
+        }
+        Err(format!(
+            "No syntax-rules pattern matches: {}",
+            Object::List(form.to_vec())
+        ))
+    }
+
+    fn match_pattern(
+        &self,
+        pattern: &Object,
+        form: &Object,
+        bindings: &mut HashMap<String, Binding>,
+    ) -> bool {
+        match pattern {
+            Object::Symbol(s) if s == "_" => true,
+            Object::Symbol(s) if self.literals.contains(s) => {
+                matches!(form, Object::Symbol(f) if unalias(f) == s)
+            }
+            Object::Symbol(s) => {
+                bindings.insert(s.clone(), Binding::One(form.clone()));
+                true
+            }
+            Object::List(patterns) => match form {
+                Object::List(items) => self.match_list(patterns, items, bindings),
+                _ => false,
+            },
+            Object::DottedList(patterns, tail) => {
+                let (items, rest) = match form {
+                    Object::List(items) => (items, Object::List(Vec::new())),
+                    Object::DottedList(items, rest) => (items, (**rest).clone()),
+                    _ => return false,
+                };
+                items.len() >= patterns.len()
+                    && self.match_list(patterns, &items[..patterns.len()], bindings)
+                    && self.match_pattern(
+                        tail,
+                        &Object::dotted(items[patterns.len()..].to_vec(), rest),
+                        bindings,
+                    )
+            }
+            _ => pattern == form,
+        }
+    }
+
+    fn match_list(
+        &self,
+        patterns: &[Object],
+        items: &[Object],
+        bindings: &mut HashMap<String, Binding>,
+    ) -> bool {
+        let ellipsis = patterns
+            .iter()
+            .position(|p| *p == Object::Symbol(ELLIPSIS.to_string()));
+
+        let Some(index) = ellipsis.filter(|&i| i > 0) else {
+            return patterns.len() == items.len()
+                && patterns
+                    .iter()
+                    .zip(items)
+                    .all(|(p, f)| self.match_pattern(p, f, bindings));
+        };
+
+        let repeated = &patterns[index - 1];
+        let before = &patterns[..index - 1];
+        let after = &patterns[index + 1..];
+        if items.len() < before.len() + after.len() {
+            return false;
+        }
+
+        let tail_start = items.len() - after.len();
+        if !self.match_list(before, &items[..before.len()], bindings)
+            || !self.match_list(after, &items[tail_start..], bindings)
+        {
+            return false;
+        }
+
+        let mut matches = Vec::new();
+        for item in &items[before.len()..tail_start] {
+            let mut sub = HashMap::new();
+            if !self.match_pattern(repeated, item, &mut sub) {
+                return false;
+            }
+            matches.push(sub);
+        }
+
+        let mut vars = Vec::new();
+        self.pattern_vars(repeated, &mut vars);
+        for var in vars {
+            let seq = matches
+                .iter_mut()
+                .filter_map(|sub| sub.remove(&var))
+                .collect();
+            bindings.insert(var, Binding::Many(seq));
+        }
+        true
+    }
+
+    fn pattern_vars(&self, pattern: &Object, vars: &mut Vec<String>) {
+        match pattern {
+            Object::Symbol(s) if s != "_" && s != ELLIPSIS && !self.literals.contains(s) => {
+                vars.push(s.clone())
+            }
+            Object::List(patterns) => {
+                for p in patterns {
+                    self.pattern_vars(p, vars);
+                }
+            }
+            Object::DottedList(patterns, tail) => {
+                for p in patterns {
+                    self.pattern_vars(p, vars);
+                }
+                self.pattern_vars(tail, vars);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn expand_template(
+    template: &Object,
+    bindings: &HashMap<String, Binding>,
+    mut renames: Option<&mut HashMap<String, String>>,
+) -> Result<Object, String> {
+    match template {
+        Object::Symbol(s) => match bindings.get(s) {
+            Some(Binding::One(obj)) => Ok(obj.clone()),
+            Some(Binding::Many(_)) => Err(format!("Pattern variable {} used without ...", s)),
+            None => match renames {
+                Some(renames) if !SYNTACTIC_KEYWORDS.contains(&s.as_str()) => Ok(Object::Symbol(
+                    renames
+                        .entry(s.clone())
+                        .or_insert_with(|| fresh_symbol(s))
+                        .clone(),
+                )),
+                _ => Ok(template.clone()),
+            },
+        },
+        Object::List(items) => {
+            // Quoted data is substituted but never renamed.
+            if items.first() == Some(&Object::Symbol("quote".to_string())) {
+                renames = None;
+            }
+
+            let mut result = Vec::new();
+            let mut i = 0;
+            while i < items.len() {
+                let followed_by_ellipsis =
+                    items.get(i + 1) == Some(&Object::Symbol(ELLIPSIS.to_string()));
+                if !followed_by_ellipsis {
+                    result.push(expand_template(
+                        &items[i],
+                        bindings,
+                        renames.as_deref_mut(),
+                    )?);
+                    i += 1;
+                    continue;
+                }
+
+                for iteration in ellipsis_bindings(&items[i], bindings)? {
+                    result.push(expand_template(
+                        &items[i],
+                        &iteration,
+                        renames.as_deref_mut(),
+                    )?);
+                }
+                i += 2;
+            }
+            Ok(Object::List(result))
+        }
+        Object::DottedList(items, tail) => {
+            let Object::List(items) = expand_template(
+                &Object::List(items.clone()),
+                bindings,
+                renames.as_deref_mut(),
+            )?
+            else {
+                unreachable!("list templates expand to lists");
+            };
+            let tail = expand_template(tail, bindings, renames)?;
+            Ok(Object::dotted(items, tail))
+        }
+        _ => Ok(template.clone()),
+    }
+}
+
+/// Builds one set of bindings per iteration for a template element followed
+/// by `...`, stepping every sequence variable it mentions in lockstep.
+fn ellipsis_bindings(
+    template: &Object,
+    bindings: &HashMap<String, Binding>,
+) -> Result<Vec<HashMap<String, Binding>>, String> {
+    let mut names = Vec::new();
+    template_symbols(template, &mut names);
+
+    let mut len = None;
+    for name in &names {
+        if let Some(Binding::Many(seq)) = bindings.get(name) {
+            match len {
+                Some(n) if n != seq.len() => {
+                    return Err("Mismatched ellipsis lengths in template".to_string());
+                }
+                _ => len = Some(seq.len()),
+            }
+        }
+    }
+    let len = len.ok_or_else(|| "Template ... follows no pattern variable".to_string())?;
+
+    Ok((0..len)
+        .map(|i| {
+            let mut iteration = bindings.clone();
+            for name in &names {
+                if let Some(Binding::Many(seq)) = bindings.get(name) {
+                    iteration.insert(name.clone(), seq[i].clone());
+                }
+            }
+            iteration
+        })
+        .collect())
+}
+
+fn template_symbols(template: &Object, names: &mut Vec<String>) {
+    match template {
+        Object::Symbol(s) => names.push(s.clone()),
+        Object::List(items) => {
+            for item in items {
+                template_symbols(item, names);
+            }
+        }
+        Object::DottedList(items, tail) => {
+            for item in items {
+                template_symbols(item, names);
+            }
+            template_symbols(tail, names);
+        }
+        _ => {}
+    }
+}
+
+/// Strips the suffix `fresh_symbol` adds to renamed identifiers.
+pub fn unalias(s: &str) -> &str {
+    match s.rsplit_once('%') {
+        Some((base, n))
+            if !base.is_empty() && !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            base
+        }
+        _ => s,
+    }
+}
@@ -0,0 +1,35 @@
+use std::{
+    env::consts::{DLL_PREFIX, DLL_SUFFIX},
+    path::PathBuf,
+};
+
+use lisp_rs::{Interpreter, parser::Object};
+
+/// The `greeter_plugin` example, which `cargo test` builds alongside this
+/// test as `target/<profile>/examples/libgreeter_plugin.so` or similar.
+fn plugin_path() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    let profile_dir = exe.parent().and_then(|deps| deps.parent()).unwrap();
+    profile_dir
+        .join("examples")
+        .join(format!("{}greeter_plugin{}", DLL_PREFIX, DLL_SUFFIX))
+}
+
+#[test]
+fn test_load_plugin() {
+    let path = plugin_path();
+    assert!(
+        path.exists(),
+        "{} is missing; build it with `cargo test --features plugins`",
+        path.display()
+    );
+
+    let mut interp = Interpreter::new();
+    let load = format!("(load-plugin {:?})", path.to_str().unwrap());
+    assert_eq!(Ok(Object::from("greeter")), interp.eval_str(&load));
+    assert_eq!(
+        Ok(Object::from("hello, lisp")),
+        interp.eval_str("(greet \"lisp\")")
+    );
+    assert!(interp.eval_str("(greet 1)").is_err());
+}